@@ -0,0 +1,128 @@
+//! Times building the parse table for long rule chains like
+//! `examples/long.gram`; run with `cargo bench`. On plain chains the time
+//! per rule should stay flat as the chain grows. In a nullable chain the
+//! FIRST sets and the table grow with the square of its length. Every
+//! timed result is checked against the sets the chain must have.
+
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+use chomchom::analysis::GrammarAnalysis;
+use chomchom::grammar::{GrammarChomsky, Rstr};
+use chomchom::parser_generator::Parser;
+use chomchom::{parser, tokeniser};
+
+const RUNS: u32 = 5;
+
+fn chomsky(source: &str) -> GrammarChomsky {
+    let ebnf = parser::parse(tokeniser::tokenize(source))
+        .expect("Failed to parse grammar");
+    ebnf.to_chomsky()
+}
+
+/// `N0 = N1 ; N1 = N2 ; … ; Nn = "a" ;`
+fn chain(len: usize) -> String {
+    let mut out = String::new();
+    for i in 0..len {
+        out.push_str(&format!("N{} = N{} ;\n", i, i + 1));
+    }
+    out.push_str(&format!("N{} = \"a\" ;\n", len));
+    out
+}
+
+/// A chain in which every nonterminal is nullable, so FOLLOW has to flow
+/// down the whole chain as well: `N0 = [ N1 ] "x0" ; …`.
+fn nullable_chain(len: usize) -> String {
+    let mut out = String::new();
+    for i in 0..len {
+        out.push_str(&format!("N{} = [ N{} ] \"x{}\" ;\n", i, i + 1, i));
+    }
+    out.push_str(&format!("N{} = \"a\" ;\n", len));
+    out
+}
+
+fn set(items: impl IntoIterator<Item = String>) -> BTreeSet<Rstr> {
+    items.into_iter().map(Rstr::from).collect()
+}
+
+/// Every `Ni` only starts with `"a"` and is only followed by the end of
+/// input.
+fn check_chain(len: usize, analysis: &GrammarAnalysis) {
+    for i in 0..=len {
+        let n = format!("N{}", i);
+        assert_eq!(analysis.first(&n), set(["a".to_string()]), "{}", n);
+        assert_eq!(analysis.follow(&n), set(["".to_string()]), "{}", n);
+    }
+}
+
+/// `Ni` starts with `"a"` or any of `"xi"` … `"x{len - 1}"` and `N{i + 1}`
+/// is followed by `"xi"`.
+fn check_nullable_chain(len: usize, analysis: &GrammarAnalysis) {
+    for i in 0..=len {
+        let n = format!("N{}", i);
+        let first = (i..len).map(|j| format!("x{}", j));
+        assert_eq!(
+            analysis.first(&n),
+            set(first.chain(["a".to_string()])),
+            "{}",
+            n
+        );
+        let follow = match i {
+            0 => "".to_string(),
+            _ => format!("x{}", i - 1),
+        };
+        assert_eq!(analysis.follow(&n), set([follow]), "{}", n);
+    }
+    assert!(analysis.table().conflicts().is_empty());
+}
+
+fn time(
+    grammar: &GrammarChomsky,
+    check: impl Fn(&GrammarAnalysis),
+) -> Duration {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let grammar = grammar.clone();
+        let start = Instant::now();
+        let parser = Parser::new(grammar).expect("Failed to generate parser");
+        best = best.min(start.elapsed());
+        check(parser.analysis());
+    }
+    best
+}
+
+fn report(
+    name: &str,
+    grammar: &GrammarChomsky,
+    check: impl Fn(&GrammarAnalysis),
+) {
+    let t = time(grammar, check);
+    let rules = grammar.rules.len();
+    println!(
+        "{:<24} {:>6} rules {:>10.3} ms {:>8.3} µs/rule",
+        name,
+        rules,
+        t.as_secs_f64() * 1e3,
+        t.as_secs_f64() * 1e6 / rules as f64
+    );
+}
+
+fn main() {
+    let long = std::fs::read_to_string("examples/long.gram")
+        .expect("Failed to read grammar.");
+    report("examples/long.gram", &chomsky(&long), |a| {
+        assert!(a.table().conflicts().is_empty())
+    });
+    for len in [500, 1000, 2000, 4000] {
+        report(&format!("chain {}", len), &chomsky(&chain(len)), |a| {
+            check_chain(len, a)
+        });
+    }
+    for len in [250, 500, 1000, 2000] {
+        report(
+            &format!("nullable chain {}", len),
+            &chomsky(&nullable_chain(len)),
+            |a| check_nullable_chain(len, a),
+        );
+    }
+}
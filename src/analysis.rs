@@ -0,0 +1,686 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    error::Error,
+    fmt::{Display, Write},
+};
+
+use crate::grammar::{capitalize_first_letter, GrammarChomsky, Rstr};
+use crate::serialize::json_escape;
+use crate::symbols::{
+    BitSet, InternedGrammar, Symbol, SymbolId, SymbolTable, END,
+};
+
+/// FIRST sets, without ε, which is tracked by the nullable flags instead.
+#[derive(Debug, Clone)]
+pub(crate) struct FirstSets {
+    pub(crate) nonterms: Vec<BitSet>,
+    pub(crate) nullable: Vec<bool>,
+    rules: Vec<BitSet>,
+    rules_nullable: Vec<bool>,
+}
+
+/// A FIFO queue of indices in which every index is queued at most once.
+struct Worklist {
+    queue: VecDeque<usize>,
+    queued: Vec<bool>,
+}
+
+impl Worklist {
+    fn new(len: usize) -> Worklist {
+        Worklist {
+            queue: VecDeque::new(),
+            queued: vec![false; len],
+        }
+    }
+
+    fn push(&mut self, i: usize) {
+        if !self.queued[i] {
+            self.queued[i] = true;
+            self.queue.push_back(i);
+        }
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        let i = self.queue.pop_front()?;
+        self.queued[i] = false;
+        Some(i)
+    }
+}
+
+/// The strongly connected components of a graph given by its adjacency
+/// lists, every component before the components it has edges to
+/// (Tarjan's algorithm, without recursion so long chains fit the stack).
+pub(crate) fn sccs(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    const UNSEEN: usize = usize::MAX;
+    let mut index = vec![UNSEEN; edges.len()];
+    let mut low = vec![0; edges.len()];
+    let mut on_stack = vec![false; edges.len()];
+    let mut stack = vec![];
+    let mut components = vec![];
+    let mut next = 0;
+    for root in 0..edges.len() {
+        if index[root] != UNSEEN {
+            continue;
+        }
+        // The nodes being visited, with the next edge to follow.
+        let mut calls = vec![(root, 0)];
+        index[root] = next;
+        low[root] = next;
+        next += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        while let Some((v, e)) = calls.last_mut() {
+            let v = *v;
+            if let Some(&w) = edges[v].get(*e) {
+                *e += 1;
+                if index[w] == UNSEEN {
+                    index[w] = next;
+                    low[w] = next;
+                    next += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    calls.push((w, 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
+                continue;
+            }
+            calls.pop();
+            if let Some((u, _)) = calls.last() {
+                low[*u] = low[*u].min(low[v]);
+            }
+            if low[v] == index[v] {
+                let mut component = vec![];
+                loop {
+                    let w = stack.pop().expect("v is on the stack");
+                    on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+    // Tarjan finds a component after everything reachable from it.
+    components.reverse();
+    components
+}
+
+/// Which nonterminals appear on the right side of the rules of each
+/// nonterminal.
+fn dependencies(grammar: &InternedGrammar) -> Vec<Vec<usize>> {
+    let mut edges = vec![vec![]; grammar.nonterminals.len()];
+    for (l, r) in grammar.rules.iter() {
+        for s in r.iter() {
+            if let Symbol::Non(n) = s {
+                edges[l.index()].push(n.index());
+            }
+        }
+    }
+    edges
+}
+
+/// The nonterminals are solved one strongly connected component at a time,
+/// components the others depend on first. Within a component a rule is
+/// revisited only when the FIRST set or nullability of a nonterminal on its
+/// right side changes.
+pub(crate) fn get_first_sets(grammar: &InternedGrammar) -> FirstSets {
+    let width = grammar.terminals.len();
+    let mut first = FirstSets {
+        nonterms: vec![BitSet::new(width); grammar.nonterminals.len()],
+        nullable: vec![false; grammar.nonterminals.len()],
+        rules: vec![BitSet::new(width); grammar.rules.len()],
+        rules_nullable: vec![false; grammar.rules.len()],
+    };
+    // The rules of a nonterminal and the rules that use it.
+    let mut rules_of = vec![vec![]; grammar.nonterminals.len()];
+    let mut users = vec![vec![]; grammar.nonterminals.len()];
+    for (i, (l, r)) in grammar.rules.iter().enumerate() {
+        rules_of[l.index()].push(i);
+        for s in r.iter() {
+            if let Symbol::Non(n) = s {
+                users[n.index()].push(i);
+            }
+        }
+    }
+    let mut component_of = vec![0; grammar.nonterminals.len()];
+    let mut worklist = Worklist::new(grammar.rules.len());
+    for (c, component) in sccs(&dependencies(grammar)).iter().rev().enumerate()
+    {
+        for n in component.iter() {
+            component_of[*n] = c;
+            for i in rules_of[*n].iter() {
+                worklist.push(*i);
+            }
+        }
+        while let Some(i) = worklist.pop() {
+            let (l, r) = &grammar.rules[i];
+            let mut nullable = true;
+            for s in r.iter() {
+                match *s {
+                    Symbol::Term(t) => {
+                        first.rules[i].insert(t);
+                        nullable = false;
+                        break;
+                    }
+                    Symbol::Non(n) => {
+                        first.rules[i].union_with(&first.nonterms[n.index()]);
+                        if !first.nullable[n.index()] {
+                            nullable = false;
+                            break;
+                        }
+                    }
+                    Symbol::Epsilon => {}
+                }
+            }
+            first.rules_nullable[i] = nullable;
+            let mut changed =
+                first.nonterms[l.index()].union_with(&first.rules[i]);
+            if nullable && !first.nullable[l.index()] {
+                first.nullable[l.index()] = true;
+                changed = true;
+            }
+            if changed {
+                for u in users[l.index()].iter() {
+                    let user = grammar.rules[*u].0;
+                    if component_of[user.index()] == c {
+                        worklist.push(*u);
+                    }
+                }
+            }
+        }
+    }
+    first
+}
+
+/// End of input ([`END`]) is in the follow of every nonterminal in
+/// `entries`.
+///
+/// With FIRST known, each occurrence of a nonterminal `n` in a rule for `l`
+/// adds a fixed set to FOLLOW(`n`), and FOLLOW(`l`) too if the rest of the
+/// rule is nullable. The latter are edges along which FOLLOW sets are
+/// passed on, one strongly connected component at a time, as all its
+/// members share one FOLLOW set.
+fn get_follow_sets(
+    grammar: &InternedGrammar,
+    first: &FirstSets,
+    entries: &[SymbolId],
+) -> Vec<BitSet> {
+    let width = grammar.terminals.len();
+    let mut follow = vec![BitSet::new(width); grammar.nonterminals.len()];
+    for entry in entries {
+        follow[entry.index()].insert(END);
+    }
+    let mut edges = vec![vec![]; grammar.nonterminals.len()];
+    for (l, r) in grammar.rules.iter() {
+        // What can follow the symbol being looked at, apart from FOLLOW(l)
+        // if `to_end` is set.
+        let mut trailer = BitSet::new(width);
+        let mut to_end = true;
+        for s in r.iter().rev() {
+            match *s {
+                Symbol::Term(t) => {
+                    trailer = BitSet::new(width);
+                    trailer.insert(t);
+                    to_end = false;
+                }
+                Symbol::Non(n) => {
+                    follow[n.index()].union_with(&trailer);
+                    if to_end {
+                        edges[l.index()].push(n.index());
+                    }
+                    if first.nullable[n.index()] {
+                        trailer.union_with(&first.nonterms[n.index()]);
+                    } else {
+                        trailer = first.nonterms[n.index()].clone();
+                        to_end = false;
+                    }
+                }
+                Symbol::Epsilon => {}
+            }
+        }
+    }
+    for component in sccs(&edges) {
+        let mut shared = BitSet::new(width);
+        for n in component.iter() {
+            shared.union_with(&follow[*n]);
+        }
+        for n in component.iter() {
+            follow[*n] = shared.clone();
+            for m in edges[*n].iter() {
+                follow[*m].union_with(&shared);
+            }
+        }
+    }
+    follow
+}
+
+const NO_RULE: u32 = u32::MAX;
+
+/// The LL(1) parse table: which production to use for a nonterminal given
+/// the lookahead terminal. End of input is the terminal `""`.
+/// Nonterminals can be named as in the EBNF grammar or capitalised.
+#[derive(Debug, Clone, Default)]
+pub struct ParseTable {
+    nonterminals: SymbolTable,
+    terminals: SymbolTable,
+    // Nonterminals that have rules, undefined ones are only referenced.
+    defined: Vec<bool>,
+    // Row major, one row per nonterminal.
+    cells: Vec<u32>,
+    // Every rule that wanted a cell, for cells wanted by more than one.
+    conflicts: BTreeMap<(SymbolId, SymbolId), BTreeSet<usize>>,
+}
+
+impl ParseTable {
+    pub fn get(&self, nonterm: &str, terminal: &str) -> Option<usize> {
+        self.cell(
+            self.nonterminals.get(&capitalize_first_letter(nonterm))?,
+            self.terminals.get(terminal)?,
+        )
+    }
+
+    pub fn contains(&self, nonterm: &str) -> bool {
+        self.nonterminals
+            .get(&capitalize_first_letter(nonterm))
+            .is_some_and(|n| self.defined[n.index()])
+    }
+
+    /// The terminals that have an entry in the row of `nonterm`, sorted.
+    pub fn lookaheads(&self, nonterm: &str) -> Vec<Rstr> {
+        let Some(n) = self.nonterminals.get(&capitalize_first_letter(nonterm))
+        else {
+            return vec![];
+        };
+        let mut lookaheads: Vec<Rstr> = self
+            .terminals
+            .iter()
+            .filter(|(t, _)| self.cell(n, *t).is_some())
+            .map(|(_, name)| name.clone())
+            .collect();
+        lookaheads.sort();
+        lookaheads
+    }
+
+    /// The nonterminals that have rules, sorted.
+    pub fn nonterminals(&self) -> impl Iterator<Item = &Rstr> {
+        let mut names: Vec<&Rstr> = self
+            .nonterminals
+            .iter()
+            .filter(|(n, _)| self.defined[n.index()])
+            .map(|(_, name)| name)
+            .collect();
+        names.sort();
+        names.into_iter()
+    }
+
+    /// Whether `nonterm` has rules, rather than only being referenced.
+    pub(crate) fn defines(&self, nonterm: SymbolId) -> bool {
+        self.defined[nonterm.index()]
+    }
+
+    pub(crate) fn cell(
+        &self,
+        nonterm: SymbolId,
+        terminal: SymbolId,
+    ) -> Option<usize> {
+        let rule = self.cells
+            [nonterm.index() * self.terminals.len() + terminal.index()];
+        (rule != NO_RULE).then_some(rule as usize)
+    }
+
+    /// Every production that belongs to the cell, more than one if it is
+    /// a conflict. The one [`ParseTable::get`] returns is last.
+    pub fn rules(&self, nonterm: &str, terminal: &str) -> Vec<usize> {
+        let (Some(n), Some(t)) = (
+            self.nonterminals.get(&capitalize_first_letter(nonterm)),
+            self.terminals.get(terminal),
+        ) else {
+            return vec![];
+        };
+        match self.conflicts.get(&(n, t)) {
+            Some(rules) => rules.iter().copied().collect(),
+            None => self.cell(n, t).into_iter().collect(),
+        }
+    }
+
+    /// The cells with more than one production, as (nonterminal, terminal,
+    /// productions).
+    pub fn conflicts(&self) -> Vec<(Rstr, Rstr, Vec<usize>)> {
+        self.conflicts
+            .iter()
+            .map(|((n, t), rules)| {
+                (
+                    self.nonterminals.name(*n).clone(),
+                    self.terminals.name(*t).clone(),
+                    rules.iter().copied().collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn set(&mut self, nonterm: SymbolId, terminal: SymbolId, rule: usize) {
+        let cell = nonterm.index() * self.terminals.len() + terminal.index();
+        let old = self.cells[cell];
+        if old != NO_RULE && old as usize != rule {
+            self.conflicts
+                .entry((nonterm, terminal))
+                .or_insert_with(|| BTreeSet::from([old as usize]))
+                .insert(rule);
+        }
+        self.cells[cell] = rule as u32;
+    }
+}
+
+fn gen_parsetable(
+    grammar: &InternedGrammar,
+    first: &FirstSets,
+    follow: &[BitSet],
+) -> ParseTable {
+    let mut pt = ParseTable {
+        nonterminals: grammar.nonterminals.clone(),
+        terminals: grammar.terminals.clone(),
+        defined: vec![false; grammar.nonterminals.len()],
+        cells: vec![
+            NO_RULE;
+            grammar.nonterminals.len() * grammar.terminals.len()
+        ],
+        conflicts: BTreeMap::new(),
+    };
+    // Later rules overwrite earlier ones on conflicts.
+    for (i, (l, _)) in grammar.rules.iter().enumerate() {
+        pt.defined[l.index()] = true;
+        if first.rules_nullable[i] {
+            for t in follow[l.index()].iter() {
+                pt.set(*l, t, i);
+            }
+        }
+        for t in first.rules[i].iter() {
+            pt.set(*l, t, i);
+        }
+    }
+    pt
+}
+
+/// Nonterminals that derive at least one string of terminals. A rule is
+/// productive once all nonterminals on its right side are, so every rule
+/// keeps count of those that are not yet.
+pub(crate) fn productive_nonterms(grammar: &InternedGrammar) -> BitSet {
+    let mut productive = BitSet::new(grammar.nonterminals.len());
+    let mut missing = vec![0; grammar.rules.len()];
+    let mut users = vec![vec![]; grammar.nonterminals.len()];
+    for (i, (_, r)) in grammar.rules.iter().enumerate() {
+        for s in r.iter() {
+            if let Symbol::Non(n) = s {
+                missing[i] += 1;
+                users[n.index()].push(i);
+            }
+        }
+    }
+    let mut done: Vec<SymbolId> = vec![];
+    for (i, (l, _)) in grammar.rules.iter().enumerate() {
+        if missing[i] == 0 && productive.insert(*l) {
+            done.push(*l);
+        }
+    }
+    while let Some(n) = done.pop() {
+        for i in users[n.index()].iter() {
+            missing[*i] -= 1;
+            let l = grammar.rules[*i].0;
+            if missing[*i] == 0 && productive.insert(l) {
+                done.push(l);
+            }
+        }
+    }
+    productive
+}
+
+/// Nullability, FIRST and FOLLOW sets and the LL(1) parse table of a
+/// grammar. In the returned sets `""` stands for ε in FIRST and for the end
+/// of input in FOLLOW, like in the parse table. Nonterminals can be named as
+/// in the EBNF grammar or capitalised.
+#[derive(Debug, Clone)]
+pub struct GrammarAnalysis {
+    pub(crate) grammar: InternedGrammar,
+    pub(crate) first: FirstSets,
+    follow: Vec<BitSet>,
+    pub(crate) table: ParseTable,
+    entries: Vec<Rstr>,
+}
+
+impl GrammarAnalysis {
+    pub fn new(
+        grammar: &GrammarChomsky,
+    ) -> Result<GrammarAnalysis, Box<dyn Error>> {
+        GrammarAnalysis::with_entries(grammar, &[&grammar.start_nonterm])
+    }
+
+    /// End of input is in the FOLLOW set of each of the `entries`, named as
+    /// in the EBNF grammar.
+    pub fn with_entries(
+        grammar: &GrammarChomsky,
+        entries: &[&str],
+    ) -> Result<GrammarAnalysis, Box<dyn Error>> {
+        let interned = InternedGrammar::new(grammar);
+        let entries = entries
+            .iter()
+            .map(|e| {
+                let e = capitalize_first_letter(e);
+                if grammar.rules.iter().any(|(l, _)| *l == e) {
+                    Ok(e)
+                } else {
+                    Err(format!("No rules for entry {}", e))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let entry_ids: Vec<SymbolId> = entries
+            .iter()
+            .map(|e| interned.nonterminals.get(e).expect("has rules"))
+            .collect();
+        let first = get_first_sets(&interned);
+        let follow = get_follow_sets(&interned, &first, &entry_ids);
+        let table = gen_parsetable(&interned, &first, &follow);
+        Ok(GrammarAnalysis {
+            grammar: interned,
+            first,
+            follow,
+            table,
+            entries,
+        })
+    }
+
+    pub fn entries(&self) -> &[Rstr] {
+        &self.entries
+    }
+
+    /// All nonterminals, in the order of their first rule.
+    pub fn nonterminals(&self) -> impl Iterator<Item = &Rstr> {
+        self.grammar.nonterminals.iter().map(|(_, n)| n)
+    }
+
+    /// The nonterminals that derive ε.
+    pub fn nullable(&self) -> Vec<&Rstr> {
+        self.grammar
+            .nonterminals
+            .iter()
+            .filter(|(n, _)| self.first.nullable[n.index()])
+            .map(|(_, name)| name)
+            .collect()
+    }
+
+    pub fn is_nullable(&self, nonterm: &str) -> bool {
+        self.grammar
+            .nonterminals
+            .get(&capitalize_first_letter(nonterm))
+            .is_some_and(|n| self.first.nullable[n.index()])
+    }
+
+    pub fn first(&self, nonterm: &str) -> BTreeSet<Rstr> {
+        match self
+            .grammar
+            .nonterminals
+            .get(&capitalize_first_letter(nonterm))
+        {
+            Some(n) => self.names(
+                &self.first.nonterms[n.index()],
+                self.first.nullable[n.index()],
+            ),
+            None => BTreeSet::new(),
+        }
+    }
+
+    /// FIRST of the right side of production `rule`.
+    pub fn rule_first(&self, rule: usize) -> BTreeSet<Rstr> {
+        self.names(&self.first.rules[rule], self.first.rules_nullable[rule])
+    }
+
+    pub fn rule_nullable(&self, rule: usize) -> bool {
+        self.first.rules_nullable[rule]
+    }
+
+    pub fn follow(&self, nonterm: &str) -> BTreeSet<Rstr> {
+        match self
+            .grammar
+            .nonterminals
+            .get(&capitalize_first_letter(nonterm))
+        {
+            Some(n) => self.names(&self.follow[n.index()], false),
+            None => BTreeSet::new(),
+        }
+    }
+
+    pub fn table(&self) -> &ParseTable {
+        &self.table
+    }
+
+    /// Production `rule` as `L -> a B`.
+    pub fn production(&self, rule: usize) -> String {
+        let (l, r) = &self.grammar.rules[rule];
+        let mut out = format!("{} ->", self.grammar.nonterminals.name(*l));
+        for s in r.iter() {
+            let _ = write!(out, " {}", self.grammar.name(*s));
+        }
+        out
+    }
+
+    fn names(&self, set: &BitSet, epsilon: bool) -> BTreeSet<Rstr> {
+        let mut names: BTreeSet<Rstr> = set
+            .iter()
+            .map(|t| self.grammar.terminals.name(t).clone())
+            .collect();
+        if epsilon {
+            names.insert("".into());
+        }
+        names
+    }
+
+    /// `{"nullable": [..], "first": {..}, "productions": [..],
+    /// "follow": {..}, "table": {..}}`, with `""` for ε and end of input.
+    pub fn to_json(&self) -> String {
+        let set = |s: BTreeSet<Rstr>| {
+            let items: Vec<String> = s.iter().map(|x| json_escape(x)).collect();
+            format!("[{}]", items.join(","))
+        };
+        let per_nonterm = |f: &dyn Fn(&str) -> String| {
+            let items: Vec<String> = self
+                .nonterminals()
+                .map(|n| format!("{}:{}", json_escape(n), f(n)))
+                .collect();
+            format!("{{{}}}", items.join(","))
+        };
+        let nullable: Vec<String> =
+            self.nullable().iter().map(|n| json_escape(n)).collect();
+        let productions: Vec<String> = (0..self.grammar.rules.len())
+            .map(|i| {
+                format!(
+                    "{{\"rule\":{},\"production\":{},\"first\":{},\
+                     \"nullable\":{}}}",
+                    i,
+                    json_escape(&self.production(i)),
+                    set(self.rule_first(i)),
+                    self.rule_nullable(i)
+                )
+            })
+            .collect();
+        let table = per_nonterm(&|n| {
+            let row: Vec<String> = self
+                .table
+                .lookaheads(n)
+                .iter()
+                .map(|t| {
+                    let rule = self.table.get(n, t).expect("is a lookahead");
+                    format!("{}:{}", json_escape(t), rule)
+                })
+                .collect();
+            format!("{{{}}}", row.join(","))
+        });
+        format!(
+            "{{\"nullable\":[{}],\"first\":{},\"productions\":[{}],\
+             \"follow\":{},\"table\":{}}}",
+            nullable.join(","),
+            per_nonterm(&|n| set(self.first(n))),
+            productions.join(","),
+            per_nonterm(&|n| set(self.follow(n))),
+            table
+        )
+    }
+}
+
+/// `{ "a", "b", ε }`; `empty` is what `""` is shown as.
+fn show_set(set: &BTreeSet<Rstr>, empty: &str) -> String {
+    let mut items: Vec<String> = set
+        .iter()
+        .filter(|x| !x.is_empty())
+        .map(|x| format!("\"{}\"", x))
+        .collect();
+    if set.contains("") {
+        items.push(empty.into());
+    }
+    format!("{{ {} }}", items.join(", "))
+}
+
+impl Display for GrammarAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let nullable: Vec<&str> =
+            self.nullable().into_iter().map(|x| x.as_ref()).collect();
+        writeln!(f, "Nullable: {}", nullable.join(", "))?;
+        writeln!(f, "FIRST:")?;
+        for n in self.nonterminals() {
+            writeln!(f, "    {}: {}", n, show_set(&self.first(n), "ε"))?;
+        }
+        writeln!(f, "FIRST per production:")?;
+        for i in 0..self.grammar.rules.len() {
+            writeln!(
+                f,
+                "    ({}) {}: {}",
+                i,
+                self.production(i),
+                show_set(&self.rule_first(i), "ε")
+            )?;
+        }
+        writeln!(f, "FOLLOW:")?;
+        for n in self.nonterminals() {
+            writeln!(f, "    {}: {}", n, show_set(&self.follow(n), "$"))?;
+        }
+        writeln!(f, "LL(1) table:")?;
+        for n in self.nonterminals().filter(|n| self.table.contains(n)) {
+            let mut lookaheads = self.table.lookaheads(n);
+            // End of input last, like in the sets.
+            lookaheads.sort_by_key(|t| t.is_empty());
+            let row: Vec<String> = lookaheads
+                .iter()
+                .map(|t| {
+                    let rule = self.table.get(n, t).expect("is a lookahead");
+                    match t.as_ref() {
+                        "" => format!("$ -> {}", rule),
+                        t => format!("\"{}\" -> {}", t, rule),
+                    }
+                })
+                .collect();
+            writeln!(f, "    {}: {}", n, row.join(", "))?;
+        }
+        Ok(())
+    }
+}
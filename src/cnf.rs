@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+
+use crate::grammar::{
+    GrammarChomsky, Helper, HelperKind, HelperNames, Rstr, NT,
+};
+use crate::left_recursion::source;
+
+/// The right side of a rule in Chomsky normal form.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CnfRule {
+    Terminal(Rstr),
+    Pair(Rstr, Rstr),
+}
+
+/// A grammar in Chomsky normal form: every rule is `A -> a` or `A -> B C`.
+/// Whether the empty string is in the language is kept in `empty` instead
+/// of a rule `S -> ε`.
+#[derive(Debug, Clone)]
+pub struct GrammarCNF {
+    pub start: Rstr,
+    pub empty: bool,
+    pub rules: Vec<(Rstr, CnfRule)>,
+    /// The helpers of the grammar and those added for the conversion, left
+    /// out of the trees.
+    pub helpers: BTreeMap<Rstr, Helper>,
+}
+
+fn is_empty(x: &NT) -> bool {
+    match x {
+        NT::Epsilon => true,
+        NT::Term(t) => t.is_empty(),
+        NT::Non(_) => false,
+    }
+}
+
+/// Removes repeated rules, keeping the first.
+fn dedup(rules: Vec<(Rstr, Vec<NT>)>) -> Vec<(Rstr, Vec<NT>)> {
+    let mut seen = BTreeSet::new();
+    rules
+        .into_iter()
+        .filter(|(l, r)| seen.insert((l.clone(), r.clone())))
+        .collect()
+}
+
+fn nullable(rules: &[(Rstr, Vec<NT>)]) -> HashSet<Rstr> {
+    let mut nullable = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (l, r) in rules.iter() {
+            if !nullable.contains(l)
+                && r.iter().all(|x| match x {
+                    NT::Non(n) => nullable.contains(n),
+                    _ => false,
+                })
+            {
+                nullable.insert(l.clone());
+                changed = true;
+            }
+        }
+    }
+    nullable
+}
+
+fn productive(rules: &[(Rstr, Vec<NT>)]) -> HashSet<Rstr> {
+    let mut productive = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (l, r) in rules.iter() {
+            if !productive.contains(l)
+                && r.iter().all(|x| match x {
+                    NT::Non(n) => productive.contains(n),
+                    _ => true,
+                })
+            {
+                productive.insert(l.clone());
+                changed = true;
+            }
+        }
+    }
+    productive
+}
+
+impl GrammarChomsky {
+    /// The grammar in Chomsky normal form. ε-rules are removed first, by
+    /// adding every variant of a rule without some of its nullable
+    /// nonterminals, then unit rules `A -> B`, by giving `A` the other rules
+    /// of every `B` it reaches through them, then nonterminals that derive
+    /// no terminal string or can not be reached. Last, terminals in longer
+    /// rules get a [`HelperKind::Term`] helper `A__term1` per rule and
+    /// rules longer than two are split into a chain of
+    /// [`HelperKind::Chain`] helpers `A__chain1`, `A__chain2`, ….
+    pub fn to_cnf(&self) -> GrammarCNF {
+        let start = self.start_nonterm.clone();
+        let rules: Vec<(Rstr, Vec<NT>)> = self
+            .rules
+            .iter()
+            .map(|(l, r)| {
+                (
+                    l.clone(),
+                    r.iter().filter(|x| !is_empty(x)).cloned().collect(),
+                )
+            })
+            .collect();
+
+        // ε-rules
+        let nullable = nullable(&rules);
+        let empty = nullable.contains(&start);
+        let mut without_empty = vec![];
+        for (l, r) in rules.iter() {
+            let mut variants: Vec<Vec<NT>> = vec![vec![]];
+            for x in r.iter() {
+                let optional = matches!(x, NT::Non(n) if nullable.contains(n));
+                let mut next = vec![];
+                for v in variants {
+                    if optional {
+                        next.push(v.clone());
+                    }
+                    let mut v = v;
+                    v.push(x.clone());
+                    next.push(v);
+                }
+                variants = next;
+            }
+            for v in variants.into_iter().rev() {
+                if !v.is_empty() {
+                    without_empty.push((l.clone(), v));
+                }
+            }
+        }
+        let rules = dedup(without_empty);
+
+        // Unit rules
+        let mut order: Vec<Rstr> = vec![];
+        let mut of: HashMap<Rstr, Vec<&Vec<NT>>> = HashMap::new();
+        for (l, r) in rules.iter() {
+            if !of.contains_key(l) {
+                order.push(l.clone());
+            }
+            of.entry(l.clone()).or_default().push(r);
+        }
+        let mut without_units = vec![];
+        for a in order.iter() {
+            let mut reached = vec![a.clone()];
+            let mut i = 0;
+            while i < reached.len() {
+                for r in of[&reached[i]].iter() {
+                    match r.as_slice() {
+                        [NT::Non(b)] => {
+                            if of.contains_key(b) && !reached.contains(b) {
+                                reached.push(b.clone());
+                            }
+                        }
+                        r => without_units.push((a.clone(), r.to_vec())),
+                    }
+                }
+                i += 1;
+            }
+        }
+        let rules = dedup(without_units);
+
+        // Useless symbols
+        let productive = productive(&rules);
+        let rules: Vec<(Rstr, Vec<NT>)> = rules
+            .into_iter()
+            .filter(|(l, r)| {
+                productive.contains(l)
+                    && r.iter().all(|x| match x {
+                        NT::Non(n) => productive.contains(n),
+                        _ => true,
+                    })
+            })
+            .collect();
+        let mut reachable: HashSet<Rstr> = HashSet::from([start.clone()]);
+        let mut todo = vec![start.clone()];
+        while let Some(n) = todo.pop() {
+            for (_, r) in rules.iter().filter(|(l, _)| *l == n) {
+                for x in r.iter() {
+                    if let NT::Non(m) = x {
+                        if reachable.insert(m.clone()) {
+                            todo.push(m.clone());
+                        }
+                    }
+                }
+            }
+        }
+        let rules: Vec<(Rstr, Vec<NT>)> = rules
+            .into_iter()
+            .filter(|(l, _)| reachable.contains(l))
+            .collect();
+
+        // Terminals in long rules and binarization
+        let used: HashSet<&Rstr> = rules.iter().map(|(l, _)| l).collect();
+        let mut names = HelperNames::new(self);
+        names.helpers.retain(|h, _| used.contains(h));
+        let mut term_of: HashMap<(Rstr, Rstr), Rstr> = HashMap::new();
+        let mut term_rules = vec![];
+        let mut out = vec![];
+        for (l, r) in rules.iter() {
+            if let [NT::Term(a)] = r.as_slice() {
+                out.push((l.clone(), CnfRule::Terminal(a.clone())));
+                continue;
+            }
+            let rule = self.rule_of(l);
+            let mut symbols: Vec<Rstr> = vec![];
+            for x in r.iter() {
+                match x {
+                    NT::Non(n) => symbols.push(n.clone()),
+                    NT::Term(a) => {
+                        let key = (rule.clone(), a.clone());
+                        let t = term_of.entry(key).or_insert_with(|| {
+                            let t = names.add(
+                                &rule,
+                                HelperKind::Term,
+                                source(std::slice::from_ref(x)).into(),
+                                Default::default(),
+                            );
+                            term_rules.push((
+                                t.clone(),
+                                CnfRule::Terminal(a.clone()),
+                            ));
+                            t
+                        });
+                        symbols.push(t.clone());
+                    }
+                    NT::Epsilon => {}
+                }
+            }
+            let mut lhs = l.clone();
+            let mut k = 0;
+            while symbols.len() - k > 2 {
+                let rest = names.add(
+                    &rule,
+                    HelperKind::Chain,
+                    source(&r[k + 1..]).into(),
+                    Default::default(),
+                );
+                out.push((
+                    lhs,
+                    CnfRule::Pair(symbols[k].clone(), rest.clone()),
+                ));
+                lhs = rest;
+                k += 1;
+            }
+            out.push((
+                lhs,
+                CnfRule::Pair(symbols[k].clone(), symbols[k + 1].clone()),
+            ));
+        }
+        out.extend(term_rules);
+
+        GrammarCNF {
+            start,
+            empty,
+            rules: out,
+            helpers: names.helpers,
+        }
+    }
+}
+
+impl Display for GrammarCNF {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.empty {
+            writeln!(f, "{} -> {}", self.start, NT::Epsilon)?;
+        }
+        for (l, r) in self.rules.iter() {
+            match r {
+                CnfRule::Terminal(a) => {
+                    writeln!(f, "{} -> {}", l, NT::Term(a.clone()))?
+                }
+                CnfRule::Pair(b, c) => writeln!(
+                    f,
+                    "{} -> {} {}",
+                    l,
+                    NT::Non(b.clone()),
+                    NT::Non(c.clone())
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
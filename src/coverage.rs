@@ -0,0 +1,533 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
+
+use crate::analysis::ParseTable;
+use crate::grammar::{GrammarChomsky, Rstr, NT};
+
+/// Something a test sentence can cover.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Goal {
+    /// A production, by its index in `rules`.
+    Production(usize),
+    /// A cell of the LL(1) table, as (nonterminal, lookahead). End of input
+    /// is the lookahead `""`.
+    Cell(Rstr, Rstr),
+}
+
+impl Display for Goal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Goal::Production(rule) => write!(f, "production {}", rule),
+            Goal::Cell(n, t) if t.is_empty() => write!(f, "cell {}, $", n),
+            Goal::Cell(n, t) => write!(f, "cell {}, \"{}\"", n, t),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    pub sentence: Vec<Rstr>,
+    pub covers: BTreeSet<Goal>,
+}
+
+/// The sentences found by [`Coverage::suite`] and the goals no sentence
+/// covers, because their nonterminal is unreachable or derives nothing, or
+/// because the LL(1) table has conflicts and would not parse the sentence.
+#[derive(Debug, Clone)]
+pub struct Suite {
+    pub cases: Vec<TestCase>,
+    pub missed: Vec<Goal>,
+}
+
+/// A derivation tree, with a child for every symbol of the production.
+enum Tree {
+    Epsilon,
+    Leaf(Rstr),
+    Node(usize, Vec<Tree>),
+}
+
+/// How the nonterminal at a position of a production gets followed by the
+/// terminal: the rest of the production starts with it at the given
+/// position, or the rest derives ε and what follows the production does.
+#[derive(Debug, Clone, Copy)]
+enum Follow {
+    Starts(usize),
+    Inherits,
+}
+
+/// (length, height) of the shortest derivations, the height breaks ties so
+/// that following the choices always ends.
+type Cost = (usize, usize);
+
+/// The production, position and [`Follow`] a nonterminal followed by a
+/// terminal is reached from.
+type FollowedFrom = (usize, usize, Follow);
+
+/// Builds test sentences that together use every production of a grammar
+/// and, with [`Coverage::with_cells`], every cell of its LL(1) table.
+///
+/// Every goal gets the shortest sentence that reaches it, each nonterminal
+/// around it derived in the shortest way. Of these, the sentences covering
+/// the most goals not covered yet are picked until all are, so the suite is
+/// small, though not always the smallest.
+#[derive(Debug, Clone)]
+pub struct Coverage<'a> {
+    grammar: &'a GrammarChomsky,
+    table: Option<&'a ParseTable>,
+    cells: bool,
+    terminals: Vec<Rstr>,
+    /// The shortest sentence of each nonterminal and its production.
+    shortest: HashMap<Rstr, (Cost, usize)>,
+    /// The shortest sentence of a nonterminal starting with a terminal, its
+    /// production and the position of the symbol that starts with it.
+    starting: HashMap<(Rstr, Rstr), (Cost, usize, usize)>,
+    /// The shortest sentence around a nonterminal, the production and
+    /// position it is reached from, `None` for the start nonterminal.
+    reach: HashMap<Rstr, (Cost, Option<(usize, usize)>)>,
+    /// Like `reach`, but with the nonterminal followed by a terminal.
+    followed: HashMap<(Rstr, Rstr), (Cost, Option<FollowedFrom>)>,
+}
+
+impl<'a> Coverage<'a> {
+    pub fn new(grammar: &'a GrammarChomsky) -> Coverage<'a> {
+        let mut terminals: Vec<Rstr> = grammar
+            .terminals
+            .iter()
+            .filter(|t| !t.is_empty())
+            .cloned()
+            .collect();
+        terminals.sort();
+        let mut coverage = Coverage {
+            grammar,
+            table: None,
+            cells: false,
+            terminals,
+            shortest: HashMap::new(),
+            starting: HashMap::new(),
+            reach: HashMap::new(),
+            followed: HashMap::new(),
+        };
+        coverage.find_shortest();
+        coverage.find_starting();
+        coverage.find_reach();
+        coverage.find_followed();
+        coverage
+    }
+
+    /// Only make sentences whose derivation `table`, the table of the
+    /// grammar, would pick, so that the parser accepts all of them even if
+    /// the table has conflicts.
+    pub fn with_table(mut self, table: &'a ParseTable) -> Self {
+        self.table = Some(table);
+        self
+    }
+
+    /// Also cover every cell of the table given to
+    /// [`Coverage::with_table`].
+    pub fn with_cells(mut self) -> Self {
+        self.cells = true;
+        self
+    }
+
+    pub fn suite(&self) -> Suite {
+        let mut goals: BTreeSet<Goal> = (0..self.grammar.rules.len())
+            .map(Goal::Production)
+            .collect();
+        let mut candidates = vec![];
+        for rule in 0..self.grammar.rules.len() {
+            candidates.extend(self.production_case(rule));
+        }
+        if let Some(table) = self.table.filter(|_| self.cells) {
+            for n in table.nonterminals() {
+                for t in table.lookaheads(n) {
+                    candidates.extend(self.cell_case(table, n, &t));
+                    goals.insert(Goal::Cell(n.clone(), t));
+                }
+            }
+        }
+
+        // The goals by index, so that counting what a candidate adds is
+        // cheap.
+        let goals: Vec<Goal> = goals.into_iter().collect();
+        let index: HashMap<&Goal, usize> =
+            goals.iter().enumerate().map(|(i, g)| (g, i)).collect();
+        let mut covers: Vec<Vec<usize>> = candidates
+            .iter()
+            .map(|c| c.covers.iter().filter_map(|g| index.get(g)).copied())
+            .map(|c| c.collect())
+            .collect();
+        let mut uncovered = vec![true; goals.len()];
+        let mut cases: Vec<TestCase> = vec![];
+        loop {
+            let best = covers
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (c.iter().filter(|g| uncovered[**g]).count(), i))
+                .filter(|(count, _)| *count > 0)
+                .min_by_key(|(count, i)| {
+                    (usize::MAX - count, candidates[*i].sentence.len(), *i)
+                });
+            let Some((_, i)) = best else {
+                break;
+            };
+            for g in covers.swap_remove(i) {
+                uncovered[g] = false;
+            }
+            cases.push(candidates.swap_remove(i));
+        }
+        Suite {
+            cases,
+            missed: goals
+                .into_iter()
+                .zip(uncovered)
+                .filter(|(_, u)| *u)
+                .map(|(g, _)| g)
+                .collect(),
+        }
+    }
+
+    fn production_case(&self, rule: usize) -> Option<TestCase> {
+        let (nonterm, rhs) = &self.grammar.rules[rule];
+        self.seq_cost(rhs)?;
+        let tree = Tree::Node(rule, self.symbol_trees(rhs));
+        self.case(self.reached(nonterm, tree)?)
+    }
+
+    fn cell_case(
+        &self,
+        table: &ParseTable,
+        nonterm: &Rstr,
+        terminal: &Rstr,
+    ) -> Option<TestCase> {
+        let rule = table.get(nonterm, terminal)?;
+        let rhs = &self.grammar.rules[rule].1;
+        let tree = match self.seq_starting(rhs, terminal) {
+            Some((_, j)) => {
+                let tree =
+                    Tree::Node(rule, self.starting_trees(rhs, j, terminal));
+                self.reached(nonterm, tree)?
+            }
+            None if self.seq_cost(rhs)?.0 == 0 => {
+                let tree = Tree::Node(rule, self.symbol_trees(rhs));
+                self.followed_by(nonterm, terminal, tree)?
+            }
+            None => return None,
+        };
+        self.case(tree)
+    }
+
+    /// The test case of `tree`, `None` if the LL(1) table would not pick
+    /// its productions, so that the parser would reject it.
+    fn case(&self, tree: Tree) -> Option<TestCase> {
+        let mut sentence = vec![];
+        leaves(&tree, &mut sentence);
+        let mut covers = BTreeSet::new();
+        self.goals(&tree, &sentence, &mut 0, &mut covers)?;
+        Some(TestCase { sentence, covers })
+    }
+
+    fn goals(
+        &self,
+        tree: &Tree,
+        sentence: &[Rstr],
+        pos: &mut usize,
+        covers: &mut BTreeSet<Goal>,
+    ) -> Option<()> {
+        match tree {
+            Tree::Epsilon => {}
+            Tree::Leaf(_) => *pos += 1,
+            Tree::Node(rule, children) => {
+                covers.insert(Goal::Production(*rule));
+                if let Some(table) = self.table {
+                    let nonterm = &self.grammar.rules[*rule].0;
+                    let lookahead =
+                        sentence.get(*pos).cloned().unwrap_or(Rstr::from(""));
+                    if table.get(nonterm, &lookahead) != Some(*rule) {
+                        return None;
+                    }
+                    if self.cells {
+                        covers.insert(Goal::Cell(nonterm.clone(), lookahead));
+                    }
+                }
+                for child in children.iter() {
+                    self.goals(child, sentence, pos, covers)?;
+                }
+            }
+        }
+        Some(())
+    }
+
+    fn symbol_cost(&self, symbol: &NT) -> Option<Cost> {
+        match symbol {
+            NT::Epsilon => Some((0, 0)),
+            NT::Term(t) => Some((usize::from(!t.is_empty()), 0)),
+            NT::Non(n) => self.shortest.get(n).map(|(c, _)| *c),
+        }
+    }
+
+    fn seq_cost(&self, symbols: &[NT]) -> Option<Cost> {
+        symbols.iter().try_fold((0, 0), |(len, h), x| {
+            self.symbol_cost(x).map(|(l, g)| (len + l, h.max(g)))
+        })
+    }
+
+    /// The shortest sentence of `symbols` that starts with `terminal`, and
+    /// the position of the symbol that starts with it.
+    fn seq_starting(
+        &self,
+        symbols: &[NT],
+        terminal: &Rstr,
+    ) -> Option<(Cost, usize)> {
+        let mut best: Option<(Cost, usize)> = None;
+        let mut before = 0;
+        for (j, x) in symbols.iter().enumerate() {
+            let here = match x {
+                NT::Term(t) if !t.is_empty() && t == terminal => Some((1, 0)),
+                NT::Non(n) => self
+                    .starting
+                    .get(&(n.clone(), terminal.clone()))
+                    .map(|(c, ..)| *c),
+                _ => None,
+            };
+            if let (Some(here), Some(rest)) =
+                (here, self.seq_cost(&symbols[j + 1..]))
+            {
+                let cost = (here.0 + rest.0, before.max(here.1).max(rest.1));
+                if best.is_none_or(|(b, _)| cost < b) {
+                    best = Some((cost, j));
+                }
+            }
+            match self.symbol_cost(x) {
+                Some((0, h)) => before = before.max(h),
+                _ => break,
+            }
+        }
+        best
+    }
+
+    fn find_shortest(&mut self) {
+        let grammar = self.grammar;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, (l, r)) in grammar.rules.iter().enumerate() {
+                let Some((len, h)) = self.seq_cost(r) else {
+                    continue;
+                };
+                let cost = (len, h + 1);
+                if self.shortest.get(l).is_none_or(|(c, _)| cost < *c) {
+                    self.shortest.insert(l.clone(), (cost, i));
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    fn find_starting(&mut self) {
+        let grammar = self.grammar;
+        let terminals = self.terminals.clone();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, (l, r)) in grammar.rules.iter().enumerate() {
+                for t in terminals.iter() {
+                    let Some(((len, h), j)) = self.seq_starting(r, t) else {
+                        continue;
+                    };
+                    let cost = (len, h + 1);
+                    let key = (l.clone(), t.clone());
+                    if self.starting.get(&key).is_none_or(|s| cost < s.0) {
+                        self.starting.insert(key, (cost, i, j));
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn find_reach(&mut self) {
+        let grammar = self.grammar;
+        self.reach
+            .insert(grammar.start_nonterm.clone(), ((0, 0), None));
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, (l, r)) in grammar.rules.iter().enumerate() {
+                let Some(&((len, depth), _)) = self.reach.get(l) else {
+                    continue;
+                };
+                for (pos, x) in r.iter().enumerate() {
+                    let NT::Non(n) = x else {
+                        continue;
+                    };
+                    let (Some(before), Some(after)) = (
+                        self.seq_cost(&r[..pos]),
+                        self.seq_cost(&r[pos + 1..]),
+                    ) else {
+                        continue;
+                    };
+                    let cost = (len + before.0 + after.0, depth + 1);
+                    if self.reach.get(n).is_none_or(|(c, _)| cost < *c) {
+                        self.reach.insert(n.clone(), (cost, Some((i, pos))));
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn find_followed(&mut self) {
+        let grammar = self.grammar;
+        let mut lookaheads = self.terminals.clone();
+        lookaheads.push(Rstr::from(""));
+        self.followed.insert(
+            (grammar.start_nonterm.clone(), Rstr::from("")),
+            ((0, 0), None),
+        );
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, (l, r)) in grammar.rules.iter().enumerate() {
+                for (pos, x) in r.iter().enumerate() {
+                    let NT::Non(n) = x else {
+                        continue;
+                    };
+                    let Some(before) = self.seq_cost(&r[..pos]) else {
+                        continue;
+                    };
+                    let after = &r[pos + 1..];
+                    let nullable =
+                        self.seq_cost(after).is_some_and(|c| c.0 == 0);
+                    for t in lookaheads.iter() {
+                        let starts = self
+                            .seq_starting(after, t)
+                            .zip(self.reach.get(l))
+                            .map(|((a, j), ((len, depth), _))| {
+                                ((len + a.0, *depth), Follow::Starts(j))
+                            });
+                        let inherits = self
+                            .followed
+                            .get(&(l.clone(), t.clone()))
+                            .filter(|_| nullable)
+                            .map(|((len, depth), _)| {
+                                ((*len, *depth), Follow::Inherits)
+                            });
+                        for ((len, depth), follow) in
+                            starts.into_iter().chain(inherits)
+                        {
+                            let cost = (len + before.0, depth + 1);
+                            let key = (n.clone(), t.clone());
+                            if self
+                                .followed
+                                .get(&key)
+                                .is_none_or(|(c, _)| cost < *c)
+                            {
+                                self.followed.insert(
+                                    key,
+                                    (cost, Some((i, pos, follow))),
+                                );
+                                changed = true;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn symbol_tree(&self, symbol: &NT) -> Tree {
+        match symbol {
+            NT::Non(n) => {
+                let rule = self.shortest[n].1;
+                Tree::Node(rule, self.symbol_trees(&self.grammar.rules[rule].1))
+            }
+            NT::Term(t) if !t.is_empty() => Tree::Leaf(t.clone()),
+            _ => Tree::Epsilon,
+        }
+    }
+
+    fn symbol_trees(&self, symbols: &[NT]) -> Vec<Tree> {
+        symbols.iter().map(|x| self.symbol_tree(x)).collect()
+    }
+
+    /// The trees of `symbols` with the one at `j` starting with `terminal`.
+    fn starting_trees(
+        &self,
+        symbols: &[NT],
+        j: usize,
+        terminal: &Rstr,
+    ) -> Vec<Tree> {
+        let mut trees = self.symbol_trees(&symbols[..j]);
+        trees.push(match &symbols[j] {
+            NT::Non(n) => {
+                let (_, rule, k) =
+                    self.starting[&(n.clone(), terminal.clone())];
+                let rhs = &self.grammar.rules[rule].1;
+                Tree::Node(rule, self.starting_trees(rhs, k, terminal))
+            }
+            x => self.symbol_tree(x),
+        });
+        trees.extend(self.symbol_trees(&symbols[j + 1..]));
+        trees
+    }
+
+    /// The tree of the start nonterminal with `tree` for `nonterm` in it.
+    fn reached(&self, nonterm: &Rstr, mut tree: Tree) -> Option<Tree> {
+        let mut nonterm = nonterm;
+        while let Some((rule, pos)) = self.reach.get(nonterm)?.1 {
+            let (l, r) = &self.grammar.rules[rule];
+            let mut trees = self.symbol_trees(&r[..pos]);
+            trees.push(tree);
+            trees.extend(self.symbol_trees(&r[pos + 1..]));
+            tree = Tree::Node(rule, trees);
+            nonterm = l;
+        }
+        Some(tree)
+    }
+
+    /// Like [`Coverage::reached`], with `terminal` right after `tree`.
+    fn followed_by(
+        &self,
+        nonterm: &Rstr,
+        terminal: &Rstr,
+        mut tree: Tree,
+    ) -> Option<Tree> {
+        let mut nonterm = nonterm.clone();
+        loop {
+            let key = (nonterm, terminal.clone());
+            let Some((rule, pos, follow)) = self.followed.get(&key)?.1 else {
+                return Some(tree);
+            };
+            let (l, r) = &self.grammar.rules[rule];
+            let mut trees = self.symbol_trees(&r[..pos]);
+            trees.push(tree);
+            match follow {
+                Follow::Starts(j) => {
+                    trees.extend(self.starting_trees(
+                        &r[pos + 1..],
+                        j,
+                        terminal,
+                    ));
+                    return self.reached(l, Tree::Node(rule, trees));
+                }
+                Follow::Inherits => {
+                    trees.extend(self.symbol_trees(&r[pos + 1..]));
+                    tree = Tree::Node(rule, trees);
+                    nonterm = l.clone();
+                }
+            }
+        }
+    }
+}
+
+fn leaves(tree: &Tree, out: &mut Vec<Rstr>) {
+    match tree {
+        Tree::Epsilon => {}
+        Tree::Leaf(t) => out.push(t.clone()),
+        Tree::Node(_, children) => {
+            for child in children.iter() {
+                leaves(child, out);
+            }
+        }
+    }
+}
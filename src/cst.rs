@@ -0,0 +1,314 @@
+use std::fmt::Display;
+
+use crate::grammar::{GrammarChomsky, Rstr};
+use crate::parser_generator::{TokenIteratorType, TokenReq, TokenTrait, AST};
+
+/// A lossless concrete syntax tree, as produced by
+/// [`get_cst_parser`](crate::parser_generator::get_cst_parser).
+pub type CST = AST<CstToken>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    Comment,
+}
+
+/// A piece of input that is not part of any token.
+#[derive(Debug, Clone)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: Rstr,
+    /// Byte offset into the input.
+    pub str_pos: usize,
+}
+
+/// Describes which input is skipped as trivia between tokens.
+///
+/// Trivia is tried before terminals, so a grammar that uses e.g. `" "` as
+/// a terminal should not enable `whitespace`.
+#[derive(Debug, Clone)]
+pub struct TriviaConfig {
+    pub whitespace: bool,
+    pub line_comments: Vec<Rstr>,
+    pub block_comments: Vec<(Rstr, Rstr)>,
+}
+
+impl Default for TriviaConfig {
+    fn default() -> Self {
+        TriviaConfig {
+            whitespace: true,
+            line_comments: vec![],
+            block_comments: vec![],
+        }
+    }
+}
+
+impl TriviaConfig {
+    /// Adds a comment that runs from `start` to the end of the line. An
+    /// empty `start` never matches.
+    pub fn with_line_comment(mut self, start: &str) -> Self {
+        self.line_comments.push(start.into());
+        self
+    }
+
+    /// Adds a comment that runs from `start` to `end`. An empty `start`
+    /// never matches.
+    pub fn with_block_comment(mut self, start: &str, end: &str) -> Self {
+        self.block_comments.push((start.into(), end.into()));
+        self
+    }
+
+    /// Length in bytes of the trivia at the start of `s`, if any.
+    fn match_trivia(&self, s: &str) -> Option<(TriviaKind, usize)> {
+        if self.whitespace {
+            let n = s.find(|c: char| !c.is_whitespace()).unwrap_or(s.len());
+            if n > 0 {
+                return Some((TriviaKind::Whitespace, n));
+            }
+        }
+        for start in self.line_comments.iter().filter(|s| !s.is_empty()) {
+            if s.starts_with(start.as_ref()) {
+                let n = s.find('\n').unwrap_or(s.len());
+                return Some((TriviaKind::Comment, n));
+            }
+        }
+        for (start, end) in
+            self.block_comments.iter().filter(|(s, _)| !s.is_empty())
+        {
+            if let Some(r) = s.strip_prefix(start.as_ref()) {
+                let n = r
+                    .find(end.as_ref())
+                    .map(|n| n + end.len())
+                    .unwrap_or(r.len());
+                return Some((TriviaKind::Comment, start.len() + n));
+            }
+        }
+        None
+    }
+}
+
+/// A token that remembers the trivia around it.
+///
+/// Trivia before a token is `leading`; only the last token of the input
+/// has `trailing` trivia.
+#[derive(Debug, Clone)]
+pub struct CstToken {
+    text: Rstr,
+    eof: bool,
+    pub leading: Vec<Trivia>,
+    pub trailing: Vec<Trivia>,
+    line: usize,
+    column: usize,
+    str_pos: usize,
+}
+
+impl CstToken {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn write_source(&self, out: &mut String) {
+        for t in self.leading.iter() {
+            out.push_str(&t.text);
+        }
+        out.push_str(&self.text);
+        for t in self.trailing.iter() {
+            out.push_str(&t.text);
+        }
+    }
+}
+
+impl Display for CstToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl TokenTrait for CstToken {
+    fn as_str(&self) -> Rstr {
+        if self.eof {
+            Rstr::from("EOF")
+        } else {
+            self.text.clone()
+        }
+    }
+    fn column(&self) -> usize {
+        self.column
+    }
+    fn line(&self) -> usize {
+        self.line
+    }
+    fn str_pos(&self) -> usize {
+        self.str_pos
+    }
+    fn make_eof(line: usize, column: usize, str_pos: usize) -> Self {
+        CstToken {
+            text: Rstr::from(""),
+            eof: true,
+            leading: vec![],
+            trailing: vec![],
+            line,
+            column,
+            str_pos,
+        }
+    }
+    fn actual_string(&self) -> Rstr {
+        self.text.clone()
+    }
+    fn is_eof(&self) -> bool {
+        self.eof
+    }
+}
+
+impl TokenReq for CstToken {}
+
+impl CST {
+    /// Reproduces the parsed input, trivia included.
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        self.write_source(&mut out);
+        out
+    }
+
+    fn write_source(&self, out: &mut String) {
+        match self {
+            AST::Node { children, .. } => {
+                for c in children.iter() {
+                    c.write_source(out);
+                }
+            }
+            AST::Token(t) => t.write_source(out),
+        }
+    }
+}
+
+struct CstTokenIter<'a> {
+    rest: &'a str,
+    terminals: Vec<Rstr>,
+    trivia: TriviaConfig,
+    str_pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> CstTokenIter<'a> {
+    fn advance(&mut self, n: usize) -> &'a str {
+        let (taken, rest) = self.rest.split_at(n);
+        for c in taken.chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 0;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.str_pos += n;
+        self.rest = rest;
+        taken
+    }
+
+    fn take_trivia(&mut self) -> Vec<Trivia> {
+        let mut trivia = vec![];
+        while let Some((kind, n)) = self.trivia.match_trivia(self.rest) {
+            let str_pos = self.str_pos;
+            let text = self.advance(n);
+            trivia.push(Trivia {
+                kind,
+                text: text.into(),
+                str_pos,
+            });
+        }
+        trivia
+    }
+}
+
+impl Iterator for CstTokenIter<'_> {
+    type Item = CstToken;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let before = (self.rest, self.str_pos, self.line, self.column);
+        let leading = self.take_trivia();
+        let Some(term) = self
+            .terminals
+            .iter()
+            .rev()
+            .find(|t| self.rest.starts_with(t.as_ref()))
+            .cloned()
+        else {
+            // Leave the trivia in place, so the unmatched input is reported
+            // from its start.
+            (self.rest, self.str_pos, self.line, self.column) = before;
+            return None;
+        };
+        let (line, column, str_pos) = (self.line, self.column, self.str_pos);
+        self.advance(term.len());
+        let save = (self.rest, self.str_pos, self.line, self.column);
+        let mut trailing = self.take_trivia();
+        if !self.rest.is_empty() {
+            (self.rest, self.str_pos, self.line, self.column) = save;
+            trailing.clear();
+        }
+        Some(CstToken {
+            text: term,
+            eof: false,
+            leading,
+            trailing,
+            line,
+            column,
+            str_pos,
+        })
+    }
+}
+
+/// The end of input with `input` as its leading trivia, if `input` is
+/// nothing but trivia.
+pub(crate) fn trivia_only(
+    input: &str,
+    trivia: &TriviaConfig,
+) -> Option<CstToken> {
+    let mut iter = CstTokenIter {
+        rest: input,
+        terminals: vec![],
+        trivia: trivia.clone(),
+        str_pos: 0,
+        line: 0,
+        column: 0,
+    };
+    let leading = iter.take_trivia();
+    if input.is_empty() || !iter.rest.is_empty() {
+        return None;
+    }
+    let mut eof = CstToken::make_eof(iter.line, iter.column, iter.str_pos);
+    eof.leading = leading;
+    Some(eof)
+}
+
+pub fn get_cst_tokenizer<'a>(
+    grammar: &GrammarChomsky,
+    trivia: TriviaConfig,
+) -> impl Fn(&'a str) -> TokenIteratorType<'a, CstToken> {
+    let mut terminals: Vec<Rstr> = grammar
+        .terminals
+        .iter()
+        .filter(|x| !x.is_empty())
+        .cloned()
+        .collect();
+    terminals.sort_by_key(|x| x.len());
+    move |str: &'a str| cst_tokens(str, terminals.clone(), trivia.clone())
+}
+
+/// `terminals` have to be sorted by length.
+pub(crate) fn cst_tokens(
+    input: &str,
+    terminals: Vec<Rstr>,
+    trivia: TriviaConfig,
+) -> TokenIteratorType<'_, CstToken> {
+    Box::new(CstTokenIter {
+        rest: input,
+        terminals,
+        trivia,
+        str_pos: 0,
+        line: 0,
+        column: 0,
+    })
+}
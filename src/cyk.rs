@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use crate::cnf::{CnfRule, GrammarCNF};
+use crate::grammar::Rstr;
+use crate::parser_generator::{ParseResult, TokenReq, AST};
+use crate::symbols::{BitSet, SymbolId, SymbolTable};
+
+/// A CYK parser. Unlike [`Parser`](crate::parser_generator::Parser) it
+/// accepts any context-free grammar, ambiguous ones included, at the cost
+/// of cubic time, which makes it a reference to check the LL(1) parser
+/// against.
+#[derive(Debug, Clone)]
+pub struct Cyk {
+    grammar: GrammarCNF,
+    nonterminals: SymbolTable,
+    /// The nonterminals with a rule `A -> a`, by `a`.
+    terminals: HashMap<Rstr, Vec<SymbolId>>,
+    /// The rules `A -> B C` as `(A, B, C)`.
+    pairs: Vec<(SymbolId, SymbolId, SymbolId)>,
+}
+
+impl Cyk {
+    pub fn new(grammar: GrammarCNF) -> Cyk {
+        let mut nonterminals = SymbolTable::default();
+        nonterminals.intern(&grammar.start);
+        let mut terminals: HashMap<Rstr, Vec<SymbolId>> = HashMap::new();
+        let mut pairs = vec![];
+        for (l, r) in grammar.rules.iter() {
+            let l = nonterminals.intern(l);
+            match r {
+                CnfRule::Terminal(a) => {
+                    terminals.entry(a.clone()).or_default().push(l)
+                }
+                CnfRule::Pair(b, c) => pairs.push((
+                    l,
+                    nonterminals.intern(b),
+                    nonterminals.intern(c),
+                )),
+            }
+        }
+        Cyk {
+            grammar,
+            nonterminals,
+            terminals,
+            pairs,
+        }
+    }
+
+    pub fn grammar(&self) -> &GrammarCNF {
+        &self.grammar
+    }
+
+    /// `table[i][len - 1]` holds the nonterminals deriving the `len` tokens
+    /// from `i`.
+    fn table<T: TokenReq>(&self, tokens: &[&T]) -> Vec<Vec<BitSet>> {
+        let n = tokens.len();
+        let width = self.nonterminals.len();
+        let mut table: Vec<Vec<BitSet>> =
+            (0..n).map(|i| vec![BitSet::new(width); n - i]).collect();
+        for (i, t) in tokens.iter().enumerate() {
+            for a in self.terminals.get(&t.as_str()).into_iter().flatten() {
+                table[i][0].insert(*a);
+            }
+        }
+        for len in 2..=n {
+            for i in 0..=n - len {
+                for k in 1..len {
+                    for (a, b, c) in self.pairs.iter() {
+                        if table[i][k - 1].contains(*b)
+                            && table[i + k][len - k - 1].contains(*c)
+                        {
+                            table[i][len - 1].insert(*a);
+                        }
+                    }
+                }
+            }
+        }
+        table
+    }
+
+    /// Whether the tokens, without end of input, are a sentence of the
+    /// grammar.
+    pub fn recognize<T: TokenReq>(&self, tokens: &[T]) -> bool {
+        let tokens: Vec<&T> = tokens.iter().filter(|t| !t.is_eof()).collect();
+        if tokens.is_empty() {
+            return self.grammar.empty;
+        }
+        let start = self.nonterminals.get(&self.grammar.start);
+        let table = self.table(&tokens);
+        start.is_some_and(|s| table[0][tokens.len() - 1].contains(s))
+    }
+
+    /// One parse tree of the tokens, without end of input. Like in
+    /// [`Parser`](crate::parser_generator::Parser), the tree is under a
+    /// `chomchom_root` node and helpers and nonterminals starting with `_`
+    /// are left out, and so are the nonterminals added for the conversion
+    /// to CNF. Nonterminals removed with the unit rules have no nodes. If
+    /// the grammar is ambiguous, which tree is unspecified.
+    pub fn parse<T: TokenReq>(&self, tokens: Vec<T>) -> ParseResult<T> {
+        let mut tokens: Vec<Option<T>> = tokens
+            .into_iter()
+            .filter(|t| !t.is_eof())
+            .map(Some)
+            .collect();
+        let n = tokens.len();
+        let start = self.nonterminals.get(&self.grammar.start);
+        let root = |children| AST::Node {
+            name: Rstr::from("chomchom_root"),
+            children,
+        };
+        if n == 0 {
+            if !self.grammar.empty {
+                return Err("Empty input is not in the language".into());
+            }
+            let start = &self.grammar.start;
+            if self.left_out(start) {
+                return Ok(root(vec![]));
+            }
+            return Ok(root(vec![AST::Node {
+                name: start.clone(),
+                children: vec![],
+            }]));
+        }
+        let table = {
+            let present: Vec<&T> = tokens.iter().flatten().collect();
+            self.table(&present)
+        };
+        let Some(start) = start.filter(|s| table[0][n - 1].contains(*s)) else {
+            return Err("The input is not in the language".into());
+        };
+        let mut out = vec![];
+        self.build(&table, &mut tokens, 0, n, start, &mut out);
+        Ok(root(out))
+    }
+
+    /// Whether `name` has no node of its own in the trees.
+    fn left_out(&self, name: &str) -> bool {
+        self.grammar.helpers.contains_key(name) || name.starts_with('_')
+    }
+
+    /// Pushes the tree of `a` over `len` tokens from `i` onto `out`, or its
+    /// children if `a` is left out.
+    fn build<T: TokenReq>(
+        &self,
+        table: &[Vec<BitSet>],
+        tokens: &mut [Option<T>],
+        i: usize,
+        len: usize,
+        a: SymbolId,
+        out: &mut Vec<AST<T>>,
+    ) {
+        let name = self.nonterminals.name(a);
+        let mut children = vec![];
+        if len == 1 {
+            let token = tokens[i].take().expect("each token is used once");
+            children.push(AST::Token(token));
+        } else {
+            let (b, c, k) = self
+                .pairs
+                .iter()
+                .filter(|(l, _, _)| *l == a)
+                .find_map(|(_, b, c)| {
+                    (1..len)
+                        .find(|k| {
+                            table[i][k - 1].contains(*b)
+                                && table[i + k][len - k - 1].contains(*c)
+                        })
+                        .map(|k| (*b, *c, k))
+                })
+                .expect("a derives the tokens");
+            self.build(table, tokens, i, k, b, &mut children);
+            self.build(table, tokens, i + k, len - k, c, &mut children);
+        }
+        if self.left_out(name) {
+            out.extend(children);
+        } else {
+            out.push(AST::Node {
+                name: name.clone(),
+                children,
+            });
+        }
+    }
+}
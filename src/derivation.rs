@@ -0,0 +1,97 @@
+use std::fmt::Display;
+
+use crate::grammar::{GrammarChomsky, RuleOrigin, NT};
+
+/// A leftmost derivation, as indices into `GrammarChomsky::rules`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Derivation {
+    pub steps: Vec<usize>,
+}
+
+impl Derivation {
+    pub fn origins<'g>(
+        &self,
+        grammar: &'g GrammarChomsky,
+    ) -> Vec<&'g RuleOrigin> {
+        self.steps.iter().map(|&i| &grammar.origins[i]).collect()
+    }
+
+    /// The sentential forms, starting with the nonterminal the first step
+    /// expands and ending with the derived sentence.
+    pub fn sentential_forms(&self, grammar: &GrammarChomsky) -> Vec<Vec<NT>> {
+        let start = match self.steps.first() {
+            Some(&step) => grammar.rules[step].0.clone(),
+            None => grammar.start_nonterm.clone(),
+        };
+        let mut form = vec![NT::Non(start)];
+        let mut forms = vec![form.clone()];
+        for &step in self.steps.iter() {
+            let (l, r) = &grammar.rules[step];
+            let Some(pos) =
+                form.iter().position(|x| matches!(x, NT::Non(n) if n == l))
+            else {
+                break;
+            };
+            form.splice(
+                pos..=pos,
+                r.iter().filter(|x| !matches!(x, NT::Epsilon)).cloned(),
+            );
+            forms.push(form.clone());
+        }
+        forms
+    }
+
+    /// Shows the derivation step by step, with the production and its EBNF
+    /// origin next to every step.
+    pub fn display<'d>(
+        &'d self,
+        grammar: &'d GrammarChomsky,
+    ) -> DerivationDisplay<'d> {
+        DerivationDisplay {
+            derivation: self,
+            grammar,
+        }
+    }
+}
+
+pub struct DerivationDisplay<'d> {
+    derivation: &'d Derivation,
+    grammar: &'d GrammarChomsky,
+}
+
+fn write_form(
+    f: &mut std::fmt::Formatter<'_>,
+    form: &[NT],
+) -> std::fmt::Result {
+    if form.is_empty() {
+        return write!(f, "{}", NT::Epsilon);
+    }
+    for (i, nt) in form.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", nt)?;
+    }
+    Ok(())
+}
+
+impl Display for DerivationDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let forms = self.derivation.sentential_forms(self.grammar);
+        write_form(f, &forms[0])?;
+        writeln!(f)?;
+        for (form, &step) in forms[1..].iter().zip(self.derivation.steps.iter())
+        {
+            let (l, r) = &self.grammar.rules[step];
+            let origin = &self.grammar.origins[step];
+            write!(f, "⇒ ")?;
+            write_form(f, form)?;
+            write!(f, "    [{}: {} ->", step, l)?;
+            for nt in r.iter() {
+                write!(f, " {}", nt)?;
+            }
+            writeln!(f, " | from {} = {}]", origin.rule, origin.source)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,474 @@
+use std::fmt::{Display, Write};
+
+use crate::analysis::GrammarAnalysis;
+use crate::grammar::{Rstr, NT};
+use crate::serialize::latex_escape;
+use crate::symbols::{BitSet, Symbol, SymbolId, END};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetKind {
+    First,
+    Follow,
+}
+
+/// Why an element was added to a set. `rule` is an index into the rules of
+/// the grammar and `position` the index of a symbol on its right side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// End of input follows an entry nonterminal.
+    Entry,
+    /// The terminal at `position` is preceded only by nullable symbols.
+    Terminal { rule: usize, position: usize },
+    /// The element is in FIRST of the nonterminal at `position`, which is
+    /// preceded only by nullable symbols.
+    First { rule: usize, position: usize },
+    /// The whole right side of `rule` is nullable.
+    Nullable { rule: usize },
+    /// The element is in FIRST of what comes after `position`.
+    FollowFirst { rule: usize, position: usize },
+    /// Everything after `position` is nullable and the element follows the
+    /// left side.
+    FollowFollow { rule: usize, position: usize },
+}
+
+/// `element ∈ set(nonterm)`, where `""` is ε in FIRST and end of input in
+/// FOLLOW.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Addition {
+    pub set: SetKind,
+    pub nonterm: Rstr,
+    pub element: Rstr,
+    pub reason: Reason,
+}
+
+/// The FIRST and FOLLOW sets computed the way it is done by hand: every
+/// iteration goes over all rules in order, until one adds nothing.
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    productions: Vec<(Rstr, Vec<NT>)>,
+    /// The additions made in each iteration; the last one is empty.
+    pub first: Vec<Vec<Addition>>,
+    pub follow: Vec<Vec<Addition>>,
+}
+
+impl GrammarAnalysis {
+    /// Recomputes FIRST and FOLLOW, recording every element added and why.
+    pub fn explain(&self) -> Explanation {
+        let grammar = &self.grammar;
+        let terminal = |t: SymbolId| grammar.terminals.name(t).clone();
+        let width = grammar.terminals.len();
+        let mut first = vec![BitSet::new(width); grammar.nonterminals.len()];
+        let mut nullable = vec![false; grammar.nonterminals.len()];
+        let mut first_iterations = vec![];
+        loop {
+            let mut added = vec![];
+            for (i, (l, r)) in grammar.rules.iter().enumerate() {
+                let mut add = |element, reason| {
+                    added.push(Addition {
+                        set: SetKind::First,
+                        nonterm: grammar.nonterminals.name(*l).clone(),
+                        element,
+                        reason,
+                    })
+                };
+                let mut rest_nullable = true;
+                for (position, s) in r.iter().enumerate() {
+                    match *s {
+                        Symbol::Term(t) => {
+                            if first[l.index()].insert(t) {
+                                let reason =
+                                    Reason::Terminal { rule: i, position };
+                                add(terminal(t), reason);
+                            }
+                            rest_nullable = false;
+                            break;
+                        }
+                        Symbol::Non(n) => {
+                            for t in first[n.index()].clone().iter() {
+                                if first[l.index()].insert(t) {
+                                    let reason =
+                                        Reason::First { rule: i, position };
+                                    add(terminal(t), reason);
+                                }
+                            }
+                            if !nullable[n.index()] {
+                                rest_nullable = false;
+                                break;
+                            }
+                        }
+                        Symbol::Epsilon => {}
+                    }
+                }
+                if rest_nullable && !nullable[l.index()] {
+                    nullable[l.index()] = true;
+                    add("".into(), Reason::Nullable { rule: i });
+                }
+            }
+            let done = added.is_empty();
+            first_iterations.push(added);
+            if done {
+                break;
+            }
+        }
+
+        let mut follow = vec![BitSet::new(width); grammar.nonterminals.len()];
+        let mut added = vec![];
+        for entry in self.entries() {
+            let n = grammar.nonterminals.get(entry).expect("has rules");
+            if follow[n.index()].insert(END) {
+                added.push(Addition {
+                    set: SetKind::Follow,
+                    nonterm: entry.clone(),
+                    element: "".into(),
+                    reason: Reason::Entry,
+                });
+            }
+        }
+        let mut follow_iterations = vec![];
+        loop {
+            for (i, (l, r)) in grammar.rules.iter().enumerate() {
+                for (position, s) in r.iter().enumerate() {
+                    let Symbol::Non(n) = *s else {
+                        continue;
+                    };
+                    let mut add = |t: SymbolId, reason| {
+                        added.push(Addition {
+                            set: SetKind::Follow,
+                            nonterm: grammar.nonterminals.name(n).clone(),
+                            element: terminal(t),
+                            reason,
+                        })
+                    };
+                    // FIRST of the symbols after `n`.
+                    let mut beta = BitSet::new(width);
+                    let mut beta_nullable = true;
+                    for s in r[position + 1..].iter() {
+                        match *s {
+                            Symbol::Term(t) => {
+                                beta.insert(t);
+                                beta_nullable = false;
+                            }
+                            Symbol::Non(m) => {
+                                beta.union_with(&first[m.index()]);
+                                beta_nullable = nullable[m.index()];
+                            }
+                            Symbol::Epsilon => {}
+                        }
+                        if !beta_nullable {
+                            break;
+                        }
+                    }
+                    for t in beta.iter() {
+                        if follow[n.index()].insert(t) {
+                            add(t, Reason::FollowFirst { rule: i, position });
+                        }
+                    }
+                    if beta_nullable {
+                        for t in follow[l.index()].clone().iter() {
+                            if follow[n.index()].insert(t) {
+                                let reason =
+                                    Reason::FollowFollow { rule: i, position };
+                                add(t, reason);
+                            }
+                        }
+                    }
+                }
+            }
+            let done = added.is_empty();
+            follow_iterations.push(std::mem::take(&mut added));
+            if done {
+                break;
+            }
+        }
+
+        let productions = grammar
+            .rules
+            .iter()
+            .map(|(l, r)| {
+                let r = r
+                    .iter()
+                    .map(|s| match *s {
+                        Symbol::Non(n) => {
+                            NT::Non(grammar.nonterminals.name(n).clone())
+                        }
+                        Symbol::Term(t) => NT::Term(terminal(t)),
+                        Symbol::Epsilon => NT::Epsilon,
+                    })
+                    .collect();
+                (grammar.nonterminals.name(*l).clone(), r)
+            })
+            .collect();
+        Explanation {
+            productions,
+            first: first_iterations,
+            follow: follow_iterations,
+        }
+    }
+}
+
+/// A part of a sentence explaining an addition, rendered as text or LaTeX.
+enum Piece<'a> {
+    Text(&'static str),
+    Element(SetKind, &'a str),
+    /// `∈`
+    In,
+    Nonterm(&'a str),
+    Set(SetKind, &'a str),
+    /// FIRST of a sequence of symbols.
+    FirstOf(&'a [NT]),
+    /// The symbols of a production, ε if there are none.
+    Symbols(&'a [NT]),
+    Production(&'a str, &'a [NT]),
+    /// `⇒* ε`
+    DerivesEpsilon,
+}
+
+impl Explanation {
+    fn pieces<'a>(&'a self, a: &'a Addition) -> Vec<Piece<'a>> {
+        use Piece::*;
+        let production = |rule: usize| {
+            let (l, r) = &self.productions[rule];
+            Production(l, r)
+        };
+        let mut p = vec![
+            Element(a.set, &a.element),
+            In,
+            Set(a.set, &a.nonterm),
+            Text(" because "),
+        ];
+        match a.reason {
+            Reason::Entry => {
+                p.extend([Text("parsing starts at "), Nonterm(&a.nonterm)]);
+            }
+            Reason::Terminal { rule, position }
+            | Reason::First { rule, position } => {
+                let r = &self.productions[rule].1;
+                p.push(production(rule));
+                if position > 0 {
+                    p.extend([
+                        Text(" and "),
+                        Symbols(&r[..position]),
+                        DerivesEpsilon,
+                    ]);
+                }
+                if let (Reason::First { .. }, NT::Non(n)) =
+                    (a.reason, &r[position])
+                {
+                    p.extend([
+                        Text(" and "),
+                        Element(a.set, &a.element),
+                        In,
+                        Set(SetKind::First, n),
+                    ]);
+                }
+            }
+            Reason::Nullable { rule } => {
+                let r = &self.productions[rule].1;
+                p.push(production(rule));
+                if r.iter().any(|x| !matches!(x, NT::Epsilon)) {
+                    p.extend([Text(" and "), Symbols(r), DerivesEpsilon]);
+                }
+            }
+            Reason::FollowFirst { rule, position } => {
+                let r = &self.productions[rule].1;
+                p.extend([
+                    production(rule),
+                    Text(" and "),
+                    Element(SetKind::First, &a.element),
+                    In,
+                    FirstOf(&r[position + 1..]),
+                ]);
+            }
+            Reason::FollowFollow { rule, position } => {
+                let (l, r) = &self.productions[rule];
+                p.push(production(rule));
+                if position + 1 < r.len() {
+                    p.extend([
+                        Text(", "),
+                        Symbols(&r[position + 1..]),
+                        DerivesEpsilon,
+                    ]);
+                }
+                p.extend([
+                    Text(" and "),
+                    Element(a.set, &a.element),
+                    In,
+                    Set(SetKind::Follow, l),
+                ]);
+            }
+        }
+        p
+    }
+
+    /// Renders every iteration as a LaTeX `tabular`.
+    pub fn to_latex(&self) -> String {
+        let mut out = String::new();
+        for (set, iterations) in [
+            (SetKind::First, &self.first),
+            (SetKind::Follow, &self.follow),
+        ] {
+            for (i, added) in iterations.iter().enumerate() {
+                let _ =
+                    writeln!(out, "% {}, iteration {}", set_name(set), i + 1);
+                out.push_str("\\begin{tabular}{lll}\n\\hline\n");
+                let _ = writeln!(
+                    out,
+                    "\\multicolumn{{3}}{{l}}{{{}, iteration {}}} \\\\",
+                    set_name(set),
+                    i + 1
+                );
+                out.push_str("\\hline\nElement & Set & Reason \\\\\n\\hline\n");
+                if added.is_empty() {
+                    out.push_str("\\multicolumn{3}{l}{No changes} \\\\\n");
+                }
+                for a in added.iter() {
+                    let pieces = self.pieces(a);
+                    let _ = writeln!(
+                        out,
+                        "{} & {} & {} \\\\",
+                        latex(&pieces[..1]),
+                        latex(&pieces[2..3]),
+                        latex(&pieces[4..])
+                    );
+                }
+                out.push_str("\\hline\n\\end{tabular}\n\n");
+            }
+        }
+        out
+    }
+}
+
+fn set_name(set: SetKind) -> &'static str {
+    match set {
+        SetKind::First => "FIRST",
+        SetKind::Follow => "FOLLOW",
+    }
+}
+
+fn nt_text(nt: &NT) -> String {
+    match nt {
+        NT::Non(n) => n.to_string(),
+        NT::Term(t) => format!("\"{}\"", t),
+        NT::Epsilon => "ε".into(),
+    }
+}
+
+fn nt_latex(nt: &NT) -> String {
+    match nt {
+        NT::Non(n) => format!("\\mathit{{{}}}", latex_escape(n)),
+        NT::Term(t) => format!("\\texttt{{{}}}", latex_escape(t)),
+        NT::Epsilon => "\\varepsilon".into(),
+    }
+}
+
+fn symbols(r: &[NT], show: fn(&NT) -> String, sep: &str) -> String {
+    let shown: Vec<String> = r
+        .iter()
+        .filter(|x| !matches!(x, NT::Epsilon))
+        .map(show)
+        .collect();
+    if shown.is_empty() {
+        show(&NT::Epsilon)
+    } else {
+        shown.join(sep)
+    }
+}
+
+fn text(pieces: &[Piece]) -> String {
+    let mut out = String::new();
+    for p in pieces {
+        match p {
+            Piece::Text(t) => out.push_str(t),
+            Piece::Element(set, "") => match set {
+                SetKind::First => out.push('ε'),
+                SetKind::Follow => out.push('$'),
+            },
+            Piece::Element(_, t) => {
+                let _ = write!(out, "\"{}\"", t);
+            }
+            Piece::In => out.push_str(" ∈ "),
+            Piece::Nonterm(n) => out.push_str(n),
+            Piece::Set(set, n) => {
+                let _ = write!(out, "{}({})", set_name(*set), n);
+            }
+            Piece::FirstOf(r) => {
+                let _ = write!(out, "FIRST({})", symbols(r, nt_text, " "));
+            }
+            Piece::Symbols(r) => out.push_str(&symbols(r, nt_text, " ")),
+            Piece::Production(l, r) => {
+                let _ = write!(out, "{} → {}", l, symbols(r, nt_text, " "));
+            }
+            Piece::DerivesEpsilon => out.push_str(" ⇒* ε"),
+        }
+    }
+    out
+}
+
+/// Text pieces are set as text, everything else in math mode.
+fn latex(pieces: &[Piece]) -> String {
+    let mut out = String::new();
+    for p in pieces {
+        let math = match p {
+            Piece::Text(t) => {
+                out.push_str(&latex_escape(t));
+                continue;
+            }
+            Piece::In => {
+                out.push_str(" $\\in$ ");
+                continue;
+            }
+            Piece::Nonterm(n) => nt_latex(&NT::Non((*n).into())),
+            Piece::Element(set, "") => match set {
+                SetKind::First => "\\varepsilon".into(),
+                SetKind::Follow => "\\$".into(),
+            },
+            Piece::Element(_, t) => format!("\\texttt{{{}}}", latex_escape(t)),
+            Piece::Set(set, n) => format!(
+                "\\mathrm{{{}}}({})",
+                set_name(*set),
+                nt_latex(&NT::Non((*n).into()))
+            ),
+            Piece::FirstOf(r) => {
+                format!("\\mathrm{{FIRST}}({})", symbols(r, nt_latex, "\\ "))
+            }
+            Piece::Symbols(r) => symbols(r, nt_latex, "\\ "),
+            Piece::Production(l, r) => format!(
+                "{} \\rightarrow {}",
+                nt_latex(&NT::Non((*l).into())),
+                symbols(r, nt_latex, "\\ ")
+            ),
+            Piece::DerivesEpsilon => {
+                out.push_str(" $\\Rightarrow^* \\varepsilon$");
+                continue;
+            }
+        };
+        let _ = write!(out, "${}$", math);
+    }
+    out
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (set, iterations) in [
+            (SetKind::First, &self.first),
+            (SetKind::Follow, &self.follow),
+        ] {
+            for (i, added) in iterations.iter().enumerate() {
+                if added.is_empty() {
+                    writeln!(
+                        f,
+                        "{}, iteration {}: no changes",
+                        set_name(set),
+                        i + 1
+                    )?;
+                    continue;
+                }
+                writeln!(f, "{}, iteration {}:", set_name(set), i + 1)?;
+                for a in added.iter() {
+                    writeln!(f, "    {}", text(&self.pieces(a)))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
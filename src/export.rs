@@ -0,0 +1,214 @@
+use std::fmt::Write;
+
+use crate::analysis::{GrammarAnalysis, ParseTable};
+use crate::grammar::Rstr;
+use crate::serialize::latex_escape;
+
+/// The table laid out for export: a row per nonterminal with rules, in rule
+/// order, and a column per terminal, sorted, with end of input last.
+struct Grid {
+    terminals: Vec<Rstr>,
+    rows: Vec<(Rstr, Vec<Vec<usize>>)>,
+}
+
+impl Grid {
+    fn new(table: &ParseTable, analysis: &GrammarAnalysis) -> Grid {
+        let mut terminals: Vec<Rstr> = analysis
+            .grammar
+            .terminals
+            .iter()
+            .map(|(_, t)| t.clone())
+            .filter(|t| !t.is_empty())
+            .collect();
+        terminals.sort();
+        terminals.push("".into());
+        let rows = analysis
+            .nonterminals()
+            .filter(|n| table.contains(n))
+            .map(|n| {
+                let cells =
+                    terminals.iter().map(|t| table.rules(n, t)).collect();
+                (n.clone(), cells)
+            })
+            .collect();
+        Grid { terminals, rows }
+    }
+
+    fn header(&self) -> impl Iterator<Item = &str> {
+        self.terminals
+            .iter()
+            .map(|t| if t.is_empty() { "$" } else { t.as_ref() })
+    }
+}
+
+fn productions(analysis: &GrammarAnalysis, rules: &[usize]) -> Vec<String> {
+    rules.iter().map(|r| analysis.production(*r)).collect()
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.into()
+    }
+}
+
+fn markdown_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\`*_|<>[]".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The exports have a row per nonterminal and a column per terminal, with
+/// end of input as `$`. A cell names its production; conflicting cells
+/// name all of them and are highlighted.
+impl ParseTable {
+    /// Conflicting cells start with `CONFLICT:` and separate the
+    /// productions with ` / `.
+    pub fn to_csv(&self, analysis: &GrammarAnalysis) -> String {
+        let grid = Grid::new(self, analysis);
+        let mut out = String::new();
+        let header: Vec<String> = grid.header().map(csv_field).collect();
+        let _ = writeln!(out, ",{}", header.join(","));
+        for (n, cells) in grid.rows.iter() {
+            out.push_str(&csv_field(n));
+            for rules in cells.iter() {
+                let cell = productions(analysis, rules).join(" / ");
+                let cell = if rules.len() > 1 {
+                    format!("CONFLICT: {}", cell)
+                } else {
+                    cell
+                };
+                let _ = write!(out, ",{}", csv_field(&cell));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// A GitHub Markdown table; conflicting cells are bold and start with
+    /// ⚠.
+    pub fn to_markdown(&self, analysis: &GrammarAnalysis) -> String {
+        let grid = Grid::new(self, analysis);
+        let mut out = String::from("| |");
+        for t in grid.header() {
+            let _ = write!(out, " {} |", markdown_escape(t));
+        }
+        out.push_str("\n|---|");
+        for _ in grid.terminals.iter() {
+            out.push_str("---|");
+        }
+        out.push('\n');
+        for (n, cells) in grid.rows.iter() {
+            let _ = write!(out, "| {} |", markdown_escape(n));
+            for rules in cells.iter() {
+                let cell: Vec<String> = productions(analysis, rules)
+                    .iter()
+                    .map(|p| markdown_escape(p))
+                    .collect();
+                if rules.len() > 1 {
+                    let _ = write!(out, " **⚠ {}** |", cell.join("<br>"));
+                } else {
+                    let _ = write!(out, " {} |", cell.join(""));
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// A standalone HTML page; conflicting cells have the `conflict` class.
+    pub fn to_html(&self, analysis: &GrammarAnalysis) -> String {
+        let grid = Grid::new(self, analysis);
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>LL(1) parse table</title>\n<style>\n\
+             table { border-collapse: collapse; font-family: monospace; }\n\
+             th, td { border: 1px solid #999; padding: 2px 6px; }\n\
+             td.conflict { background: #f8b4b4; font-weight: bold; }\n\
+             </style>\n</head>\n<body>\n<table>\n<tr><th></th>",
+        );
+        for t in grid.header() {
+            let _ = write!(out, "<th>{}</th>", html_escape(t));
+        }
+        out.push_str("</tr>\n");
+        for (n, cells) in grid.rows.iter() {
+            let _ = write!(out, "<tr><th>{}</th>", html_escape(n));
+            for rules in cells.iter() {
+                let cell: Vec<String> = productions(analysis, rules)
+                    .iter()
+                    .map(|p| html_escape(p))
+                    .collect();
+                if rules.len() > 1 {
+                    let _ = write!(
+                        out,
+                        "<td class=\"conflict\">{}</td>",
+                        cell.join("<br>")
+                    );
+                } else {
+                    let _ = write!(out, "<td>{}</td>", cell.join(""));
+                }
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n</body>\n</html>\n");
+        out
+    }
+
+    /// A LaTeX `tabular`; conflicting cells are red, which needs the
+    /// `xcolor` package.
+    pub fn to_latex(&self, analysis: &GrammarAnalysis) -> String {
+        let grid = Grid::new(self, analysis);
+        let mut out = format!(
+            "\\begin{{tabular}}{{l|{}}}\n",
+            "l".repeat(grid.terminals.len())
+        );
+        for t in grid.header() {
+            let _ = write!(out, " & \\texttt{{{}}}", latex_escape(t));
+        }
+        out.push_str(" \\\\\n\\hline\n");
+        for (n, cells) in grid.rows.iter() {
+            out.push_str(&latex_escape(n));
+            for rules in cells.iter() {
+                let cell: Vec<String> = productions(analysis, rules)
+                    .iter()
+                    .map(|p| {
+                        let p = latex_escape(p).replace('ε', "$\\varepsilon$");
+                        format!("\\texttt{{{}}}", p)
+                    })
+                    .collect();
+                if rules.len() > 1 {
+                    let _ = write!(
+                        out,
+                        " & \\textcolor{{red}}{{{}}}",
+                        cell.join(" / ")
+                    );
+                } else {
+                    let _ = write!(out, " & {}", cell.join(""));
+                }
+            }
+            out.push_str(" \\\\\n");
+        }
+        out.push_str("\\end{tabular}\n");
+        out
+    }
+}
@@ -0,0 +1,384 @@
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+use crate::grammar::{SpanTree, EBNF};
+use crate::parser::{parse_definitions, RuleDefinition};
+use crate::tokeniser::{try_tokenize, Span};
+
+/// How tightly an expression binds: alternations, then concatenations,
+/// then `x +`, then the rest.
+fn precedence(e: &EBNF) -> u8 {
+    match e {
+        EBNF::Or(_) => 0,
+        EBNF::Concat(_) => 1,
+        EBNF::OneOrMore(_) => 2,
+        _ => 3,
+    }
+}
+
+/// The expression with the fewest parentheses, in a place that needs at
+/// least precedence `min`.
+fn show(e: &EBNF, min: u8) -> String {
+    let shown = match e {
+        EBNF::Epsilon => "ε".to_string(),
+        EBNF::Non(n) => n.to_string(),
+        EBNF::Term(t) => format!("\"{}\"", t),
+        EBNF::Iteration(a) => format!("{{ {} }}", show(a, 0)),
+        EBNF::Optional(a) => format!("[ {} ]", show(a, 0)),
+        EBNF::OneOrMore(a) => format!("{} +", show(a, 3)),
+        EBNF::Concat(a) => {
+            let items: Vec<String> = a.iter().map(|x| show(x, 2)).collect();
+            items.join(" ")
+        }
+        EBNF::Or(a) => {
+            let items: Vec<String> = a.iter().map(|x| show(x, 1)).collect();
+            items.join(" | ")
+        }
+    };
+    if precedence(e) < min {
+        format!("( {} )", shown)
+    } else {
+        shown
+    }
+}
+
+fn is_symbol(e: &EBNF) -> bool {
+    matches!(e, EBNF::Epsilon | EBNF::Non(_) | EBNF::Term(_))
+}
+
+fn columns(s: &str) -> usize {
+    s.chars().count()
+}
+
+/// Continues `line` with the first of `shown`, and starts a new line for
+/// each of the others, moving the finished ones to `lines`.
+fn append(lines: &mut Vec<String>, line: &mut String, shown: Vec<String>) {
+    for (k, s) in shown.into_iter().enumerate() {
+        if k == 0 {
+            line.push_str(&s);
+        } else {
+            lines.push(std::mem::replace(line, s));
+        }
+    }
+}
+
+/// `e` as in [`show`], starting at column `col` and broken into lines so
+/// that none goes past `width`, with `end` more columns after the last.
+/// Concatenations are filled, alternations of single symbols too and other
+/// alternations get a line per alternative, with `|` under the bracket
+/// they are in. Lines after the first are indented.
+fn layout(
+    e: &EBNF,
+    min: u8,
+    col: usize,
+    width: usize,
+    end: usize,
+) -> Vec<String> {
+    let flat = show(e, min);
+    if col + columns(&flat) + end <= width {
+        return vec![flat];
+    }
+    // The column after `line`, the one following the finished `lines`.
+    let after = |lines: &[String], line: &str| match lines {
+        [] => col + columns(line),
+        _ => columns(line),
+    };
+    let group = |open: &str, a: &EBNF, close: &str| {
+        let mut lines = layout(a, 0, col + 2, width, end + 2);
+        lines[0] = format!("{} {}", open, lines[0]);
+        if let Some(last) = lines.last_mut() {
+            last.push(' ');
+            last.push_str(close);
+        }
+        lines
+    };
+    if precedence(e) < min {
+        return group("(", e, ")");
+    }
+    match e {
+        EBNF::Iteration(a) => group("{", a, "}"),
+        EBNF::Optional(a) => group("[", a, "]"),
+        EBNF::OneOrMore(a) => {
+            let mut lines = layout(a, 3, col, width, end + 2);
+            if let Some(last) = lines.last_mut() {
+                last.push_str(" +");
+            }
+            lines
+        }
+        EBNF::Concat(items) => {
+            let mut lines = vec![];
+            let mut line = String::new();
+            for (k, x) in items.iter().enumerate() {
+                let x_end = if k + 1 == items.len() { end } else { 0 };
+                let mut at = after(&lines, &line);
+                if k > 0 {
+                    if at + 1 + columns(&show(x, 2)) + x_end > width {
+                        lines.push(std::mem::replace(
+                            &mut line,
+                            " ".repeat(col),
+                        ));
+                        at = col;
+                    } else {
+                        at += 1;
+                        line.push(' ');
+                    }
+                }
+                append(&mut lines, &mut line, layout(x, 2, at, width, x_end));
+            }
+            lines.push(line);
+            lines
+        }
+        EBNF::Or(alternatives) => {
+            let fill = alternatives.iter().all(is_symbol);
+            let bar = format!("{}| ", " ".repeat(col.saturating_sub(2)));
+            let mut lines = vec![];
+            let mut line = String::new();
+            for (k, a) in alternatives.iter().enumerate() {
+                let a_end = if k + 1 == alternatives.len() { end } else { 0 };
+                let shown = show(a, 1);
+                if k > 0
+                    && fill
+                    && after(&lines, &line) + 3 + columns(&shown) + a_end
+                        <= width
+                {
+                    line.push_str(" | ");
+                    line.push_str(&shown);
+                    continue;
+                }
+                if k > 0 {
+                    lines.push(std::mem::replace(&mut line, bar.clone()));
+                }
+                append(&mut lines, &mut line, layout(a, 1, col, width, a_end));
+            }
+            lines.push(line);
+            lines
+        }
+        _ => vec![flat],
+    }
+}
+
+/// A line of the formatted grammar.
+enum Entry {
+    Blank,
+    Comment(Span),
+    Rule {
+        definition: RuleDefinition,
+        /// Comments inside the rule, with the alternative they follow.
+        inner: Vec<(usize, Span)>,
+        /// Comments after the rule on the same line.
+        trailing: Vec<Span>,
+    },
+}
+
+/// Formats a grammar: every rule with the fewest parentheses, the `=` of
+/// consecutive rules aligned and, if it does not fit in `width` columns,
+/// the rule wrapped with `|` under the `=`. Alternations of single symbols
+/// are filled up to the width, others get a line per alternative, and
+/// alternatives that are still too long are wrapped inside. Comments and
+/// single blank lines between rules are kept.
+pub fn format_grammar(source: &str, width: usize) -> Result<String, String> {
+    let (tokens, comments) = try_tokenize(source)?;
+    let text = |s: &Span| &source[s.start..s.end];
+
+    let mut entries: Vec<Entry> = vec![];
+    let mut comments = comments.into_iter().peekable();
+    let mut last_end = None;
+    for definition in parse_definitions(tokens)? {
+        let start = definition.span.start;
+        place(source, &mut entries, &mut comments, &mut last_end, start);
+        if last_end.is_some_and(|e| newlines(source, e, start) > 1) {
+            entries.push(Entry::Blank);
+        }
+        let starts: Vec<usize> = alternatives(&definition)
+            .iter()
+            .map(|(_, s)| s.map_or(0, |s| s.span.start))
+            .collect();
+        let mut inner = vec![];
+        while let Some(c) = comments.next_if(|c| c.start < definition.span.end)
+        {
+            let k = starts.iter().filter(|s| **s <= c.start).count();
+            inner.push((k.saturating_sub(1), c));
+        }
+        last_end = Some(definition.span.end);
+        entries.push(Entry::Rule {
+            definition,
+            inner,
+            trailing: vec![],
+        });
+    }
+    place(
+        source,
+        &mut entries,
+        &mut comments,
+        &mut last_end,
+        usize::MAX,
+    );
+
+    let mut out = String::new();
+    for (i, entry) in entries.iter().enumerate() {
+        match entry {
+            Entry::Blank => out.push('\n'),
+            Entry::Comment(c) => {
+                out.push_str(text(c));
+                out.push('\n');
+            }
+            Entry::Rule {
+                definition,
+                inner,
+                trailing,
+            } => {
+                // Consecutive rules, up to a blank line, align their `=`.
+                let block = |range: &mut dyn Iterator<Item = usize>| {
+                    range
+                        .map_while(|j| match &entries[j] {
+                            Entry::Blank => None,
+                            Entry::Comment(_) => Some(0),
+                            Entry::Rule { definition, .. } => {
+                                Some(definition.nonterm.chars().count())
+                            }
+                        })
+                        .max()
+                        .unwrap_or(0)
+                };
+                let pad = block(&mut (0..i).rev())
+                    .max(block(&mut (i..entries.len())));
+                let comments: Vec<(usize, &str)> =
+                    inner.iter().map(|(k, c)| (*k, text(c))).collect();
+                out.push_str(&show_rule(definition, pad, width, &comments));
+                for c in trailing.iter() {
+                    out.push(' ');
+                    out.push_str(text(c));
+                }
+                out.push('\n');
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn newlines(source: &str, from: usize, to: usize) -> usize {
+    source[from..to].matches('\n').count()
+}
+
+/// Adds the comments starting before `end`, each on its own line or, if it
+/// is on the line of the rule before, after that rule.
+fn place(
+    source: &str,
+    entries: &mut Vec<Entry>,
+    comments: &mut Peekable<IntoIter<Span>>,
+    last_end: &mut Option<usize>,
+    end: usize,
+) {
+    while let Some(c) = comments.next_if(|c| c.start < end) {
+        match (entries.last_mut(), *last_end) {
+            (Some(Entry::Rule { trailing, .. }), Some(e))
+                if newlines(source, e, c.start) == 0 =>
+            {
+                trailing.push(c)
+            }
+            (_, Some(e)) => {
+                if newlines(source, e, c.start) > 1 {
+                    entries.push(Entry::Blank);
+                }
+                entries.push(Entry::Comment(c));
+            }
+            (_, None) => entries.push(Entry::Comment(c)),
+        }
+        *last_end = Some(c.end);
+    }
+}
+
+/// The top level alternatives of a rule, with their spans.
+fn alternatives(
+    definition: &RuleDefinition,
+) -> Vec<(&EBNF, Option<&SpanTree>)> {
+    match &definition.rule {
+        EBNF::Or(a) if a.len() == definition.spans.children.len() => a
+            .iter()
+            .zip(definition.spans.children.iter().map(Some))
+            .collect(),
+        EBNF::Or(a) => a.iter().map(|x| (x, None)).collect(),
+        e => vec![(e, Some(&definition.spans))],
+    }
+}
+
+/// The rule, without a final newline, with the name padded to `pad`.
+fn show_rule(
+    definition: &RuleDefinition,
+    pad: usize,
+    width: usize,
+    comments: &[(usize, &str)],
+) -> String {
+    let mut lines = vec![];
+    if !definition.annotations.is_empty() {
+        let annotations: Vec<String> = definition
+            .annotations
+            .iter()
+            .map(|(name, args)| {
+                let mut a = format!("@{}", name);
+                for arg in args.iter() {
+                    a.push_str(&format!(" \"{}\"", arg));
+                }
+                a
+            })
+            .collect();
+        lines.push(annotations.join(" "));
+    }
+
+    let head = format!("{:<pad$} = ", definition.nonterm, pad = pad);
+    let cont = format!("{}| ", " ".repeat(pad + 1));
+    let alternatives = alternatives(definition);
+    let shown: Vec<String> = alternatives
+        .iter()
+        .enumerate()
+        .map(|(k, (a, _))| {
+            let mut shown = show(a, 1);
+            for (_, c) in comments.iter().filter(|(j, _)| *j == k) {
+                shown.push(' ');
+                shown.push_str(c);
+            }
+            shown
+        })
+        .collect();
+    let one_line = format!("{}{} ;", head, shown.join(" | "));
+    if comments.is_empty() && one_line.chars().count() <= width {
+        lines.push(one_line);
+        return lines.join("\n");
+    }
+
+    let fill =
+        comments.is_empty() && alternatives.iter().all(|(a, _)| is_symbol(a));
+    if !fill {
+        for (k, (a, _)) in alternatives.iter().enumerate() {
+            let end = if k + 1 == alternatives.len() { 2 } else { 0 };
+            let mut shown = layout(a, 1, pad + 3, width, end);
+            if let Some(last) = shown.last_mut() {
+                for (_, c) in comments.iter().filter(|(j, _)| *j == k) {
+                    last.push(' ');
+                    last.push_str(c);
+                }
+            }
+            let prefix = if k == 0 { &head } else { &cont };
+            lines.push(format!("{}{}", prefix, shown[0]));
+            lines.extend(shown.into_iter().skip(1));
+        }
+        if let Some(last) = lines.last_mut() {
+            last.push_str(" ;");
+        }
+        return lines.join("\n");
+    }
+    let mut line = format!("{}{}", head, shown[0]);
+    for (k, a) in shown.iter().enumerate().skip(1) {
+        let end = if k + 1 == shown.len() { 2 } else { 0 };
+        let joined = format!("{} | {}", line, a);
+        if fill && joined.chars().count() + end <= width {
+            line = joined;
+        } else {
+            lines.push(line);
+            line = format!("{}{}", cont, a);
+        }
+    }
+    line.push_str(" ;");
+    lines.push(line);
+    lines.join("\n")
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use crate::grammar::{capitalize_first_letter, GrammarChomsky, Rstr, NT};
+
+/// A xorshift random number generator. It is not good for anything but
+/// picking productions, but the same seed gives the same numbers on every
+/// platform and version.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // Spread the bits of small seeds, the state must not be 0.
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Rng((z ^ (z >> 31)).max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// A number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Generates random sentences of a grammar.
+///
+/// Each nonterminal is expanded with one of its productions, picked at
+/// random by weight. Only productions that can still finish within
+/// `max_depth` levels of the derivation tree are picked, and when there is
+/// none, those that finish soonest, so generating always ends.
+#[derive(Debug, Clone)]
+pub struct Generator<'a> {
+    grammar: &'a GrammarChomsky,
+    /// The productions of each nonterminal, as indices into `rules`.
+    productions: HashMap<Rstr, Vec<usize>>,
+    /// The height of the lowest derivation tree of each production, `None`
+    /// for productions that derive no string of terminals.
+    heights: Vec<Option<usize>>,
+    weights: Vec<f64>,
+    max_depth: usize,
+    rng: Rng,
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(grammar: &'a GrammarChomsky) -> Generator<'a> {
+        let mut productions: HashMap<Rstr, Vec<usize>> = HashMap::new();
+        for (i, (l, _)) in grammar.rules.iter().enumerate() {
+            productions.entry(l.clone()).or_default().push(i);
+        }
+        let mut lowest: HashMap<&Rstr, usize> = HashMap::new();
+        let mut heights = vec![None; grammar.rules.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, (l, r)) in grammar.rules.iter().enumerate() {
+                let height = r.iter().try_fold(1, |h, x| match x {
+                    NT::Non(n) => lowest.get(n).map(|m| h.max(m + 1)),
+                    _ => Some(h),
+                });
+                let Some(h) = height else {
+                    continue;
+                };
+                if heights[i] != height {
+                    heights[i] = height;
+                    changed = true;
+                }
+                let best = lowest.entry(l).or_insert(h);
+                *best = (*best).min(h);
+            }
+        }
+        Generator {
+            grammar,
+            productions,
+            heights,
+            weights: vec![1.0; grammar.rules.len()],
+            max_depth: 20,
+            rng: Rng::new(0),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    /// The depth of the derivation tree after which only the productions
+    /// that finish soonest are picked, 20 by default.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// How likely production `rule` is picked compared to the others of its
+    /// nonterminal, 1 by default. Productions with weight 0 are only picked
+    /// when nothing else can finish.
+    pub fn with_weight(mut self, rule: usize, weight: f64) -> Self {
+        if let Some(w) = self.weights.get_mut(rule) {
+            *w = weight.max(0.0);
+        }
+        self
+    }
+
+    /// A random sentence of the start nonterminal.
+    pub fn sentence(&mut self) -> Option<Vec<Rstr>> {
+        let start = self.grammar.start_nonterm.clone();
+        self.sentence_from(&start)
+    }
+
+    /// A random sentence of `nonterm`, `None` if it derives none.
+    pub fn sentence_from(&mut self, nonterm: &str) -> Option<Vec<Rstr>> {
+        let mut out = vec![];
+        let mut stack = vec![(NT::Non(capitalize_first_letter(nonterm)), 0)];
+        while let Some((symbol, depth)) = stack.pop() {
+            match symbol {
+                NT::Non(n) => {
+                    let rule = self.pick(&n, depth)?;
+                    let r = &self.grammar.rules[rule].1;
+                    stack
+                        .extend(r.iter().rev().map(|x| (x.clone(), depth + 1)));
+                }
+                NT::Term(t) if !t.is_empty() => out.push(t),
+                _ => {}
+            }
+        }
+        Some(out)
+    }
+
+    fn pick(&mut self, nonterm: &Rstr, depth: usize) -> Option<usize> {
+        let finishing: Vec<(usize, usize)> = self
+            .productions
+            .get(nonterm)?
+            .iter()
+            .filter_map(|i| self.heights[*i].map(|h| (*i, h)))
+            .collect();
+        let lowest = finishing.iter().map(|(_, h)| *h).min()?;
+        let left = self.max_depth.saturating_sub(depth).max(lowest);
+        let mut candidates: Vec<usize> = finishing
+            .iter()
+            .filter(|(i, h)| *h <= left && self.weights[*i] > 0.0)
+            .map(|(i, _)| *i)
+            .collect();
+        if candidates.is_empty() {
+            candidates = finishing
+                .iter()
+                .filter(|(_, h)| *h == lowest)
+                .map(|(i, _)| *i)
+                .collect();
+        }
+        let total: f64 = candidates.iter().map(|i| self.weights[*i]).sum();
+        if total <= 0.0 {
+            let k = self.rng.next_u64() % candidates.len() as u64;
+            return Some(candidates[k as usize]);
+        }
+        let mut x = self.rng.next_f64() * total;
+        for i in candidates.iter() {
+            x -= self.weights[*i];
+            if x < 0.0 {
+                return Some(*i);
+            }
+        }
+        candidates.last().copied()
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Display;
+
+use crate::cnf::CnfRule;
+use crate::grammar::{
+    GrammarChomsky, Helper, HelperKind, HelperNames, Rstr, RuleOrigin, NT,
+};
+use crate::left_recursion::{
+    remove_direct, source, substitute_earlier, Productions,
+};
+
+/// One step of [`GrammarChomsky::to_gnf`], with the rules it produced.
+#[derive(Debug, Clone)]
+pub enum GnfStep {
+    /// The grammar in Chomsky normal form, which the conversion starts from.
+    Cnf(GrammarChomsky),
+    /// The rules of `nonterm` starting with `using` got the rules of
+    /// `using` in place of it.
+    Substitute {
+        nonterm: Rstr,
+        using: Rstr,
+        rules: Vec<Vec<NT>>,
+    },
+    /// `nonterm -> nonterm α | β` was replaced by `nonterm -> β | β tail`
+    /// and `tail -> α | α tail`.
+    LeftRecursion {
+        nonterm: Rstr,
+        tail: Rstr,
+        rules: Vec<Vec<NT>>,
+        tail_rules: Vec<Vec<NT>>,
+    },
+    /// Nonterminals no longer reachable from the start were dropped.
+    Unreachable(Vec<Rstr>),
+}
+
+fn show_rules(
+    f: &mut std::fmt::Formatter<'_>,
+    nonterm: &Rstr,
+    rules: &[Vec<NT>],
+) -> std::fmt::Result {
+    for r in rules.iter() {
+        write!(f, "  {} ->", nonterm)?;
+        for nt in r.iter() {
+            write!(f, " {}", nt)?;
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+impl Display for GnfStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GnfStep::Cnf(grammar) => {
+                writeln!(f, "Chomsky normal form:")?;
+                for line in grammar.to_string().lines() {
+                    writeln!(f, "  {}", line)?;
+                }
+                Ok(())
+            }
+            GnfStep::Substitute {
+                nonterm,
+                using,
+                rules,
+            } => {
+                writeln!(
+                    f,
+                    "Substitute {} in the rules of {}:",
+                    using, nonterm
+                )?;
+                show_rules(f, nonterm, rules)
+            }
+            GnfStep::LeftRecursion {
+                nonterm,
+                tail,
+                rules,
+                tail_rules,
+            } => {
+                writeln!(
+                    f,
+                    "Remove the left recursion of {} with {}:",
+                    nonterm, tail
+                )?;
+                show_rules(f, nonterm, rules)?;
+                show_rules(f, tail, tail_rules)
+            }
+            GnfStep::Unreachable(nonterms) => {
+                let names: Vec<&str> =
+                    nonterms.iter().map(|n| n.as_ref()).collect();
+                writeln!(f, "Drop the unreachable {}", names.join(", "))
+            }
+        }
+    }
+}
+
+/// The rules of a grammar being converted, by nonterminal.
+struct Rules {
+    order: Vec<Rstr>,
+    of: Productions<()>,
+}
+
+impl Rules {
+    fn of(&self, nonterm: &Rstr) -> Vec<Vec<NT>> {
+        self.of[nonterm].iter().map(|(r, _)| r.clone()).collect()
+    }
+
+    fn grammar(
+        &self,
+        start: &Rstr,
+        helpers: &BTreeMap<Rstr, Helper>,
+    ) -> GrammarChomsky {
+        let mut rules = vec![];
+        let mut origins = vec![];
+        for n in self.order.iter() {
+            for (definition, (r, _)) in self.of[n].iter().enumerate() {
+                let source: Vec<&str> = r.iter().map(NT::name).collect();
+                rules.push((n.clone(), r.clone()));
+                origins.push(RuleOrigin {
+                    rule: n.clone(),
+                    definition,
+                    source: source.join(" ").into(),
+                });
+            }
+        }
+        let mut grammar = GrammarChomsky::from_rules(
+            start.clone(),
+            rules,
+            origins,
+            BTreeMap::new(),
+        );
+        grammar.helpers = helpers
+            .iter()
+            .filter(|(h, _)| grammar.nonterminals.contains(*h))
+            .map(|(h, helper)| (h.clone(), helper.clone()))
+            .collect();
+        grammar
+    }
+}
+
+impl GrammarChomsky {
+    /// The grammar in Greibach normal form, where every rule is
+    /// `A -> a B1 … Bn`, and the steps taken to get there.
+    ///
+    /// Starting from [`GrammarChomsky::to_cnf`], the nonterminals are put in
+    /// order. Going forward, each gets the rules of earlier ones
+    /// substituted at the start of its rules, after which its left recursion
+    /// is direct and is removed with a new [`HelperKind::Tail`] helper
+    /// `A__tail1`. Going back, every
+    /// nonterminal then gets later ones substituted, as those already start
+    /// with a terminal, and last the tails do too. If the language has the
+    /// empty string, the start gets `S -> ε`, or if `S` is used in a rule, a
+    /// new [`HelperKind::Start`] helper `S__start1 -> ε` with the rules of
+    /// `S` takes its place.
+    pub fn to_gnf(&self) -> (GrammarChomsky, Vec<GnfStep>) {
+        let cnf = self.to_cnf();
+        let mut start = cnf.start.clone();
+        let mut rules = Rules {
+            order: vec![],
+            of: HashMap::new(),
+        };
+        for (l, r) in cnf.rules.iter() {
+            if !rules.of.contains_key(l) {
+                rules.order.push(l.clone());
+            }
+            let r = match r {
+                CnfRule::Terminal(a) => vec![NT::Term(a.clone())],
+                CnfRule::Pair(b, c) => {
+                    vec![NT::Non(b.clone()), NT::Non(c.clone())]
+                }
+            };
+            rules.of.entry(l.clone()).or_default().push((r, ()));
+        }
+        let mut names = HelperNames::new(self);
+        names.extend(&cnf.helpers);
+        let mut steps =
+            vec![GnfStep::Cnf(rules.grammar(&start, &names.helpers))];
+        let order = rules.order.clone();
+        let mut tails = vec![];
+
+        for (i, a) in order.iter().enumerate() {
+            // Substituting an earlier nonterminal gives rules starting with
+            // a later one than it, so this ends.
+            substitute_earlier(&mut rules.of, a, &order[..i], |using, r| {
+                steps.push(GnfStep::Substitute {
+                    nonterm: a.clone(),
+                    using: using.clone(),
+                    rules: r.iter().map(|(r, _)| r.clone()).collect(),
+                })
+            });
+            let Some(tail) = remove_direct(&mut rules.of, a, false, |alphas| {
+                let rule = match names.helpers.get(a) {
+                    Some(helper) => helper.rule.clone(),
+                    None => self.rule_of(a),
+                };
+                let alternatives: Vec<String> =
+                    alphas.iter().map(|r| source(r)).collect();
+                names.add(
+                    &rule,
+                    HelperKind::Tail,
+                    alternatives.join(" | ").into(),
+                    Default::default(),
+                )
+            }) else {
+                continue;
+            };
+            let at = rules.order.iter().position(|n| n == a).unwrap_or(0);
+            rules.order.insert(at + 1, tail.clone());
+            tails.push(tail.clone());
+            steps.push(GnfStep::LeftRecursion {
+                nonterm: a.clone(),
+                rules: rules.of(a),
+                tail_rules: rules.of(&tail),
+                tail,
+            });
+        }
+
+        for (i, a) in order.iter().enumerate().rev() {
+            substitute_earlier(
+                &mut rules.of,
+                a,
+                &order[i + 1..],
+                |using, r| {
+                    steps.push(GnfStep::Substitute {
+                        nonterm: a.clone(),
+                        using: using.clone(),
+                        rules: r.iter().map(|(r, _)| r.clone()).collect(),
+                    })
+                },
+            );
+        }
+        let all = rules.order.clone();
+        for z in tails.iter() {
+            substitute_earlier(&mut rules.of, z, &all, |using, r| {
+                steps.push(GnfStep::Substitute {
+                    nonterm: z.clone(),
+                    using: using.clone(),
+                    rules: r.iter().map(|(r, _)| r.clone()).collect(),
+                })
+            });
+        }
+
+        let mut reachable: HashSet<Rstr> = HashSet::from([start.clone()]);
+        let mut todo = vec![start.clone()];
+        while let Some(n) = todo.pop() {
+            for (r, _) in rules.of.get(&n).into_iter().flatten() {
+                for x in r.iter() {
+                    if let NT::Non(m) = x {
+                        if reachable.insert(m.clone()) {
+                            todo.push(m.clone());
+                        }
+                    }
+                }
+            }
+        }
+        let unreachable: Vec<Rstr> = rules
+            .order
+            .iter()
+            .filter(|n| !reachable.contains(*n))
+            .cloned()
+            .collect();
+        if !unreachable.is_empty() {
+            rules.order.retain(|n| reachable.contains(n));
+            steps.push(GnfStep::Unreachable(unreachable));
+        }
+        if cnf.empty {
+            let start_nt = NT::Non(start.clone());
+            let on_right = rules
+                .order
+                .iter()
+                .flat_map(|n| rules.of[n].iter())
+                .any(|(r, _)| r.contains(&start_nt));
+            if on_right {
+                // `S -> ε` would let every `S` inside a rule derive ε too.
+                let empty_start = names.add(
+                    &self.rule_of(&start),
+                    HelperKind::Start,
+                    format!("ε | {}", start).into(),
+                    Default::default(),
+                );
+                let copied = rules.of[&start].clone();
+                rules.of.insert(empty_start.clone(), copied);
+                rules.order.insert(0, empty_start.clone());
+                start = empty_start;
+            } else if !rules.of.contains_key(&start) {
+                rules.order.insert(0, start.clone());
+            }
+            rules
+                .of
+                .entry(start.clone())
+                .or_default()
+                .insert(0, (vec![NT::Epsilon], ()));
+        }
+        (rules.grammar(&start, &names.helpers), steps)
+    }
+}
@@ -1,11 +1,13 @@
 use std::{
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fmt::Display,
     io::IsTerminal,
-    rc::Rc,
+    sync::Arc,
 };
 
-pub type Rstr = Rc<str>;
+use crate::tokeniser::Span;
+
+pub type Rstr = Arc<str>;
 
 fn termcolor(r: u8, g: u8, b: u8) -> String {
     if std::io::stdout().is_terminal() {
@@ -23,36 +25,19 @@ fn termreset() -> String {
     }
 }
 
-fn num_to_alphastr(mut num: u32) -> Rstr {
-    let mut result = "".to_string();
-    num += 1;
-
-    while num > 0 {
-        let remainder = (num % 26) as u8;
-        result.push((b'A' + remainder - if num >= 26 { 0 } else { 1 }) as char);
-        num /= 26;
-        if num == 0 {
-            break;
-        }
-    }
-
-    result.push('_');
-
-    Rc::from(result.chars().rev().collect::<String>())
-}
-
-fn capitalize_first_letter(s: &str) -> Rstr {
+/// The name a nonterminal of the EBNF grammar has in the BNF grammar.
+pub fn capitalize_first_letter(s: &str) -> Rstr {
     let mut c = s.chars();
 
     match c.next() {
-        None => Rc::from(""), // Handle empty string case
-        Some(first_char) => {
-            Rc::from(first_char.to_uppercase().collect::<String>() + c.as_str())
-        }
+        None => Arc::from(""), // Handle empty string case
+        Some(first_char) => Arc::from(
+            first_char.to_uppercase().collect::<String>() + c.as_str(),
+        ),
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EBNF {
     Epsilon,
     Non(Rstr),
@@ -64,33 +49,265 @@ pub enum EBNF {
     Concat(Vec<EBNF>),
 }
 
-#[derive(Debug)]
+/// How the nodes of a rule appear in the `AST`, set by annotations in front
+/// of the rule:
+///
+/// - `@inline` leaves the node out, its children go to the parent,
+/// - `@collapse` replaces the node by its child if it has only one,
+/// - `@drop` drops all tokens matched directly by the rule, `@drop "(" ")"`
+///   only the listed ones,
+/// - `@keep "x"` keeps the listed tokens even if they would be dropped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleShape {
+    pub inline: bool,
+    pub collapse: bool,
+    pub drop_all: bool,
+    pub drop: BTreeSet<Rstr>,
+    pub keep: BTreeSet<Rstr>,
+}
+
+impl RuleShape {
+    pub fn drops(&self, token: &str) -> bool {
+        !self.keep.contains(token)
+            && (self.drop_all || self.drop.contains(token))
+    }
+
+    pub fn apply(
+        &mut self,
+        annotation: &str,
+        args: Vec<Rstr>,
+    ) -> Result<(), String> {
+        match annotation {
+            "inline" | "collapse" if !args.is_empty() => {
+                return Err(format!("@{} takes no arguments", annotation))
+            }
+            "inline" => self.inline = true,
+            "collapse" => self.collapse = true,
+            "drop" if args.is_empty() => self.drop_all = true,
+            "drop" => self.drop.extend(args),
+            "keep" => self.keep.extend(args),
+            other => return Err(format!("Unknown annotation @{}", other)),
+        }
+        Ok(())
+    }
+
+    /// The annotations that give this shape, e.g. `@drop "(" ")"`.
+    pub fn annotations(&self) -> Vec<String> {
+        let args = |set: &BTreeSet<Rstr>| -> String {
+            set.iter().map(|t| format!(" \"{}\"", t)).collect()
+        };
+        let mut out = vec![];
+        if self.inline {
+            out.push("@inline".to_string());
+        }
+        if self.collapse {
+            out.push("@collapse".to_string());
+        }
+        if self.drop_all {
+            out.push("@drop".to_string());
+        }
+        if !self.drop.is_empty() {
+            out.push(format!("@drop{}", args(&self.drop)));
+        }
+        if !self.keep.is_empty() {
+            out.push(format!("@keep{}", args(&self.keep)));
+        }
+        out
+    }
+}
+
+/// Where an [`EBNF`] expression is in the grammar source, with the spans of
+/// its parts in the same order as in the expression.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanTree {
+    pub span: Span,
+    pub children: Vec<SpanTree>,
+}
+
+#[derive(Debug, Clone)]
 pub struct GrammarEBNF {
     pub start_nonterm: Rstr,
     pub rules: BTreeMap<Rstr, Vec<EBNF>>,
+    pub shapes: BTreeMap<Rstr, RuleShape>,
+    /// The spans of the definitions in `rules`, empty if the grammar was
+    /// parsed without them.
+    pub spans: BTreeMap<Rstr, Vec<SpanTree>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum NT {
     Epsilon,
-    Non(Rc<str>),
-    Term(Rc<str>),
+    Non(Arc<str>),
+    Term(Arc<str>),
 }
 
 #[derive(Debug, Clone)]
 pub struct GrammarChomsky {
-    pub nonterminals: HashSet<Rc<str>>,
-    pub terminals: HashSet<Rc<str>>,
-    pub rules: Vec<(Rc<str>, Vec<NT>)>,
-    pub start_nonterm: Rc<str>,
+    pub nonterminals: HashSet<Arc<str>>,
+    pub terminals: HashSet<Arc<str>>,
+    pub rules: Vec<(Arc<str>, Vec<NT>)>,
+    pub start_nonterm: Arc<str>,
+    /// The EBNF rule each production in `rules` was generated from.
+    pub origins: Vec<RuleOrigin>,
+    pub shapes: BTreeMap<Arc<str>, RuleShape>,
+    /// Helpers added by [`GrammarChomsky::remove_left_recursion`], mapped to
+    /// the nonterminal whose left recursion they replace.
+    pub tails: BTreeMap<Arc<str>, Arc<str>>,
+    /// Nonterminals added by [`GrammarEBNF::to_chomsky`] for parts of EBNF
+    /// rules, and by later transformations.
+    pub helpers: BTreeMap<Arc<str>, Helper>,
+}
+
+/// The EBNF construct a [`Helper`] stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HelperKind {
+    /// An alternation `a | b`.
+    Alt,
+    /// An option `[ a ]`.
+    Opt,
+    /// A repetition `{ a }` or `a *`, or the rest of `a +`.
+    Rep,
+    /// A repetition `a +`.
+    Plus,
+    /// The rest of a left-recursive nonterminal, added by
+    /// [`GrammarChomsky::remove_left_recursion`] and
+    /// [`GrammarChomsky::to_gnf`].
+    Tail,
+    /// A terminal in a longer rule of [`GrammarChomsky::to_cnf`].
+    Term,
+    /// The rest of a rule split up by [`GrammarChomsky::to_cnf`].
+    Chain,
+    /// What follows a prefix pulled out by [`GrammarChomsky::left_factor`].
+    Fact,
+    /// The start of [`GrammarChomsky::to_gnf`] when the language has the
+    /// empty string and the old start is used in a rule.
+    Start,
+}
+
+impl HelperKind {
+    /// The part of the helper names after the rule, `__alt1` for `Alt`.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            HelperKind::Alt => "alt",
+            HelperKind::Opt => "opt",
+            HelperKind::Rep => "rep",
+            HelperKind::Plus => "plus",
+            HelperKind::Tail => "tail",
+            HelperKind::Term => "term",
+            HelperKind::Chain => "chain",
+            HelperKind::Fact => "fact",
+            HelperKind::Start => "start",
+        }
+    }
+}
+
+/// A nonterminal standing for a construct inside an EBNF rule, named
+/// `Rule__opt1`, `Rule__rep2`, … after the rule and the construct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Helper {
+    /// The EBNF rule the construct is in.
+    pub rule: Rstr,
+    pub kind: HelperKind,
+    /// The text of the construct.
+    pub source: Rstr,
+    /// Where the construct is in the grammar source, empty if the grammar
+    /// was parsed without spans.
+    pub span: Span,
+}
+
+/// Names the helpers of [`GrammarEBNF::to_chomsky`] and of the later
+/// transformations so that they differ from each other and from every
+/// nonterminal of the grammar.
+pub(crate) struct HelperNames {
+    taken: HashSet<Rstr>,
+    /// The last number used for each rule and kind.
+    counters: HashMap<(Rstr, HelperKind), usize>,
+    pub(crate) helpers: BTreeMap<Rstr, Helper>,
+}
+
+impl HelperNames {
+    /// Names that differ from the nonterminals of `grammar`, adding to its
+    /// helpers.
+    pub(crate) fn new(grammar: &GrammarChomsky) -> HelperNames {
+        HelperNames {
+            taken: grammar.nonterminals.clone(),
+            counters: HashMap::new(),
+            helpers: grammar.helpers.clone(),
+        }
+    }
+
+    /// Takes the names of `helpers` too, keeping them.
+    pub(crate) fn extend(&mut self, helpers: &BTreeMap<Rstr, Helper>) {
+        for (name, helper) in helpers.iter() {
+            self.taken.insert(name.clone());
+            self.helpers.insert(name.clone(), helper.clone());
+        }
+    }
+
+    fn fresh(
+        &mut self,
+        rule: &Rstr,
+        kind: HelperKind,
+        source: &EBNF,
+        span: Option<&SpanTree>,
+    ) -> Rstr {
+        let span = span.map(|s| s.span).unwrap_or_default();
+        self.add(rule, kind, source.to_ebnf().into(), span)
+    }
+
+    /// A new helper `Rule__kindN` for a construct in the EBNF rule `rule`.
+    ///
+    /// `Rule` is the capitalised name `rule` has in the BNF grammar, not the
+    /// spelling of the EBNF source: [`GrammarEBNF::to_chomsky`] capitalises
+    /// every nonterminal, and a helper is named like the other nonterminals
+    /// of its rule so the two sort and read together.
+    pub(crate) fn add(
+        &mut self,
+        rule: &Rstr,
+        kind: HelperKind,
+        source: Rstr,
+        span: Span,
+    ) -> Rstr {
+        let parent = capitalize_first_letter(rule);
+        let k = self.counters.entry((parent.clone(), kind)).or_insert(0);
+        let name = loop {
+            *k += 1;
+            let name: Rstr =
+                format!("{}__{}{}", parent, kind.suffix(), k).into();
+            if self.taken.insert(name.clone()) {
+                break name;
+            }
+        };
+        self.helpers.insert(
+            name.clone(),
+            Helper {
+                rule: rule.clone(),
+                kind,
+                source,
+                span,
+            },
+        );
+        name
+    }
+}
+
+/// Where a chomsky production came from: definition number `definition`
+/// (from 0) of `rule` in the EBNF grammar, whose text is `source`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleOrigin {
+    pub rule: Rstr,
+    pub definition: usize,
+    pub source: Rstr,
 }
 
 impl GrammarChomsky {
-    fn from_rules(
-        start_nonterm: Rc<str>,
-        rules: Vec<(Rc<str>, Vec<NT>)>,
+    pub(crate) fn from_rules(
+        start_nonterm: Arc<str>,
+        rules: Vec<(Arc<str>, Vec<NT>)>,
+        origins: Vec<RuleOrigin>,
+        shapes: BTreeMap<Arc<str>, RuleShape>,
     ) -> Self {
-        let nonterminals: HashSet<Rc<str>> = rules
+        let nonterminals: HashSet<Arc<str>> = rules
             .iter()
             .map(|(l, _)| l.clone())
             .chain(rules.iter().flat_map(|(_, r)| {
@@ -104,10 +321,9 @@ impl GrammarChomsky {
             }))
             .collect();
 
-        let terminals: HashSet<Rc<str>> = rules
+        let terminals: HashSet<Arc<str>> = rules
             .iter()
-            .map(|(l, _)| l.clone())
-            .chain(rules.iter().flat_map(|(_, r)| {
+            .flat_map(|(_, r)| {
                 r.iter().filter_map(|x| {
                     if let NT::Term(nt) = x {
                         Some(nt.clone())
@@ -115,44 +331,114 @@ impl GrammarChomsky {
                         None
                     }
                 })
-            }))
+            })
             .collect();
 
-        for n in nonterminals.iter() {
-            if !rules.iter().any(|(l, _)| l == n) {
-                panic!("Invalid gramar")
-            }
-        }
-
         GrammarChomsky {
             rules,
             start_nonterm: capitalize_first_letter(&start_nonterm),
             nonterminals,
             terminals,
+            origins,
+            shapes,
+            tails: BTreeMap::new(),
+            helpers: BTreeMap::new(),
+        }
+    }
+
+    /// Whether production `rule` belongs to a nonterminal added for a part
+    /// of an EBNF rule rather than to the rule itself.
+    pub(crate) fn is_generated(&self, rule: usize) -> bool {
+        self.origins.get(rule).is_some_and(|o| {
+            self.rules[rule].0 != capitalize_first_letter(&o.rule)
+        })
+    }
+
+    pub fn shape(&self, nonterm: &str) -> Option<&RuleShape> {
+        self.shapes.get(nonterm)
+    }
+
+    /// The EBNF rule `nonterm` belongs to, itself if it is not known.
+    pub(crate) fn rule_of(&self, nonterm: &Rstr) -> Rstr {
+        if let Some(helper) = self.helpers.get(nonterm) {
+            return helper.rule.clone();
         }
+        self.rules
+            .iter()
+            .zip(self.origins.iter())
+            .find(|((l, _), _)| l == nonterm)
+            .map(|(_, o)| o.rule.clone())
+            .unwrap_or_else(|| nonterm.clone())
     }
 }
 
 impl GrammarEBNF {
+    /// The grammar in the syntax it is parsed from, the start rule first and
+    /// every rule after its annotations.
     pub fn to_ebnf(&self) -> String {
+        let start = self.rules.get_key_value(&self.start_nonterm);
+        let rest = self.rules.iter().filter(|(n, _)| **n != self.start_nonterm);
         let mut out = String::new();
-        for rule in self.rules.iter() {
-            for r in rule.1 {
-                out = format!("{}\n{} = {}", out, rule.0, r.to_ebnf());
+        for (nonterm, definitions) in start.into_iter().chain(rest) {
+            let annotations = self
+                .shapes
+                .get(nonterm)
+                .map(|s| s.annotations())
+                .unwrap_or_default();
+            if !annotations.is_empty() {
+                out.push_str(&annotations.join(" "));
+                out.push('\n');
+            }
+            for r in definitions {
+                out.push_str(&format!("{} = {} ;\n", nonterm, r.to_ebnf()));
             }
         }
         out
     }
+    /// The grammar with one nonterminal per rule and a [`Helper`] for every
+    /// alternation, option and repetition inside the rules.
     pub fn to_chomsky(&self) -> GrammarChomsky {
+        let mut names = HelperNames {
+            taken: self
+                .rules
+                .iter()
+                .flat_map(|(l, r)| {
+                    std::iter::once(capitalize_first_letter(l))
+                        .chain(r.iter().flat_map(EBNF::nonterminals))
+                })
+                .collect(),
+            counters: HashMap::new(),
+            helpers: BTreeMap::new(),
+        };
         let mut new_rules = vec![];
-        let mut new_nonterms = 0;
+        let mut origins = vec![];
         for rule in self.rules.iter() {
-            for r in rule.1.iter() {
-                let nr = r.to_chomsky(&mut new_rules, &mut new_nonterms);
-                new_rules.push((capitalize_first_letter(rule.0), nr))
+            let spans = self.spans.get(rule.0);
+            for (definition, r) in rule.1.iter().enumerate() {
+                let span = spans.and_then(|s| s.get(definition));
+                let nr = r.to_chomsky(rule.0, span, &mut new_rules, &mut names);
+                new_rules.push((capitalize_first_letter(rule.0), nr));
+                let origin = RuleOrigin {
+                    rule: rule.0.clone(),
+                    definition,
+                    source: r.to_ebnf().into(),
+                };
+                origins.resize(new_rules.len(), origin);
             }
         }
-        GrammarChomsky::from_rules(self.start_nonterm.clone(), new_rules)
+        let shapes = self
+            .shapes
+            .iter()
+            .map(|(n, s)| (capitalize_first_letter(n), s.clone()))
+            .collect();
+        let mut grammar = GrammarChomsky::from_rules(
+            self.start_nonterm.clone(),
+            new_rules,
+            origins,
+            shapes,
+        );
+        grammar.helpers = names.helpers;
+        grammar
     }
 }
 
@@ -169,6 +455,16 @@ impl Display for GrammarChomsky {
     }
 }
 
+impl NT {
+    /// The symbol without colours, `ε` for epsilon.
+    pub fn name(&self) -> &str {
+        match self {
+            NT::Epsilon => "ε",
+            NT::Non(a) | NT::Term(a) => a,
+        }
+    }
+}
+
 impl Display for NT {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -214,54 +510,66 @@ impl EBNF {
             EBNF::Term(a) => format!("\"{}\"", a),
         }
     }
+    /// The nonterminals used in the expression, capitalised.
+    fn nonterminals(&self) -> Vec<Rstr> {
+        match self {
+            EBNF::Epsilon | EBNF::Term(_) => vec![],
+            EBNF::Non(a) => vec![capitalize_first_letter(a)],
+            EBNF::Iteration(a) | EBNF::OneOrMore(a) | EBNF::Optional(a) => {
+                a.nonterminals()
+            }
+            EBNF::Or(a) | EBNF::Concat(a) => {
+                a.iter().flat_map(EBNF::nonterminals).collect()
+            }
+        }
+    }
+
     fn to_chomsky(
         &self,
+        rule: &Rstr,
+        span: Option<&SpanTree>,
         rules: &mut Vec<(Rstr, Vec<NT>)>,
-        nonterm_counter: &mut u32,
+        names: &mut HelperNames,
     ) -> Vec<NT> {
+        let child = |i: usize| span.and_then(|s| s.children.get(i));
         match self {
             EBNF::Epsilon => vec![NT::Epsilon],
             EBNF::Non(a) => vec![NT::Non(capitalize_first_letter(a))],
             EBNF::Term(a) => vec![NT::Term(a.clone())],
             EBNF::Concat(a) => {
                 let mut v = vec![];
-                for a in a {
-                    v.extend(a.to_chomsky(rules, nonterm_counter));
+                for (i, a) in a.iter().enumerate() {
+                    v.extend(a.to_chomsky(rule, child(i), rules, names));
                 }
                 v
             }
             EBNF::Or(a) => {
-                let new_nt = num_to_alphastr(*nonterm_counter);
-                *nonterm_counter += 1;
-                for a in a {
-                    let na = a.to_chomsky(rules, nonterm_counter);
+                let new_nt = names.fresh(rule, HelperKind::Alt, self, span);
+                for (i, a) in a.iter().enumerate() {
+                    let na = a.to_chomsky(rule, child(i), rules, names);
                     rules.push((new_nt.clone(), na));
                 }
                 vec![NT::Non(new_nt)]
             }
             EBNF::Optional(a) => {
-                let na = a.to_chomsky(rules, nonterm_counter);
-                let new_nt = num_to_alphastr(*nonterm_counter);
-                *nonterm_counter += 1;
+                let na = a.to_chomsky(rule, child(0), rules, names);
+                let new_nt = names.fresh(rule, HelperKind::Opt, self, span);
                 rules.push((new_nt.clone(), na));
                 rules.push((new_nt.clone(), vec![NT::Epsilon]));
                 vec![NT::Non(new_nt)]
             }
             EBNF::Iteration(a) => {
-                let mut na = a.to_chomsky(rules, nonterm_counter);
-                let new_nt = num_to_alphastr(*nonterm_counter);
-                *nonterm_counter += 1;
+                let mut na = a.to_chomsky(rule, child(0), rules, names);
+                let new_nt = names.fresh(rule, HelperKind::Rep, self, span);
                 rules.push((new_nt.clone(), vec![NT::Epsilon]));
                 na.push(NT::Non(new_nt.clone()));
                 rules.push((new_nt.clone(), na));
                 vec![NT::Non(new_nt)]
             }
             EBNF::OneOrMore(a) => {
-                let mut na = a.to_chomsky(rules, nonterm_counter);
-                let new_nt = num_to_alphastr(*nonterm_counter);
-                *nonterm_counter += 1;
-                let new_new_nt = num_to_alphastr(*nonterm_counter);
-                *nonterm_counter += 1;
+                let mut na = a.to_chomsky(rule, child(0), rules, names);
+                let new_nt = names.fresh(rule, HelperKind::Plus, self, span);
+                let new_new_nt = names.fresh(rule, HelperKind::Rep, self, span);
                 na.push(NT::Non(new_new_nt.clone()));
                 rules.push((new_nt.clone(), na.clone()));
                 rules.push((new_new_nt.clone(), na));
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::grammar::{
+    GrammarChomsky, HelperKind, HelperNames, Rstr, RuleOrigin, NT,
+};
+use crate::left_recursion::source;
+
+/// One step of [`GrammarChomsky::left_factor`]: the rules of `nonterm`
+/// starting with `prefix` were replaced by `nonterm -> prefix helper`.
+/// `rule` is the EBNF rule they came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Factoring {
+    pub rule: Rstr,
+    pub nonterm: Rstr,
+    pub prefix: Vec<NT>,
+    pub helper: Rstr,
+    /// How many rules shared the prefix.
+    pub alternatives: usize,
+}
+
+impl Display for Factoring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let prefix: Vec<&str> = self.prefix.iter().map(NT::name).collect();
+        write!(
+            f,
+            "{}: factored `{}` out of {} alternatives into {}",
+            self.rule,
+            prefix.join(" "),
+            self.alternatives,
+            self.helper
+        )
+    }
+}
+
+fn common_prefix(a: &[NT], b: &[NT]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+impl GrammarChomsky {
+    /// An equivalent grammar in which no two rules of a nonterminal start
+    /// with the same symbol. The longest prefix shared by such rules is
+    /// pulled out, `A -> α β | α γ` becoming `A -> α A__fact1` and
+    /// `A__fact1 -> β | γ`, until nothing changes. The helpers are
+    /// [`HelperKind::Fact`] helpers of the EBNF rule and inlined in the
+    /// trees.
+    ///
+    /// Also returns what was factored, in order.
+    pub fn left_factor(&self) -> (GrammarChomsky, Vec<Factoring>) {
+        let mut order: Vec<Rstr> = vec![];
+        let mut productions: HashMap<Rstr, Vec<(Vec<NT>, RuleOrigin)>> =
+            HashMap::new();
+        for ((l, r), o) in self.rules.iter().zip(self.origins.iter()) {
+            if !productions.contains_key(l) {
+                order.push(l.clone());
+            }
+            productions
+                .entry(l.clone())
+                .or_default()
+                .push((r.clone(), o.clone()));
+        }
+        let mut names = HelperNames::new(self);
+        let mut shapes = self.shapes.clone();
+        let mut report = vec![];
+        // Helpers go after the nonterminal and its earlier helpers.
+        let mut helpers: HashMap<Rstr, usize> = HashMap::new();
+
+        let mut i = 0;
+        while i < order.len() {
+            let nonterm = order[i].clone();
+            let rules = &productions[&nonterm];
+            // The first rule that shares its first symbol with a later one.
+            let shared = rules.iter().enumerate().find_map(|(a, (r, _))| {
+                let first = r.first().filter(|x| **x != NT::Epsilon)?;
+                let group: Vec<usize> = (a..rules.len())
+                    .filter(|b| rules[*b].0.first() == Some(first))
+                    .collect();
+                (group.len() > 1).then_some(group)
+            });
+            let Some(group) = shared else {
+                i += 1;
+                continue;
+            };
+            let first = &rules[group[0]].0;
+            let len = group
+                .iter()
+                .map(|b| common_prefix(first, &rules[*b].0))
+                .min()
+                .unwrap_or(0);
+            let prefix = first[..len].to_vec();
+            let origin = rules[group[0]].1.clone();
+
+            let alternatives: Vec<String> = group
+                .iter()
+                .map(|b| match &rules[*b].0[len..] {
+                    [] => "ε".to_string(),
+                    rest => source(rest),
+                })
+                .collect();
+            let helper = names.add(
+                &origin.rule,
+                HelperKind::Fact,
+                alternatives.join(" | ").into(),
+                Default::default(),
+            );
+
+            let mut kept = vec![];
+            let mut moved = vec![];
+            for (b, (r, o)) in productions
+                .remove(&nonterm)
+                .unwrap_or_default()
+                .into_iter()
+                .enumerate()
+            {
+                if b == group[0] {
+                    let mut r = prefix.clone();
+                    r.push(NT::Non(helper.clone()));
+                    kept.push((r, o.clone()));
+                }
+                if group.contains(&b) {
+                    let rest = if r.len() == len {
+                        vec![NT::Epsilon]
+                    } else {
+                        r[len..].to_vec()
+                    };
+                    moved.push((rest, o));
+                } else {
+                    kept.push((r, o));
+                }
+            }
+            productions.insert(nonterm.clone(), kept);
+            productions.insert(helper.clone(), moved);
+            let count = helpers.entry(nonterm.clone()).or_default();
+            order.insert(i + 1 + *count, helper.clone());
+            *count += 1;
+            let mut shape = shapes.get(&nonterm).cloned().unwrap_or_default();
+            shape.inline = true;
+            shape.collapse = false;
+            shapes.insert(helper.clone(), shape);
+            report.push(Factoring {
+                rule: origin.rule,
+                nonterm,
+                prefix,
+                helper,
+                alternatives: group.len(),
+            });
+            // The same nonterminal may have another shared prefix.
+        }
+
+        if report.is_empty() {
+            return (self.clone(), report);
+        }
+        let mut rules = vec![];
+        let mut origins = vec![];
+        for n in order.iter() {
+            for (r, o) in productions.remove(n).unwrap_or_default() {
+                rules.push((n.clone(), r));
+                origins.push(o);
+            }
+        }
+        let mut out = GrammarChomsky::from_rules(
+            self.start_nonterm.clone(),
+            rules,
+            origins,
+            shapes,
+        );
+        out.tails = self.tails.clone();
+        out.helpers = names.helpers;
+        (out, report)
+    }
+}
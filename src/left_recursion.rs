@@ -0,0 +1,388 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::analysis::{get_first_sets, sccs};
+use crate::grammar::{
+    GrammarChomsky, HelperKind, HelperNames, Rstr, RuleOrigin, NT,
+};
+use crate::parser_generator::{TokenReq, AST};
+use crate::symbols::{InternedGrammar, Symbol};
+
+/// The rules of each nonterminal, with what each rule came from.
+pub(crate) type Productions<P> = HashMap<Rstr, Vec<(Vec<NT>, P)>>;
+
+/// Edges from each nonterminal to the nonterminals its rules can start
+/// with. With `nullable` also to those after a nullable prefix.
+fn left_corners(
+    grammar: &InternedGrammar,
+    nullable: Option<&[bool]>,
+) -> Vec<Vec<usize>> {
+    let mut edges = vec![vec![]; grammar.nonterminals.len()];
+    for (l, r) in grammar.rules.iter() {
+        for s in r.iter() {
+            match s {
+                Symbol::Non(n) => {
+                    edges[l.index()].push(n.index());
+                    if !nullable.is_some_and(|x| x[n.index()]) {
+                        break;
+                    }
+                }
+                Symbol::Term(_) => break,
+                Symbol::Epsilon => {}
+            }
+        }
+    }
+    edges
+}
+
+/// The components of `edges` that contain a cycle, their members sorted.
+fn cycles(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut cycles: Vec<Vec<usize>> = sccs(edges)
+        .into_iter()
+        .filter(|c| c.len() > 1 || edges[c[0]].contains(&c[0]))
+        .collect();
+    for c in cycles.iter_mut() {
+        c.sort();
+    }
+    cycles.sort();
+    cycles
+}
+
+fn is_empty(x: &NT) -> bool {
+    match x {
+        NT::Epsilon => true,
+        NT::Term(t) => t.is_empty(),
+        NT::Non(_) => false,
+    }
+}
+
+/// The symbols as in an EBNF rule, for the source of a helper.
+pub(crate) fn source(r: &[NT]) -> String {
+    let symbols: Vec<String> = r
+        .iter()
+        .map(|x| match x {
+            NT::Term(t) => format!("\"{}\"", t),
+            x => x.name().to_string(),
+        })
+        .collect();
+    symbols.join(" ")
+}
+
+/// `a` followed by `b`, `ε` if both are empty.
+fn concat(a: &[NT], b: &[NT]) -> Vec<NT> {
+    let r: Vec<NT> = a
+        .iter()
+        .chain(b)
+        .filter(|x| !is_empty(x))
+        .cloned()
+        .collect();
+    if r.is_empty() {
+        vec![NT::Epsilon]
+    } else {
+        r
+    }
+}
+
+/// Substitutes the rules of the nonterminals in `earlier` at the start of
+/// the rules of `head` until none starts with one of them. `step` gets each
+/// nonterminal substituted and the rules of `head` after it.
+pub(crate) fn substitute_earlier<P: Clone>(
+    productions: &mut Productions<P>,
+    head: &Rstr,
+    earlier: &[Rstr],
+    mut step: impl FnMut(&Rstr, &[(Vec<NT>, P)]),
+) {
+    // With ε rules a substitution can start with an earlier member again,
+    // so substitute until none does.
+    while let Some(using) =
+        productions[head].iter().find_map(|(r, _)| match r.first() {
+            Some(NT::Non(n)) if earlier.contains(n) => Some(n.clone()),
+            _ => None,
+        })
+    {
+        let mut next: Vec<(Vec<NT>, P)> = vec![];
+        for (r, p) in productions.remove(head).unwrap_or_default() {
+            let substituted = if r.first() == Some(&NT::Non(using.clone())) {
+                productions[&using]
+                    .iter()
+                    .map(|(d, _)| (concat(d, &r[1..]), p.clone()))
+                    .collect()
+            } else {
+                vec![(r, p)]
+            };
+            for (r, p) in substituted {
+                if !next.iter().any(|(x, _)| *x == r) {
+                    next.push((r, p));
+                }
+            }
+        }
+        step(&using, &next);
+        productions.insert(head.clone(), next);
+    }
+}
+
+/// Replaces the direct left recursion `A -> A α | β` of `head` by
+/// `A -> β A'` and `A' -> α A' | ε`, or with `epsilon` unset by
+/// `A -> β | β A'` and `A' -> α | α A'`. The tail `A'` is named by `tail`
+/// from the `α`s. Returns the tail, or `None` if `head` has no such
+/// recursion.
+pub(crate) fn remove_direct<P: Clone>(
+    productions: &mut Productions<P>,
+    head: &Rstr,
+    epsilon: bool,
+    tail: impl FnOnce(&[&[NT]]) -> Rstr,
+) -> Option<Rstr> {
+    let head_nt = NT::Non(head.clone());
+    let (mut recursive, rest): (Vec<_>, Vec<_>) = productions
+        .remove(head)
+        .unwrap_or_default()
+        .into_iter()
+        .partition(|(r, _)| r.first() == Some(&head_nt));
+    // `A -> A` adds nothing.
+    recursive.retain(|(r, _)| r.len() > 1);
+    if recursive.is_empty() || rest.is_empty() {
+        recursive.extend(rest);
+        productions.insert(head.clone(), recursive);
+        return None;
+    }
+    let alphas: Vec<&[NT]> = recursive.iter().map(|(r, _)| &r[1..]).collect();
+    let tail = tail(&alphas);
+    let tail_nt = [NT::Non(tail.clone())];
+    let with_tail = |rules: Vec<(Vec<NT>, P)>| -> Vec<(Vec<NT>, P)> {
+        rules
+            .into_iter()
+            .flat_map(|(r, p)| {
+                let r_tail = (concat(&r, &tail_nt), p.clone());
+                if epsilon {
+                    vec![r_tail]
+                } else {
+                    vec![(r, p), r_tail]
+                }
+            })
+            .collect()
+    };
+    let epsilon_rule = (vec![NT::Epsilon], recursive[0].1.clone());
+    let mut tail_rules = with_tail(
+        recursive
+            .into_iter()
+            .map(|(r, p)| (r[1..].to_vec(), p))
+            .collect(),
+    );
+    if epsilon {
+        tail_rules.push(epsilon_rule);
+    }
+    productions.insert(head.clone(), with_tail(rest));
+    productions.insert(tail.clone(), tail_rules);
+    Some(tail)
+}
+
+impl GrammarChomsky {
+    /// The groups of nonterminals that derive a sentential form starting
+    /// with themselves, also past nullable nonterminals. Members are in rule
+    /// order.
+    pub fn left_recursion(&self) -> Vec<Vec<Rstr>> {
+        let grammar = InternedGrammar::new(self);
+        let first = get_first_sets(&grammar);
+        let names: Vec<&Rstr> =
+            grammar.nonterminals.iter().map(|(_, n)| n).collect();
+        cycles(&left_corners(&grammar, Some(&first.nullable)))
+            .into_iter()
+            .map(|c| c.into_iter().map(|n| names[n].clone()).collect())
+            .collect()
+    }
+
+    /// An equivalent grammar without left recursion, by the standard
+    /// algorithm: within each group of mutually left-recursive
+    /// nonterminals, rules starting with an earlier member get that member's
+    /// rules substituted, and then `A -> A α | β` is replaced by
+    /// `A -> β A__tail1` and `A__tail1 -> α A__tail1 | ε`.
+    ///
+    /// Nonterminals generated for parts of EBNF rules come first in a
+    /// group, so recursion through them ends up in the EBNF rule, and they
+    /// are dropped once nothing uses them. Every tail is a
+    /// [`HelperKind::Tail`] helper and is recorded in
+    /// [`GrammarChomsky::tails`], for [`AST::restore_left_recursion`]. Left
+    /// recursion hidden behind a nullable prefix is left alone.
+    pub fn remove_left_recursion(&self) -> GrammarChomsky {
+        let grammar = InternedGrammar::new(self);
+        let groups = cycles(&left_corners(&grammar, None));
+        if groups.is_empty() {
+            return self.clone();
+        }
+        let names: Vec<Rstr> = grammar
+            .nonterminals
+            .iter()
+            .map(|(_, n)| n.clone())
+            .collect();
+        let generated: HashSet<&Rstr> = (0..self.rules.len())
+            .filter(|i| self.is_generated(*i))
+            .map(|i| &self.rules[i].0)
+            .collect();
+        let mut order: Vec<Rstr> = vec![];
+        let mut productions: Productions<RuleOrigin> = HashMap::new();
+        for ((l, r), o) in self.rules.iter().zip(self.origins.iter()) {
+            if !productions.contains_key(l) {
+                order.push(l.clone());
+            }
+            productions
+                .entry(l.clone())
+                .or_default()
+                .push((r.clone(), o.clone()));
+        }
+        let mut helper_names = HelperNames::new(self);
+        let mut tails = self.tails.clone();
+        let mut shapes = self.shapes.clone();
+        let mut helpers = vec![];
+
+        for group in groups {
+            let mut members: Vec<Rstr> =
+                group.iter().map(|n| names[*n].clone()).collect();
+            members.sort_by_key(|n| !generated.contains(n));
+            helpers.extend(
+                members.iter().filter(|n| generated.contains(n)).cloned(),
+            );
+            for (i, head) in members.iter().enumerate() {
+                substitute_earlier(
+                    &mut productions,
+                    head,
+                    &members[..i],
+                    |_, _| {},
+                );
+                let Some(tail) =
+                    remove_direct(&mut productions, head, true, |alphas| {
+                        let alternatives: Vec<String> =
+                            alphas.iter().map(|a| source(a)).collect();
+                        helper_names.add(
+                            &self.rule_of(head),
+                            HelperKind::Tail,
+                            alternatives.join(" | ").into(),
+                            Default::default(),
+                        )
+                    })
+                else {
+                    continue;
+                };
+                let at = order.iter().position(|n| n == head).unwrap_or(0);
+                order.insert(at + 1, tail.clone());
+                if let Some(mut shape) = shapes.get(head).cloned() {
+                    shape.inline = false;
+                    shape.collapse = false;
+                    shapes.insert(tail.clone(), shape);
+                }
+                tails.insert(tail, head.clone());
+            }
+        }
+
+        // Drop the generated members that were substituted everywhere.
+        loop {
+            let unused = helpers.iter().position(|h| {
+                *h != self.start_nonterm
+                    && !productions.iter().any(|(l, p)| {
+                        l != h
+                            && p.iter()
+                                .any(|(r, _)| r.contains(&NT::Non(h.clone())))
+                    })
+            });
+            let Some(unused) = unused else {
+                break;
+            };
+            let h = helpers.swap_remove(unused);
+            productions.remove(&h);
+            order.retain(|n| *n != h);
+        }
+
+        let mut rules = vec![];
+        let mut origins = vec![];
+        for n in order.iter() {
+            for (r, o) in productions.remove(n).unwrap_or_default() {
+                rules.push((n.clone(), r));
+                origins.push(o);
+            }
+        }
+        let mut out = GrammarChomsky::from_rules(
+            self.start_nonterm.clone(),
+            rules,
+            origins,
+            shapes,
+        );
+        out.tails = tails;
+        out.helpers = helper_names.helpers;
+        out.helpers.retain(|h, _| out.nonterminals.contains(h));
+        out
+    }
+}
+
+impl<T: TokenReq> AST<T> {
+    /// Gives the trees of a grammar from
+    /// [`GrammarChomsky::remove_left_recursion`] their left-associative
+    /// shape back: `A(β, A__tail1(α1, A__tail1(α2, A__tail1())))` becomes
+    /// `A(A(A(β), α1), α2)`. The tails of an inlined `A` are inlined too,
+    /// leaving `β α1 α2`.
+    pub fn restore_left_recursion(self, grammar: &GrammarChomsky) -> AST<T> {
+        let AST::Node { name, children } = self else {
+            return self;
+        };
+        let is_tail = |x: Option<&AST<T>>| {
+            matches!(x, Some(AST::Node { name: t, .. })
+                if grammar.tails.get(t) == Some(&name))
+        };
+        let mut children: Vec<AST<T>> = children
+            .into_iter()
+            .flat_map(|c| match c {
+                AST::Node { name: t, children }
+                    if !grammar.tails.contains_key(&name)
+                        && grammar
+                            .tails
+                            .get(&t)
+                            .is_some_and(|h| *h != name) =>
+                {
+                    // The head was inlined, so its tails are too.
+                    let mut flat = vec![];
+                    let mut rest = children;
+                    while let Some(c) = rest.pop() {
+                        match c {
+                            AST::Node { name: u, children } if u == t => {
+                                flat.extend(rest);
+                                rest = children;
+                            }
+                            c => {
+                                rest.push(c);
+                                flat.extend(rest);
+                                break;
+                            }
+                        }
+                    }
+                    flat.into_iter()
+                        .map(|c| c.restore_left_recursion(grammar))
+                        .collect()
+                }
+                c => vec![c.restore_left_recursion(grammar)],
+            })
+            .collect();
+        let mut tail = if is_tail(children.last()) {
+            children.pop()
+        } else {
+            None
+        };
+        let mut tree = AST::Node {
+            name: name.clone(),
+            children,
+        };
+        while let Some(AST::Node { children: rest, .. }) = tail {
+            let mut rest = rest;
+            tail = if is_tail(rest.last()) {
+                rest.pop()
+            } else {
+                None
+            };
+            if rest.is_empty() && tail.is_none() {
+                break;
+            }
+            rest.insert(0, tree);
+            tree = AST::Node {
+                name: name.clone(),
+                children: rest,
+            };
+        }
+        tree
+    }
+}
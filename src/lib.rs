@@ -1,21 +1,63 @@
 use std::error::Error;
 
-use crate::parser_generator::{TokenIteratorType, TokenReq};
+use crate::cst::{CstToken, TriviaConfig};
+use crate::parser_generator::{ParseResult, TokenIteratorType, TokenReq};
 
+pub mod analysis;
+pub mod cnf;
+pub mod coverage;
+pub mod cst;
+pub mod cyk;
+pub mod derivation;
+pub mod explain;
+pub mod export;
+pub mod format;
+pub mod generate;
+pub mod gnf;
 pub mod grammar;
+pub mod left_factoring;
+pub mod left_recursion;
+pub mod lint;
 pub mod parser;
 pub mod parser_generator;
+pub mod parsingtbl;
+pub mod query;
+pub mod serialize;
+pub mod simplify;
+pub mod symbols;
 pub mod tokeniser;
+pub mod trace;
 
 pub fn get_parser<'a, TokenT: TokenReq>(
     grammar_file: &str,
     tokeniser: impl Fn(&'a str) -> TokenIteratorType<'a, TokenT>,
-) -> impl Fn(&'a str) -> Result<parser_generator::AST<TokenT>, Box<dyn Error>> {
+) -> impl Fn(&'a str) -> ParseResult<TokenT> {
     let gram_str =
         std::fs::read_to_string(grammar_file).expect("Failed to read grammar.");
-    let tokens = tokeniser::tokenize(gram_str.as_str());
-    let ast = parser::parse(tokens).expect("Failed to parse grammar");
+    let ast = parser::parse_source(gram_str.as_str())
+        .expect("Failed to parse grammar");
     // println!("{}", &ast.to_chomsky());
     parser_generator::get_parser(ast.to_chomsky(), tokeniser)
         .expect("Failed to generate parser")
 }
+
+pub fn get_cst_parser<'a>(
+    grammar_file: &str,
+    trivia: TriviaConfig,
+) -> impl Fn(&'a str) -> ParseResult<CstToken> {
+    let gram_str =
+        std::fs::read_to_string(grammar_file).expect("Failed to read grammar.");
+    let ast = parser::parse_source(gram_str.as_str())
+        .expect("Failed to parse grammar");
+    parser_generator::get_cst_parser(ast.to_chomsky(), trivia)
+        .expect("Failed to generate parser")
+}
+
+/// Builds a [`Parser`](parser_generator::Parser) from a grammar file.
+pub fn load_parser(
+    grammar_file: &str,
+) -> Result<parser_generator::Parser, Box<dyn Error>> {
+    let gram_str = std::fs::read_to_string(grammar_file)?;
+    let ast = parser::parse_source(gram_str.as_str())?;
+    parser_generator::Parser::new(ast.to_chomsky())
+}
@@ -0,0 +1,359 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::analysis::productive_nonterms;
+use crate::grammar::{capitalize_first_letter, GrammarChomsky, Rstr, NT};
+use crate::symbols::{BitSet, InternedGrammar, Symbol};
+
+/// The checks done by [`GrammarChomsky::lint`]. Each has a code like
+/// `G001` and a name like `undefined-nonterminal`, either of which selects
+/// it in a [`LintConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LintCode {
+    /// A nonterminal is used but has no rules.
+    UndefinedNonterminal,
+    /// A rule can not be reached from the start nonterminal.
+    UnreachableRule,
+    /// A nonterminal derives no string of terminals.
+    UnproductiveNonterminal,
+    /// A nonterminal has the same alternative more than once.
+    DuplicateAlternative,
+    /// A terminal is only used in unreachable rules.
+    UnusedTerminal,
+    /// A nonterminal derives a sentential form starting with itself.
+    LeftRecursion,
+}
+
+impl LintCode {
+    pub const ALL: [LintCode; 6] = [
+        LintCode::UndefinedNonterminal,
+        LintCode::UnreachableRule,
+        LintCode::UnproductiveNonterminal,
+        LintCode::DuplicateAlternative,
+        LintCode::UnusedTerminal,
+        LintCode::LeftRecursion,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            LintCode::UndefinedNonterminal => "G001",
+            LintCode::UnreachableRule => "G002",
+            LintCode::UnproductiveNonterminal => "G003",
+            LintCode::DuplicateAlternative => "G004",
+            LintCode::UnusedTerminal => "G005",
+            LintCode::LeftRecursion => "G006",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LintCode::UndefinedNonterminal => "undefined-nonterminal",
+            LintCode::UnreachableRule => "unreachable-rule",
+            LintCode::UnproductiveNonterminal => "unproductive-nonterminal",
+            LintCode::DuplicateAlternative => "duplicate-alternative",
+            LintCode::UnusedTerminal => "unused-terminal",
+            LintCode::LeftRecursion => "left-recursion",
+        }
+    }
+}
+
+impl FromStr for LintCode {
+    type Err = String;
+
+    /// Accepts both the code and the name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LintCode::ALL
+            .into_iter()
+            .find(|c| c.code().eq_ignore_ascii_case(s) || c.name() == s)
+            .ok_or_else(|| format!("Unknown lint {}", s))
+    }
+}
+
+impl Display for LintCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warn,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warn => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// The severity of every [`LintCode`]. By default undefined nonterminals
+/// are errors, as no parser can be built with them, and the rest are
+/// warnings.
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    levels: BTreeMap<LintCode, Severity>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            levels: BTreeMap::new(),
+        }
+        .with(LintCode::UndefinedNonterminal, Severity::Error)
+    }
+}
+
+impl LintConfig {
+    pub fn with(mut self, code: LintCode, severity: Severity) -> Self {
+        self.levels.insert(code, severity);
+        self
+    }
+
+    pub fn warn(self, code: LintCode) -> Self {
+        self.with(code, Severity::Warn)
+    }
+
+    pub fn deny(self, code: LintCode) -> Self {
+        self.with(code, Severity::Error)
+    }
+
+    pub fn severity(&self, code: LintCode) -> Severity {
+        self.levels.get(&code).copied().unwrap_or(Severity::Warn)
+    }
+}
+
+/// One problem found in a grammar. `rule` is the index of the chomsky
+/// production it was found in, whose [`RuleOrigin`] tells where in the
+/// EBNF grammar that is.
+///
+/// [`RuleOrigin`]: crate::grammar::RuleOrigin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub code: LintCode,
+    pub severity: Severity,
+    pub message: String,
+    pub rule: Option<usize>,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+/// Where production `rule` is in the EBNF grammar, as `` `x = a b` `` or,
+/// for a production of a generated nonterminal, `` `a b` in `x` ``.
+fn place(grammar: &GrammarChomsky, rule: usize) -> String {
+    let Some(origin) = grammar.origins.get(rule) else {
+        return format!("`{}`", grammar.rules[rule].0);
+    };
+    if grammar.rules[rule].0 == capitalize_first_letter(&origin.rule) {
+        format!("`{} = {}`", origin.rule, origin.source)
+    } else {
+        format!("`{}` in `{}`", origin.source, origin.rule)
+    }
+}
+
+impl GrammarChomsky {
+    /// Checks the grammar for the problems in [`LintCode`], with severities
+    /// from `config`. The diagnostics are sorted by code.
+    pub fn lint(&self, config: &LintConfig) -> Vec<Diagnostic> {
+        self.lint_where(config, |_| true)
+    }
+
+    /// Like [`GrammarChomsky::lint`], but only does the checks that
+    /// `config` makes errors.
+    pub(crate) fn lint_errors(&self, config: &LintConfig) -> Vec<Diagnostic> {
+        self.lint_where(config, |code| config.severity(code) == Severity::Error)
+    }
+
+    fn lint_where(
+        &self,
+        config: &LintConfig,
+        check: impl Fn(LintCode) -> bool,
+    ) -> Vec<Diagnostic> {
+        let grammar = InternedGrammar::new(self);
+        let mut out = vec![];
+        let mut report = |code: LintCode, message: String, rule| {
+            out.push(Diagnostic {
+                code,
+                severity: config.severity(code),
+                message,
+                rule,
+            })
+        };
+
+        if check(LintCode::UndefinedNonterminal) {
+            let mut defined = BitSet::new(grammar.nonterminals.len());
+            for (l, _) in grammar.rules.iter() {
+                defined.insert(*l);
+            }
+            let mut seen = HashSet::new();
+            for (i, (_, r)) in grammar.rules.iter().enumerate() {
+                for s in r.iter() {
+                    if let Symbol::Non(n) = s {
+                        if !defined.contains(*n)
+                            && seen.insert((*n, place(self, i)))
+                        {
+                            report(
+                                LintCode::UndefinedNonterminal,
+                                format!(
+                                    "`{}` has no rules, used in {}",
+                                    grammar.nonterminals.name(*n),
+                                    place(self, i)
+                                ),
+                                Some(i),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        let reachable = reachable(&grammar, &self.start_nonterm);
+        if check(LintCode::UnreachableRule) {
+            let mut seen = HashSet::new();
+            for (i, (l, _)) in grammar.rules.iter().enumerate() {
+                if reachable.contains(*l) || self.is_generated(i) {
+                    continue;
+                }
+                let name = grammar.nonterminals.name(*l);
+                if seen.insert(*l) {
+                    report(
+                        LintCode::UnreachableRule,
+                        format!(
+                            "`{}` can not be reached from `{}`",
+                            name, self.start_nonterm
+                        ),
+                        Some(i),
+                    );
+                }
+            }
+        }
+
+        if check(LintCode::UnproductiveNonterminal) {
+            let productive = productive_nonterms(&grammar);
+            let mut seen = HashSet::new();
+            for (i, (l, _)) in grammar.rules.iter().enumerate() {
+                if productive.contains(*l) || !seen.insert(*l) {
+                    continue;
+                }
+                let what = if self.is_generated(i) {
+                    place(self, i)
+                } else {
+                    format!("`{}`", grammar.nonterminals.name(*l))
+                };
+                report(
+                    LintCode::UnproductiveNonterminal,
+                    format!("{} derives no string of terminals", what),
+                    Some(i),
+                );
+            }
+        }
+
+        if check(LintCode::DuplicateAlternative) {
+            let mut seen = BTreeSet::new();
+            for (i, (l, r)) in self.rules.iter().enumerate() {
+                if seen.insert((l, r)) {
+                    continue;
+                }
+                let alternative: Vec<&str> = r.iter().map(NT::name).collect();
+                report(
+                    LintCode::DuplicateAlternative,
+                    format!(
+                        "the alternative `{}` is repeated in {}",
+                        alternative.join(" "),
+                        place(self, i)
+                    ),
+                    Some(i),
+                );
+            }
+        }
+
+        if check(LintCode::UnusedTerminal) {
+            let mut used = BitSet::new(grammar.terminals.len());
+            for (l, r) in grammar.rules.iter() {
+                if !reachable.contains(*l) {
+                    continue;
+                }
+                for s in r.iter() {
+                    if let Symbol::Term(t) = s {
+                        used.insert(*t);
+                    }
+                }
+            }
+            let mut seen = HashSet::new();
+            for (i, (_, r)) in grammar.rules.iter().enumerate() {
+                for s in r.iter() {
+                    if let Symbol::Term(t) = s {
+                        if !used.contains(*t) && seen.insert(*t) {
+                            report(
+                                LintCode::UnusedTerminal,
+                                format!(
+                                    "\"{}\" is only used in unreachable rules, in {}",
+                                    grammar.terminals.name(*t),
+                                    place(self, i)
+                                ),
+                                Some(i),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        if check(LintCode::LeftRecursion) {
+            for group in self.left_recursion() {
+                let rules: Vec<usize> = group
+                    .iter()
+                    .filter_map(|n| self.rules.iter().position(|(l, _)| l == n))
+                    .collect();
+                let named: Vec<String> = rules
+                    .iter()
+                    .filter(|i| !self.is_generated(**i))
+                    .map(|i| format!("`{}`", self.rules[*i].0))
+                    .collect();
+                let message = match named.len() {
+                    0 => format!("{} is left-recursive", place(self, rules[0])),
+                    1 => format!("{} is left-recursive", named[0]),
+                    _ => format!("{} are left-recursive", named.join(", ")),
+                };
+                report(LintCode::LeftRecursion, message, Some(rules[0]));
+            }
+        }
+
+        out.sort_by_key(|d| d.code);
+        out
+    }
+}
+
+fn reachable(grammar: &InternedGrammar, start: &Rstr) -> BitSet {
+    let mut uses = vec![vec![]; grammar.nonterminals.len()];
+    for (l, r) in grammar.rules.iter() {
+        for s in r.iter() {
+            if let Symbol::Non(n) = s {
+                uses[l.index()].push(*n);
+            }
+        }
+    }
+    let mut reachable = BitSet::new(grammar.nonterminals.len());
+    let mut todo: Vec<_> =
+        grammar.nonterminals.get(start).into_iter().collect();
+    while let Some(n) = todo.pop() {
+        if reachable.insert(n) {
+            todo.extend(uses[n.index()].iter().copied());
+        }
+    }
+    reachable
+}
@@ -7,52 +7,462 @@ use std::{
     io::{Read, Write},
 };
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Debug,
+    Json,
+    Sexpr,
+    Dot,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TraceFormat {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AnalysisFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExplainFormat {
+    Text,
+    Latex,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TableFormat {
+    Csv,
+    Markdown,
+    Html,
+    Latex,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Format grammar files in place.
+    Fmt {
+        files: Vec<String>,
+        /// Only report the files that are not formatted and fail if there
+        /// are any.
+        #[arg(long)]
+        check: bool,
+        /// The width to wrap long rules at.
+        #[arg(long, default_value_t = 80)]
+        width: usize,
+    },
+    /// Print random sentences of a grammar, one per line.
+    Generate {
+        grammar: String,
+        /// How many sentences to print.
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+        /// The same seed gives the same sentences.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// The depth of the derivation tree after which the productions
+        /// that finish soonest are picked.
+        #[arg(long, default_value_t = 20)]
+        max_depth: usize,
+        /// The weight of a production, by its number in `--analysis`, as
+        /// `RULE=WEIGHT`.
+        #[arg(long, value_name = "RULE=WEIGHT", value_parser = parse_weight)]
+        weight: Vec<(usize, f64)>,
+        /// The nonterminal to generate, the start one by default.
+        #[arg(long)]
+        entry: Option<String>,
+        /// What to put between the tokens.
+        #[arg(long, default_value = " ")]
+        separator: String,
+    },
+    /// Print a small set of sentences that together use every production,
+    /// one per line.
+    Cover {
+        grammar: String,
+        /// Also use every cell of the LL(1) table.
+        #[arg(long)]
+        cells: bool,
+        /// Write each sentence to its own file in this directory instead.
+        #[arg(long)]
+        out: Option<String>,
+        /// What to put between the tokens.
+        #[arg(long, default_value = " ")]
+        separator: String,
+    },
+}
+
+fn parse_weight(s: &str) -> Result<(usize, f64), String> {
+    let (rule, weight) = s
+        .split_once('=')
+        .ok_or("expected RULE=WEIGHT".to_string())?;
+    let rule = rule.parse().map_err(|e| format!("{}", e))?;
+    let weight = weight.parse().map_err(|e| format!("{}", e))?;
+    Ok((rule, weight))
+}
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
-    grammar: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(required = true)]
+    grammar: Option<String>,
+    /// How to print the parsed tree.
+    #[arg(long, value_enum, default_value = "debug")]
+    format: Format,
+    /// Print the leftmost derivation of the input.
+    #[arg(long)]
+    derivation: bool,
+    /// Print every step of the LL(1) driver.
+    #[arg(long, value_enum)]
+    trace: Option<TraceFormat>,
+    /// Print the nullable nonterminals, FIRST and FOLLOW sets and the LL(1)
+    /// table.
+    #[arg(long, value_enum)]
+    analysis: Option<AnalysisFormat>,
+    /// Explain every iteration of computing the FIRST and FOLLOW sets.
+    #[arg(long, value_enum)]
+    explain: Option<ExplainFormat>,
+    /// Print the LL(1) parse table, conflicts highlighted.
+    #[arg(long, value_enum)]
+    table: Option<TableFormat>,
+    /// Only print the grammar in the parsingtbl input format.
+    #[arg(long)]
+    parsingtbl: bool,
+    /// Only print the conversion of the grammar to Greibach normal form,
+    /// step by step.
+    #[arg(long)]
+    gnf: bool,
+    /// Only print the grammar converted to BNF and back, with tail
+    /// recursion as repetitions and single-use helpers inlined.
+    #[arg(long)]
+    simplify: bool,
+    /// Remove left recursion from the grammar, keeping the trees
+    /// left-associative.
+    #[arg(long)]
+    remove_left_recursion: bool,
+    /// Pull common prefixes of alternatives out into helper rules.
+    #[arg(long)]
+    left_factor: bool,
+    /// Parse with a CYK parser over the grammar in Chomsky normal form,
+    /// which also works for ambiguous grammars.
+    #[arg(long)]
+    cyk: bool,
+    /// Make a grammar lint, given by code or name, a warning.
+    #[arg(long, value_name = "LINT")]
+    warn: Vec<LintCode>,
+    /// Make a grammar lint, given by code or name, an error.
+    #[arg(long, value_name = "LINT")]
+    deny: Vec<LintCode>,
+}
+
+use chomchom::analysis::GrammarAnalysis;
+use chomchom::coverage::Coverage;
+use chomchom::cyk::Cyk;
+use chomchom::generate::Generator;
+use chomchom::grammar::{capitalize_first_letter, NT};
+use chomchom::lint::{LintCode, LintConfig};
+use chomchom::parser_generator::{get_tokenizer, Token, AST};
+
+use chomchom::{format, parser, parser_generator, tokeniser};
+
+fn fmt(
+    files: &[String],
+    check: bool,
+    width: usize,
+) -> Result<(), Box<dyn Error>> {
+    let mut unformatted = false;
+    let mut failed = false;
+    for file in files.iter() {
+        let formatted = std::fs::read_to_string(file)
+            .map_err(|e| e.to_string())
+            .and_then(|source| {
+                Ok((format::format_grammar(&source, width)?, source))
+            });
+        let (formatted, source) = match formatted {
+            Ok(x) => x,
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                failed = true;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            eprintln!("{} is not formatted", file);
+            unformatted = true;
+        } else {
+            std::fs::write(file, formatted)?;
+        }
+    }
+    if unformatted || failed {
+        std::process::exit(1);
+    }
+    Ok(())
 }
 
-use chomchom::{grammar::NT, parser_generator::get_tokenizer};
+fn generate(
+    grammar: &str,
+    count: usize,
+    seed: u64,
+    max_depth: usize,
+    weights: &[(usize, f64)],
+    entry: Option<&str>,
+    separator: &str,
+) -> Result<(), Box<dyn Error>> {
+    let source = std::fs::read_to_string(grammar)?;
+    let grammar = parser::parse_source(&source)?.to_chomsky();
+    let mut generator = Generator::new(&grammar)
+        .with_seed(seed)
+        .with_max_depth(max_depth);
+    for (rule, weight) in weights.iter() {
+        if *rule >= grammar.rules.len() {
+            return Err(format!(
+                "There is no production {}, the last is {}",
+                rule,
+                grammar.rules.len() - 1
+            )
+            .into());
+        }
+        generator = generator.with_weight(*rule, *weight);
+    }
+    let entry = entry.unwrap_or(&grammar.start_nonterm);
+    let name = capitalize_first_letter(entry);
+    if !grammar.rules.iter().any(|(l, _)| *l == name) {
+        return Err(format!("No rules for entry {}", name).into());
+    }
+    for _ in 0..count {
+        let sentence = generator
+            .sentence_from(entry)
+            .ok_or(format!("{} derives no sentence", entry))?;
+        println!("{}", sentence.join(separator));
+    }
+    Ok(())
+}
 
-use chomchom::{parser, parser_generator, tokeniser};
+fn cover(
+    grammar: &str,
+    cells: bool,
+    out: Option<&str>,
+    separator: &str,
+) -> Result<(), Box<dyn Error>> {
+    let source = std::fs::read_to_string(grammar)?;
+    let grammar = parser::parse_source(&source)?.to_chomsky();
+    let analysis = GrammarAnalysis::new(&grammar)?;
+    let mut coverage = Coverage::new(&grammar).with_table(analysis.table());
+    if cells {
+        coverage = coverage.with_cells();
+    }
+    let suite = coverage.suite();
+    for goal in suite.missed.iter() {
+        eprintln!("{} is not covered", goal);
+    }
+    if let Some(out) = out {
+        std::fs::create_dir_all(out)?;
+    }
+    for (i, case) in suite.cases.iter().enumerate() {
+        let sentence = case.sentence.join(separator);
+        match out {
+            Some(out) => std::fs::write(
+                std::path::Path::new(out).join(format!("{:03}.txt", i + 1)),
+                sentence,
+            )?,
+            None => println!("{}", sentence),
+        }
+    }
+    Ok(())
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    let gram_str =
-        std::fs::read_to_string(args.grammar).expect("Failed to read grammar.");
+    match &args.command {
+        Some(Command::Fmt {
+            files,
+            check,
+            width,
+        }) => return fmt(files, *check, *width),
+        Some(Command::Generate {
+            grammar,
+            count,
+            seed,
+            max_depth,
+            weight,
+            entry,
+            separator,
+        }) => {
+            return generate(
+                grammar,
+                *count,
+                *seed,
+                *max_depth,
+                weight,
+                entry.as_deref(),
+                separator,
+            )
+        }
+        Some(Command::Cover {
+            grammar,
+            cells,
+            out,
+            separator,
+        }) => return cover(grammar, *cells, out.as_deref(), separator),
+        None => {}
+    }
+    let gram_str = std::fs::read_to_string(
+        args.grammar.as_ref().expect("The grammar is required"),
+    )
+    .expect("Failed to read grammar.");
     // let mut gram_str = String::new();
     // std::io::stdin()
     //     .read_to_string(&mut gram_str)
     //     .expect("Failed to read");
-    let tokens = tokeniser::tokenize(gram_str.as_str());
+    let tokens = tokeniser::try_tokenize(gram_str.as_str())?.0;
+    if args.parsingtbl {
+        print!(
+            "{}",
+            parser::parse_with_spans(tokens)?
+                .to_chomsky()
+                .to_parsingtbl()
+        );
+        return Ok(());
+    }
+    if args.simplify {
+        let simplified = parser::parse_with_spans(tokens)?
+            .to_chomsky()
+            .to_ebnf_grammar();
+        print!("{}", simplified.to_ebnf());
+        return Ok(());
+    }
+    if args.gnf {
+        let (gnf, steps) =
+            parser::parse_with_spans(tokens)?.to_chomsky().to_gnf();
+        for step in steps.iter() {
+            println!("{}", step);
+        }
+        print!("Greibach normal form:\n{}", gnf);
+        return Ok(());
+    }
     println!("Tokenization complete!");
     std::io::stdout().flush()?;
-    let ast = parser::parse(tokens)?;
-    println!("{}", &ast.to_ebnf());
+    let ast = parser::parse_with_spans(tokens)?;
+    print!("{}", &ast.to_ebnf());
     println!("{}", ast.to_chomsky());
     // dbg!(parser_generator::follow(
     //     &NT::Non("F".into()),
     //     &ast.to_chomsky()
     // )?);
-    let grammar = ast.to_chomsky();
+    let mut grammar = ast.to_chomsky();
+    if args.remove_left_recursion {
+        grammar = grammar.remove_left_recursion();
+        println!("{}", grammar);
+    }
+    if args.left_factor {
+        let (factored, report) = grammar.left_factor();
+        for factoring in report.iter() {
+            eprintln!("{}", factoring);
+        }
+        grammar = factored;
+        println!("{}", grammar);
+    }
     let mut sentense = String::new();
     std::io::stdin()
         .read_to_string(&mut sentense)
         .expect("Failed to read");
-    let parser =
-        parser_generator::get_parser(grammar.clone(), get_tokenizer(&grammar))?;
-    let parsed = match parser(&sentense) {
+    if args.cyk {
+        let cnf = grammar.to_cnf();
+        println!("{}", cnf);
+        let tokens = get_tokenizer(&grammar)(&sentense).collect();
+        match Cyk::new(cnf).parse(tokens) {
+            Err(err) => println!("Error: {}", err),
+            Ok(parsed) => print_tree(args.format, parsed),
+        }
+        return Ok(());
+    }
+    let mut lints = LintConfig::default();
+    for code in args.warn.iter() {
+        lints = lints.warn(*code);
+    }
+    for code in args.deny.iter() {
+        lints = lints.deny(*code);
+    }
+    for diagnostic in grammar.lint(&lints).iter() {
+        eprintln!("{}", diagnostic);
+    }
+    let parser = parser_generator::Parser::with_lints(
+        grammar.clone(),
+        &[&grammar.start_nonterm],
+        &lints,
+    )?;
+    match args.analysis {
+        Some(AnalysisFormat::Text) => print!("{}", parser.analysis()),
+        Some(AnalysisFormat::Json) => {
+            println!("{}", parser.analysis().to_json())
+        }
+        None => {}
+    }
+    let table = parser.table();
+    match args.table {
+        Some(TableFormat::Csv) => print!("{}", table.to_csv(parser.analysis())),
+        Some(TableFormat::Markdown) => {
+            print!("{}", table.to_markdown(parser.analysis()))
+        }
+        Some(TableFormat::Html) => {
+            print!("{}", table.to_html(parser.analysis()))
+        }
+        Some(TableFormat::Latex) => {
+            print!("{}", table.to_latex(parser.analysis()))
+        }
+        None => {}
+    }
+    match args.explain {
+        Some(ExplainFormat::Text) => print!("{}", parser.analysis().explain()),
+        Some(ExplainFormat::Latex) => {
+            print!("{}", parser.analysis().explain().to_latex())
+        }
+        None => {}
+    }
+    if let Some(format) = args.trace {
+        let (_, trace) = parser.trace(&sentense);
+        match format {
+            TraceFormat::Table => print!("{}", trace.table(parser.analysis())),
+            TraceFormat::Json => {
+                println!("{}", trace.to_json(parser.analysis()))
+            }
+        }
+    }
+    let (parsed, derivation) = match parser.derive(&sentense) {
         Err(err) => {
             println!("Error: {}", err);
             return Ok(());
         }
         Ok(p) => p,
     };
-    dbg!(parsed);
+    let parsed = parsed.restore_left_recursion(&grammar);
+    if args.derivation {
+        print!("{}", derivation.display(&grammar));
+    }
+    print_tree(args.format, parsed);
 
     // parsed
 
     Ok(())
 }
+
+fn print_tree(format: Format, parsed: AST<Token>) {
+    match format {
+        Format::Debug => {
+            dbg!(parsed);
+        }
+        Format::Json => println!("{}", parsed.to_json()),
+        Format::Sexpr => println!("{}", parsed.to_sexpr()),
+        Format::Dot => print!("{}", parsed.to_dot()),
+    }
+}
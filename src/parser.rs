@@ -1,27 +1,132 @@
-use crate::grammar::{GrammarEBNF, Rstr, EBNF};
-use crate::tokeniser::Token;
+use crate::grammar::{GrammarEBNF, Rstr, RuleShape, SpanTree, EBNF};
+use crate::tokeniser::{try_tokenize, Span, Token};
 use std::collections::{BTreeMap, VecDeque};
 
+/// The tokens still to parse, with their spans.
+struct Tokens {
+    tokens: VecDeque<(Token, Span)>,
+    /// The span of the last token taken.
+    last: Span,
+}
+
+impl Tokens {
+    fn front(&self) -> Option<&Token> {
+        self.tokens.front().map(|(t, _)| t)
+    }
+
+    fn pop_front(&mut self) -> Option<Token> {
+        let (token, span) = self.tokens.pop_front()?;
+        self.last = span;
+        Some(token)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// The span of the next token.
+    fn next_span(&self) -> Span {
+        self.tokens.front().map(|(_, s)| *s).unwrap_or(self.last)
+    }
+}
+
+/// Parses a grammar without keeping where its parts are in the source; see
+/// [`parse_source`].
 pub fn parse(tokens: Vec<Token>) -> Result<GrammarEBNF, String> {
-    let mut deq: VecDeque<Token> = tokens.into();
+    parse_with_spans(tokens.into_iter().map(|t| (t, Span::default())).collect())
+}
+
+/// Tokenizes and parses a grammar, with the spans of its parts in
+/// [`GrammarEBNF::spans`].
+pub fn parse_source(source: &str) -> Result<GrammarEBNF, String> {
+    parse_with_spans(try_tokenize(source)?.0)
+}
+
+/// A rule as written in the grammar source.
+#[derive(Debug, Clone)]
+pub struct RuleDefinition {
+    pub annotations: Vec<(Rstr, Vec<Rstr>)>,
+    pub nonterm: Rstr,
+    pub rule: EBNF,
+    pub spans: SpanTree,
+    /// From the first annotation, or the name, to the semicolon.
+    pub span: Span,
+}
+
+/// The rules of a grammar in the order they are written.
+pub fn parse_definitions(
+    tokens: Vec<(Token, Span)>,
+) -> Result<Vec<RuleDefinition>, String> {
+    let mut deq = Tokens {
+        tokens: tokens.into(),
+        last: Span::default(),
+    };
+    let mut definitions = vec![];
+    while !deq.is_empty() {
+        let start = deq.next_span();
+        let annotations = parse_annotations(&mut deq);
+        let (nonterm, rule, spans) = parse_rule(&mut deq)?;
+        definitions.push(RuleDefinition {
+            annotations,
+            nonterm,
+            rule,
+            spans,
+            span: start.to(deq.last),
+        });
+    }
+    Ok(definitions)
+}
+
+pub fn parse_with_spans(
+    tokens: Vec<(Token, Span)>,
+) -> Result<GrammarEBNF, String> {
     let mut rules: BTreeMap<Rstr, Vec<EBNF>> = BTreeMap::new();
+    let mut spans: BTreeMap<Rstr, Vec<SpanTree>> = BTreeMap::new();
+    let mut shapes: BTreeMap<Rstr, RuleShape> = BTreeMap::new();
     let mut start_nonterm: Option<Rstr> = None;
-    while !deq.is_empty() {
-        let (nonterm, rule) = parse_rule(&mut deq)?;
+    for definition in parse_definitions(tokens)? {
+        let nonterm = definition.nonterm;
+        if !definition.annotations.is_empty() {
+            let shape = shapes.entry(nonterm.clone()).or_default();
+            for (annotation, args) in definition.annotations {
+                shape.apply(&annotation, args)?;
+            }
+        }
         if start_nonterm.is_none() {
             start_nonterm = Some(nonterm.clone());
         }
-        rules.entry(nonterm).or_default().push(rule);
+        rules
+            .entry(nonterm.clone())
+            .or_default()
+            .push(definition.rule);
+        spans.entry(nonterm).or_default().push(definition.spans);
     }
 
     Ok(GrammarEBNF {
         start_nonterm: start_nonterm
             .unwrap_or("No starting nonterminal.".into()),
         rules,
+        shapes,
+        spans,
     })
 }
 
-fn parse_rule(tokens: &mut VecDeque<Token>) -> Result<(Rstr, EBNF), String> {
+fn parse_annotations(tokens: &mut Tokens) -> Vec<(Rstr, Vec<Rstr>)> {
+    let mut annotations = vec![];
+    while let Some(Token::Annotation(name)) = tokens.front() {
+        let name = name.clone();
+        tokens.pop_front();
+        let mut args = vec![];
+        while let Some(Token::Terminal(arg)) = tokens.front() {
+            args.push(arg.clone());
+            tokens.pop_front();
+        }
+        annotations.push((name, args));
+    }
+    annotations
+}
+
+fn parse_rule(tokens: &mut Tokens) -> Result<(Rstr, EBNF, SpanTree), String> {
     let Token::Nonterminal(nonterm) = tokens
         .pop_front()
         .ok_or("Nonterminal on lhs of rule expected.")?
@@ -31,118 +136,166 @@ fn parse_rule(tokens: &mut VecDeque<Token>) -> Result<(Rstr, EBNF), String> {
     if !matches!(tokens.pop_front(), Some(Token::Equal)) {
         return Err("Equal expected.".into());
     }
-    let rule = parse_rhs(tokens)?;
+    let (rule, span) = parse_rhs(tokens)?;
     if parse_semicolon(tokens).is_err() {
         Err(format!("No semicolon after rule: {:?}", rule))
     } else {
-        Ok((nonterm, rule))
+        Ok((nonterm, rule, span))
     }
 }
 
-fn parse_rhs(tokens: &mut VecDeque<Token>) -> Result<EBNF, String> {
+fn parse_rhs(tokens: &mut Tokens) -> Result<(EBNF, SpanTree), String> {
     parse_alternation(tokens)
 }
 
-fn parse_term(tokens: &mut VecDeque<Token>) -> Result<EBNF, String> {
+/// A leaf of the span tree, for the token just taken.
+fn leaf(tokens: &Tokens) -> SpanTree {
+    SpanTree {
+        span: tokens.last,
+        children: vec![],
+    }
+}
+
+fn parse_term(tokens: &mut Tokens) -> Result<(EBNF, SpanTree), String> {
+    let start = tokens.next_span();
     if let Some(token) = tokens.front() {
         match token {
             Token::LParen => {
                 tokens.pop_front();
-                let r = parse_rhs(tokens)?;
+                let (r, mut span) = parse_rhs(tokens)?;
                 if !matches!(tokens.pop_front(), Some(Token::RParen)) {
                     Err("No matching ')' found.".into())
                 } else {
-                    Ok(r)
+                    // The parentheses belong to what they group.
+                    span.span = start.to(tokens.last);
+                    Ok((r, span))
                 }
             }
             Token::LBracket => {
                 tokens.pop_front();
-                let r = parse_rhs(tokens)?;
+                let (r, span) = parse_rhs(tokens)?;
                 if !matches!(tokens.pop_front(), Some(Token::RBracket)) {
                     Err("No matching ')' found.".into())
                 } else {
-                    Ok(EBNF::Optional(Box::new(r)))
+                    Ok((
+                        EBNF::Optional(Box::new(r)),
+                        SpanTree {
+                            span: start.to(tokens.last),
+                            children: vec![span],
+                        },
+                    ))
                 }
             }
             Token::LBrace => {
                 tokens.pop_front();
-                let r = parse_rhs(tokens)?;
+                let (r, span) = parse_rhs(tokens)?;
                 if !matches!(tokens.pop_front(), Some(Token::RBrace)) {
                     Err("No matching '}' found.".into())
                 } else {
-                    Ok(EBNF::Iteration(Box::new(r)))
+                    Ok((
+                        EBNF::Iteration(Box::new(r)),
+                        SpanTree {
+                            span: start.to(tokens.last),
+                            children: vec![span],
+                        },
+                    ))
                 }
             }
             Token::Nonterminal(_) => {
                 let Some(Token::Nonterminal(pc)) = tokens.pop_front() else {
                     unreachable!()
                 };
-                Ok(EBNF::Non(pc))
+                Ok((EBNF::Non(pc), leaf(tokens)))
             }
             Token::Terminal(_) => {
                 let Some(Token::Terminal(pc)) = tokens.pop_front() else {
                     unreachable!()
                 };
-                Ok(EBNF::Term(pc))
+                Ok((EBNF::Term(pc), leaf(tokens)))
             }
             Token::Epsilon => {
                 tokens.pop_front();
-                Ok(EBNF::Epsilon)
+                Ok((EBNF::Epsilon, leaf(tokens)))
             }
             Token::RBrace => Err("lol".into()),
-            idk => Err(format!("Unexpected '{:?}', {:?}", idk, tokens)),
+            idk => Err(format!("Unexpected '{:?}', {:?}", idk, tokens.tokens)),
         }
     } else {
         Err("wtf2".into())
     }
 }
 
-fn parse_factor(tokens: &mut VecDeque<Token>) -> Result<EBNF, String> {
-    let term = parse_term(tokens)?;
+fn parse_factor(tokens: &mut Tokens) -> Result<(EBNF, SpanTree), String> {
+    let (term, span) = parse_term(tokens)?;
 
     if let Some(token) = tokens.front() {
         match token {
             Token::Asterix => {
                 tokens.pop_front();
-                Ok(EBNF::Iteration(Box::new(term)))
+                Ok((
+                    EBNF::Iteration(Box::new(term)),
+                    SpanTree {
+                        span: span.span.to(tokens.last),
+                        children: vec![span],
+                    },
+                ))
             }
             Token::Plus => {
                 tokens.pop_front();
-                Ok(EBNF::OneOrMore(Box::new(term)))
+                Ok((
+                    EBNF::OneOrMore(Box::new(term)),
+                    SpanTree {
+                        span: span.span.to(tokens.last),
+                        children: vec![span],
+                    },
+                ))
             }
-            _ => Ok(term),
+            _ => Ok((term, span)),
         }
     } else {
         Err("Expected one of Asterix or Plus, found EOF".into())
     }
 }
 
-fn flaten_or(x: EBNF) -> Vec<EBNF> {
+fn flaten_or(x: (EBNF, SpanTree)) -> Vec<(EBNF, SpanTree)> {
     match x {
-        EBNF::Or(or) => or.into_iter().flat_map(flaten_or).collect(),
+        (EBNF::Or(or), span) if or.len() == span.children.len() => or
+            .into_iter()
+            .zip(span.children)
+            .flat_map(flaten_or)
+            .collect(),
         els => vec![els],
     }
 }
 
-fn parse_alternation(tokens: &mut VecDeque<Token>) -> Result<EBNF, String> {
+fn parse_alternation(tokens: &mut Tokens) -> Result<(EBNF, SpanTree), String> {
     let factor = parse_concatanation(tokens)?;
     if let Some(Token::Or) = tokens.front() {
         tokens.pop_front();
         let r = vec![factor, parse_alternation(tokens)?];
-        Ok(EBNF::Or(r.into_iter().flat_map(flaten_or).collect()))
+        let (or, children): (Vec<EBNF>, Vec<SpanTree>) =
+            r.into_iter().flat_map(flaten_or).unzip();
+        let span = children[0].span.to(children[children.len() - 1].span);
+        Ok((EBNF::Or(or), SpanTree { span, children }))
     } else {
         Ok(factor)
     }
 }
 
-fn flaten_cat(x: EBNF) -> Vec<EBNF> {
+fn flaten_cat(x: (EBNF, SpanTree)) -> Vec<(EBNF, SpanTree)> {
     match x {
-        EBNF::Concat(cat) => cat.into_iter().flat_map(flaten_cat).collect(),
+        (EBNF::Concat(cat), span) if cat.len() == span.children.len() => cat
+            .into_iter()
+            .zip(span.children)
+            .flat_map(flaten_cat)
+            .collect(),
         els => vec![els],
     }
 }
 
-fn parse_concatanation(tokens: &mut VecDeque<Token>) -> Result<EBNF, String> {
+fn parse_concatanation(
+    tokens: &mut Tokens,
+) -> Result<(EBNF, SpanTree), String> {
     let factor = parse_factor(tokens)?;
     if let Some(t) = tokens.front() {
         if matches!(t, Token::Semicolon) {
@@ -152,9 +305,11 @@ fn parse_concatanation(tokens: &mut VecDeque<Token>) -> Result<EBNF, String> {
             match c {
                 Ok(c) => {
                     let cs = vec![factor, c];
-                    Ok(EBNF::Concat(
-                        cs.into_iter().flat_map(flaten_cat).collect(),
-                    ))
+                    let (cat, children): (Vec<EBNF>, Vec<SpanTree>) =
+                        cs.into_iter().flat_map(flaten_cat).unzip();
+                    let span =
+                        children[0].span.to(children[children.len() - 1].span);
+                    Ok((EBNF::Concat(cat), SpanTree { span, children }))
                 }
                 Err(_) => Ok(factor),
             }
@@ -164,7 +319,7 @@ fn parse_concatanation(tokens: &mut VecDeque<Token>) -> Result<EBNF, String> {
     }
 }
 
-fn parse_semicolon(tokens: &mut VecDeque<Token>) -> Result<(), String> {
+fn parse_semicolon(tokens: &mut Tokens) -> Result<(), String> {
     if let Some(Token::Semicolon) = tokens.pop_front() {
         Ok(())
     } else {
@@ -1,180 +1,19 @@
 use std::{
-    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    collections::BTreeSet,
     error::Error,
     fmt::{Debug, Display},
-    iter,
-    rc::Rc,
 };
 
-use crate::grammar::{self, GrammarChomsky, GrammarEBNF, Rstr, NT};
-
-fn first(
-    input: &NT,
-    first_table: &BTreeMap<Rstr, BTreeSet<Rstr>>,
-) -> Result<BTreeSet<Rstr>, Box<dyn Error>> {
-    Ok(match input {
-        NT::Term(f) => BTreeSet::from([f.clone(); 1]),
-        NT::Epsilon => BTreeSet::from([Rc::from(""); 1]),
-        NT::Non(n) => first_table
-            .get(n)
-            .ok_or("No first for nonterminal")?
-            .clone(),
-    })
-}
-
-fn get_first_table(
-    grammar: &GrammarChomsky,
-) -> Result<(Vec<BTreeSet<Rstr>>, BTreeMap<Rstr, BTreeSet<Rstr>>), Box<dyn Error>>
-{
-    let mut table = Vec::new();
-    table.resize(grammar.rules.len(), BTreeSet::new());
-    let mut symbol_table: BTreeMap<Rstr, BTreeSet<Rstr>> = BTreeMap::new();
-    let mut changed = true;
-    while changed {
-        changed = false;
-        for (i, (l, r)) in grammar.rules.iter().enumerate() {
-            for nt in r {
-                match nt {
-                    NT::Term(f) => {
-                        changed = table[i].insert(f.clone()) || changed;
-                        if !f.is_empty() {
-                            break;
-                        }
-                    }
-                    NT::Non(n) => {
-                        if let Some(first) = symbol_table.get(n) {
-                            if !first
-                                .is_subset(&BTreeSet::from([Rstr::from("")]))
-                            {
-                                changed = first
-                                    .iter()
-                                    .cloned()
-                                    .map(|x| table[i].insert(x))
-                                    .any(|x| x || changed);
-                                if !first.contains("") {
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                    NT::Epsilon => {
-                        table[i].insert("".into());
-                    }
-                }
-            }
-            symbol_table
-                .entry(l.clone())
-                .or_default()
-                .extend(table[i].iter().cloned());
-        }
-    }
-    Ok((table, symbol_table))
-}
-
-fn get_follow_table(
-    grammar: &GrammarChomsky,
-    first_table: &BTreeMap<Rstr, BTreeSet<Rstr>>,
-) -> Result<BTreeMap<Rstr, BTreeSet<Rstr>>, Box<dyn Error>> {
-    let mut follow_table: BTreeMap<Rstr, BTreeSet<Rstr>> = BTreeMap::new();
-    follow_table.insert(
-        grammar.start_nonterm.clone(),
-        BTreeSet::from([Rstr::from(""); 1]),
-    );
-    let mut changed = true;
-    while changed {
-        changed = false;
-        for (l, r) in grammar.rules.iter() {
-            let mut last = vec![NT::Epsilon];
-            for nt in r {
-                for la in last.iter() {
-                    if let NT::Non(n) = la {
-                        changed = first(nt, first_table)?
-                            .iter()
-                            .filter(|x| !x.is_empty())
-                            .map(|x| {
-                                follow_table
-                                    .entry(n.clone())
-                                    .or_default()
-                                    .insert(x.clone())
-                            })
-                            .any(|x| x || changed);
-                    }
-                }
-                if first(nt, first_table)?.contains("") {
-                    last.push(nt.clone())
-                } else {
-                    last = vec![nt.clone()];
-                }
-            }
-            for la in last {
-                if let NT::Non(n) = la {
-                    if let Some(f) = follow_table.get(l) {
-                        let f = f.clone();
-                        changed = f
-                            .iter()
-                            .map(|x| {
-                                follow_table
-                                    .entry(n.clone())
-                                    .or_default()
-                                    .insert(x.clone())
-                            })
-                            .any(|x| x || changed);
-                    }
-                }
-            }
-        }
-    }
-    Ok(follow_table)
-}
-
-type ParseTable = BTreeMap<Rstr, BTreeMap<Rstr, usize>>;
-
-fn gen_parsetable(
-    grammar: &GrammarChomsky,
-) -> Result<ParseTable, Box<dyn Error>> {
-    // let mut first_table = vec![];
-    // for (_l, r) in grammar.rules.iter() {
-    //     first_table
-    //         .push(first(r.first().ok_or("Non LL(1) gramamr.")?, grammar)?);
-    // }
-    // dbg!(&first_table);
-    // dbg!(get_first_table(grammar));
-    let (first_table, symbol_first_table) = get_first_table(grammar)?;
-    // println!("Got table.");
-    // dbg!(&first_table, &symbol_first_table);
-    // let mut follow_table = BTreeMap::new();
-    // for nt in grammar.nonterminals.iter() {
-    //     follow_table.insert(
-    //         nt.clone(),
-    //         follow(&NT::Non(nt.clone()), grammar, &symbol_first_table)?,
-    //     );
-    // }
-    let follow_table = get_follow_table(grammar, &symbol_first_table)?;
-    // println!("Got second table.");
-    // dbg!(&follow_table);
-    let mut pt = ParseTable::new();
-    for (i, (l, _)) in grammar.rules.iter().enumerate() {
-        let use_follow = first_table[i].contains(&Rstr::from(""));
-        let tp_iter = first_table[i]
-            .iter()
-            .filter(|x| !x.is_empty())
-            .cloned()
-            .zip(iter::repeat(i));
-        if use_follow {
-            let ft =
-                follow_table.get(l).ok_or(format!("No follow for {}", l))?;
-            pt.entry(l.clone())
-                .or_default()
-                .extend(ft.iter().cloned().zip(iter::repeat(i)).chain(tp_iter));
-        } else {
-            pt.entry(l.clone()).or_default().extend(tp_iter);
-        }
-        // dbg!(&pt);
-    }
-    // dbg!(first_table);
-    // dbg!(follow_table);
-    Ok(pt)
-}
+pub use crate::analysis::ParseTable;
+use crate::analysis::{productive_nonterms, GrammarAnalysis};
+use crate::cst::{cst_tokens, trivia_only, CstToken, TriviaConfig};
+use crate::derivation::Derivation;
+use crate::grammar::{
+    capitalize_first_letter, GrammarChomsky, Rstr, RuleShape,
+};
+use crate::lint::LintConfig;
+use crate::symbols::{BitSet, Symbol, SymbolId, END};
+use crate::trace::{Trace, TraceAction, TraceStep};
 
 #[derive(Debug)]
 pub enum AST<TokenT: TokenReq> {
@@ -195,23 +34,24 @@ impl<T: TokenReq> AST<T> {
 }
 
 enum StackObject {
-    Term(Rstr),
-    Nonterm(Rstr),
+    Term(SymbolId),
+    Nonterm(SymbolId),
     Epsilon,
     PopNode,
 }
 
-impl From<NT> for StackObject {
-    fn from(value: NT) -> Self {
+impl From<Symbol> for StackObject {
+    fn from(value: Symbol) -> Self {
         match value {
-            NT::Non(a) => StackObject::Nonterm(a),
-            NT::Term(a) => StackObject::Term(a),
-            NT::Epsilon => StackObject::Epsilon,
+            Symbol::Non(a) => StackObject::Nonterm(a),
+            Symbol::Term(a) => StackObject::Term(a),
+            Symbol::Epsilon => StackObject::Epsilon,
         }
     }
 }
 
 #[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
 enum TokenType {
     EOF,
     String(Rstr),
@@ -310,6 +150,9 @@ impl TokenTrait for Token {
     fn actual_string(&self) -> Rstr {
         self.as_str()
     }
+    fn is_eof(&self) -> bool {
+        matches!(self.token, TokenType::EOF)
+    }
 }
 
 pub trait TokenTrait {
@@ -321,6 +164,9 @@ pub trait TokenTrait {
     fn actual_string(&self) -> Rstr; // {
                                      // self.as_str()
                                      // }
+    /// Whether the token is the end of input made by
+    /// [`TokenTrait::make_eof`], never a token of the input.
+    fn is_eof(&self) -> bool;
 }
 
 // impl PartialEq<dyn TokenTrait> for Token {
@@ -343,24 +189,22 @@ struct TokenIter {
     pos: usize,
     str_pos: usize,
     line: usize,
-    input_str: Rstr,
 }
 
 impl TokenIter {
     fn new(string: Rstr, terminals: Vec<Rstr>) -> TokenIter {
         TokenIter {
-            str: string.clone(),
+            str: string,
             curr: Rstr::from(""),
             terminals,
             pos: 0,
             str_pos: 0,
             line: 0,
-            input_str: string,
         }
     }
 }
 
-impl<'a> Iterator for TokenIter {
+impl Iterator for TokenIter {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -396,8 +240,7 @@ fn print_arrow(pos: usize, len: usize) -> String {
     " ".repeat(pos) + &"^".repeat(len.max(1))
 }
 
-pub type TokenIteratorType<'s, TokenT: TokenReq> =
-    Box<dyn Iterator<Item = TokenT> + 's>;
+pub type TokenIteratorType<'s, TokenT> = Box<dyn Iterator<Item = TokenT> + 's>;
 
 pub fn get_tokenizer<'a>(
     grammar: &GrammarChomsky,
@@ -408,104 +251,572 @@ pub fn get_tokenizer<'a>(
     move |str: &'a str| Box::new(TokenIter::new(str.into(), terminals.clone()))
 }
 
+pub type ParseResult<TokenT> = Result<AST<TokenT>, Box<dyn Error>>;
+pub type TraceResult<TokenT> = (ParseResult<TokenT>, Trace);
+pub type DerivationResult<TokenT> =
+    Result<(AST<TokenT>, Derivation), Box<dyn Error>>;
+
+/// What may follow a prefix of a sentence, see [`Parser::complete`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    /// Terminals that can come next.
+    pub expected: BTreeSet<Rstr>,
+    /// Whether the input can end right after the prefix.
+    pub can_end: bool,
+    /// Whether the prefix can be extended to a sentence of the language.
+    pub viable: bool,
+    /// The input after the last consumed token, e.g. the start of a keyword
+    /// that is being typed, or where the prefix stopped being viable.
+    pub rest: Rstr,
+}
+
+/// An LL(1) parser built from a grammar. It owns the grammar and its parse
+/// table and is `Send + Sync`, so it can be built once and shared between
+/// threads.
+///
+/// The methods without a `_tokens` suffix use the built-in tokenizer, see
+/// [`Parser::tokenize`].
+#[derive(Debug, Clone)]
+pub struct Parser {
+    grammar: GrammarChomsky,
+    analysis: GrammarAnalysis,
+    productive: BitSet,
+    // Per nonterminal: whether it gets no node of its own, and its shape.
+    inline: Vec<bool>,
+    shapes: Vec<Option<RuleShape>>,
+    // Sorted by length, the built-in tokenizers try the longest first.
+    terminals: Vec<Rstr>,
+}
+
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Parser>();
+};
+
+impl Parser {
+    pub fn new(grammar: GrammarChomsky) -> Result<Parser, Box<dyn Error>> {
+        let start = grammar.start_nonterm.clone();
+        Parser::with_entries(grammar, &[&start])
+    }
+
+    /// A parser that can start from any of the `entries` nonterminals, named
+    /// as in the EBNF grammar. End of input is in the FOLLOW set of each of
+    /// them, so they all share one parse table. The methods that take no
+    /// entry start from the first one.
+    pub fn with_entries(
+        grammar: GrammarChomsky,
+        entries: &[&str],
+    ) -> Result<Parser, Box<dyn Error>> {
+        Parser::with_lints(grammar, entries, &LintConfig::default())
+    }
+
+    /// Like [`Parser::with_entries`], but fails if `grammar` has any
+    /// diagnostic that `lints` makes an error. Only those lints are checked.
+    pub fn with_lints(
+        grammar: GrammarChomsky,
+        entries: &[&str],
+        lints: &LintConfig,
+    ) -> Result<Parser, Box<dyn Error>> {
+        if entries.is_empty() {
+            return Err("No entry points".into());
+        }
+        let errors: Vec<String> = grammar
+            .lint_errors(lints)
+            .iter()
+            .filter(|d| d.is_error())
+            .map(|d| d.to_string())
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors.join("\n").into());
+        }
+        let analysis = GrammarAnalysis::with_entries(&grammar, entries)?;
+        let interned = &analysis.grammar;
+        let productive = productive_nonterms(interned);
+        let shapes: Vec<Option<RuleShape>> = interned
+            .nonterminals
+            .iter()
+            .map(|(_, n)| grammar.shape(n).cloned())
+            .collect();
+        let inline = interned
+            .nonterminals
+            .iter()
+            .map(|(n, name)| {
+                // Tails stay for `restore_left_recursion` to find.
+                !grammar.tails.contains_key(name)
+                    && (grammar.helpers.contains_key(name)
+                        || name.starts_with('_')
+                        || shapes[n.index()].as_ref().is_some_and(|s| s.inline))
+            })
+            .collect();
+        let mut terminals: Vec<Rstr> = grammar
+            .terminals
+            .iter()
+            .filter(|x| !x.is_empty())
+            .cloned()
+            .collect();
+        terminals.sort_by_key(|x| x.len());
+        Ok(Parser {
+            grammar,
+            analysis,
+            productive,
+            inline,
+            shapes,
+            terminals,
+        })
+    }
+
+    pub fn grammar(&self) -> &GrammarChomsky {
+        &self.grammar
+    }
+
+    pub fn table(&self) -> &ParseTable {
+        &self.analysis.table
+    }
+
+    pub fn analysis(&self) -> &GrammarAnalysis {
+        &self.analysis
+    }
+
+    pub fn entries(&self) -> &[Rstr] {
+        self.analysis.entries()
+    }
+
+    /// The built-in tokenizer, matching the longest terminal at each point.
+    pub fn tokenize<'a>(&self, input: &'a str) -> TokenIteratorType<'a, Token> {
+        Box::new(TokenIter::new(input.into(), self.terminals.clone()))
+    }
+
+    fn entry(&self, entry: &str) -> Result<SymbolId, Box<dyn Error>> {
+        let name = capitalize_first_letter(entry);
+        if !self.entries().contains(&name) {
+            return Err(format!("{} is not an entry point", name).into());
+        }
+        Ok(self
+            .analysis
+            .grammar
+            .nonterminals
+            .get(&name)
+            .expect("has rules"))
+    }
+
+    /// The first entry point, where parsing starts unless told otherwise.
+    fn start(&self) -> SymbolId {
+        self.analysis
+            .grammar
+            .nonterminals
+            .get(&self.entries()[0])
+            .expect("has rules")
+    }
+
+    /// The id of the terminal `token` stands for, [`END`] for end of input.
+    fn terminal<TokenT: TokenReq>(&self, token: &TokenT) -> Option<SymbolId> {
+        if token.is_eof() {
+            Some(END)
+        } else {
+            self.analysis.grammar.terminals.get(&token.as_str())
+        }
+    }
+
+    /// Parses from the first entry point.
+    pub fn parse(&self, input: &str) -> ParseResult<Token> {
+        self.drive(self.start(), input, self.tokenize(input), false, None)
+            .map(|(ast, _)| ast)
+    }
+
+    pub fn parse_from(&self, entry: &str, input: &str) -> ParseResult<Token> {
+        self.parse_tokens(entry, input, self.tokenize(input))
+    }
+
+    pub fn parse_tokens<'a, TokenT: TokenReq>(
+        &self,
+        entry: &str,
+        input: &'a str,
+        tokens: TokenIteratorType<'a, TokenT>,
+    ) -> ParseResult<TokenT> {
+        self.drive(self.entry(entry)?, input, tokens, false, None)
+            .map(|(ast, _)| ast)
+    }
+
+    /// Parses from the first entry point into a lossless tree: every
+    /// nonterminal (including the helpers and `_`-prefixed ones) gets a node
+    /// and the skipped input is kept as trivia on the tokens, so
+    /// `to_source()` on the tree gives back the input byte for byte. Input
+    /// that is only trivia has no token to keep it, it is kept on an end of
+    /// input token under the root.
+    pub fn parse_cst(
+        &self,
+        input: &str,
+        trivia: &TriviaConfig,
+    ) -> ParseResult<CstToken> {
+        let (mut cst, _) = self.drive(
+            self.start(),
+            input,
+            cst_tokens(input, self.terminals.clone(), trivia.clone()),
+            true,
+            None,
+        )?;
+        if let (AST::Node { children, .. }, Some(eof)) =
+            (&mut cst, trivia_only(input, trivia))
+        {
+            children.push(AST::Token(eof));
+        }
+        let source = cst.to_source();
+        if source != input {
+            let pos = source.len();
+            return Err(format!(
+                "Unrecognised input at byte {}: `{}`",
+                pos,
+                input[pos..].chars().take(20).collect::<String>()
+            )
+            .into());
+        }
+        Ok(cst)
+    }
+
+    /// Parses from the first entry point and also returns the leftmost
+    /// derivation the parser went through.
+    pub fn derive(&self, input: &str) -> DerivationResult<Token> {
+        self.derive_tokens(input, self.tokenize(input))
+    }
+
+    pub fn derive_tokens<'a, TokenT: TokenReq>(
+        &self,
+        input: &'a str,
+        tokens: TokenIteratorType<'a, TokenT>,
+    ) -> DerivationResult<TokenT> {
+        let (ast, steps) =
+            self.drive(self.start(), input, tokens, false, None)?;
+        Ok((ast, Derivation { steps }))
+    }
+
+    /// Parses from the first entry point and records every step of the
+    /// driver. The trace is returned even if parsing fails.
+    pub fn trace(&self, input: &str) -> TraceResult<Token> {
+        self.trace_tokens(input, self.tokenize(input))
+    }
+
+    pub fn trace_tokens<'a, TokenT: TokenReq>(
+        &self,
+        input: &'a str,
+        tokens: TokenIteratorType<'a, TokenT>,
+    ) -> TraceResult<TokenT> {
+        let mut steps = vec![];
+        let result =
+            self.drive(self.start(), input, tokens, false, Some(&mut steps));
+        (result.map(|(ast, _)| ast), Trace { steps })
+    }
+
+    /// Runs the driver from the first entry point over a prefix of a
+    /// sentence, without requiring it to end, and tells what may come next.
+    pub fn complete(&self, input: &str) -> Completion {
+        self.complete_tokens(input, self.tokenize(input))
+    }
+
+    pub fn complete_tokens<'a, TokenT: TokenReq>(
+        &self,
+        input: &'a str,
+        tokens: TokenIteratorType<'a, TokenT>,
+    ) -> Completion {
+        let mut stack = vec![StackObject::Nonterm(self.start())];
+        let mut end = 0;
+        let mut viable = true;
+        'tokens: for i in tokens {
+            let Some(lookahead) = self.terminal(&i) else {
+                viable = false;
+                break;
+            };
+            loop {
+                match stack.pop() {
+                    Some(StackObject::Nonterm(non)) => {
+                        match self.analysis.table.cell(non, lookahead) {
+                            Some(rul) => stack.extend(
+                                self.analysis.grammar.rules[rul]
+                                    .1
+                                    .iter()
+                                    .rev()
+                                    .copied()
+                                    .map(StackObject::from),
+                            ),
+                            None => {
+                                viable = false;
+                                break 'tokens;
+                            }
+                        }
+                    }
+                    Some(StackObject::Term(term)) if term == lookahead => {
+                        end = i.str_pos() + i.actual_string().len();
+                        continue 'tokens;
+                    }
+                    Some(StackObject::Epsilon | StackObject::PopNode) => {}
+                    Some(StackObject::Term(_)) | None => {
+                        viable = false;
+                        break 'tokens;
+                    }
+                }
+            }
+        }
+        let rest: Rstr = input.get(end..).unwrap_or("").into();
+        if !viable {
+            return Completion {
+                expected: BTreeSet::new(),
+                can_end: false,
+                viable,
+                rest,
+            };
+        }
+        let mut expected = BitSet::new(self.analysis.grammar.terminals.len());
+        let mut can_end = true;
+        for x in stack.iter().rev() {
+            match x {
+                StackObject::Term(t) => {
+                    expected.insert(*t);
+                    can_end = false;
+                }
+                StackObject::Nonterm(n) => {
+                    expected
+                        .union_with(&self.analysis.first.nonterms[n.index()]);
+                    can_end = self.analysis.first.nullable[n.index()];
+                }
+                StackObject::Epsilon | StackObject::PopNode => {}
+            }
+            if !can_end {
+                break;
+            }
+        }
+        let expected: BTreeSet<Rstr> = expected
+            .iter()
+            .map(|t| self.analysis.grammar.terminals.name(t).clone())
+            .collect();
+        let viable = stack.iter().all(|x| match x {
+            StackObject::Nonterm(n) => self.productive.contains(*n),
+            _ => true,
+        }) && (rest.is_empty()
+            || expected
+                .iter()
+                .any(|t| t.len() > rest.len() && t.starts_with(rest.as_ref())));
+        Completion {
+            expected,
+            can_end: can_end && rest.is_empty(),
+            viable,
+            rest,
+        }
+    }
+}
+
 pub fn get_parser<'a, TokenT: TokenReq>(
     grammar: GrammarChomsky,
     tokenizer: impl Fn(&'a str) -> TokenIteratorType<'a, TokenT>,
-) -> Result<
-    impl Fn(&'a str) -> Result<AST<TokenT>, Box<dyn Error>>,
-    Box<dyn Error>,
-> {
-    let parse_table = gen_parsetable(&grammar)?;
+) -> Result<impl Fn(&'a str) -> ParseResult<TokenT>, Box<dyn Error>> {
+    let parser = Parser::new(grammar)?;
     // println!("{:?}", parse_table);
     Ok(move |input_str: &'a str| {
-        let mut stack =
-            vec![StackObject::Nonterm(grammar.start_nonterm.clone())];
-        // let mut input = str.chars().map(|x| x.to_string());
+        let start = &parser.grammar.start_nonterm;
+        parser.parse_tokens(start, input_str, tokenizer(input_str))
+    })
+}
+
+/// Like [`get_parser`], but parsing can start from any of the `entries`
+/// nonterminals, see [`Parser::with_entries`]. The returned parser takes
+/// the input and the entry to start from.
+pub fn get_parser_from<'a, TokenT: TokenReq>(
+    grammar: GrammarChomsky,
+    tokenizer: impl Fn(&'a str) -> TokenIteratorType<'a, TokenT>,
+    entries: &[&str],
+) -> Result<impl Fn(&'a str, &str) -> ParseResult<TokenT>, Box<dyn Error>> {
+    let parser = Parser::with_entries(grammar, entries)?;
+    Ok(move |input_str: &'a str, entry: &str| {
+        parser.parse_tokens(entry, input_str, tokenizer(input_str))
+    })
+}
+
+/// See [`Parser::complete`].
+pub fn get_completer<'a, TokenT: TokenReq>(
+    grammar: GrammarChomsky,
+    tokenizer: impl Fn(&'a str) -> TokenIteratorType<'a, TokenT>,
+) -> Result<impl Fn(&'a str) -> Completion, Box<dyn Error>> {
+    let parser = Parser::new(grammar)?;
+    Ok(move |input_str: &'a str| {
+        parser.complete_tokens(input_str, tokenizer(input_str))
+    })
+}
+
+/// See [`Parser::trace`].
+pub fn get_trace_parser<'a, TokenT: TokenReq>(
+    grammar: GrammarChomsky,
+    tokenizer: impl Fn(&'a str) -> TokenIteratorType<'a, TokenT>,
+) -> Result<impl Fn(&'a str) -> TraceResult<TokenT>, Box<dyn Error>> {
+    let parser = Parser::new(grammar)?;
+    Ok(move |input_str: &'a str| {
+        parser.trace_tokens(input_str, tokenizer(input_str))
+    })
+}
+
+/// See [`Parser::derive`].
+pub fn get_derivation_parser<'a, TokenT: TokenReq>(
+    grammar: GrammarChomsky,
+    tokenizer: impl Fn(&'a str) -> TokenIteratorType<'a, TokenT>,
+) -> Result<impl Fn(&'a str) -> DerivationResult<TokenT>, Box<dyn Error>> {
+    let parser = Parser::new(grammar)?;
+    Ok(move |input_str: &'a str| {
+        parser.derive_tokens(input_str, tokenizer(input_str))
+    })
+}
+
+/// See [`Parser::parse_cst`].
+pub fn get_cst_parser<'a>(
+    grammar: GrammarChomsky,
+    trivia: TriviaConfig,
+) -> Result<impl Fn(&'a str) -> ParseResult<CstToken>, Box<dyn Error>> {
+    let parser = Parser::new(grammar)?;
+    Ok(move |input_str: &'a str| parser.parse_cst(input_str, &trivia))
+}
+
+impl Parser {
+    /// The step the driver is about to take, `None` for bookkeeping only
+    /// steps.
+    fn trace_step<TokenT: TokenReq>(
+        &self,
+        stack: &[StackObject],
+        input_str: &str,
+        i: &TokenT,
+    ) -> Option<TraceStep> {
+        let lookahead = self.terminal(i);
+        let action = match *stack.last()? {
+            StackObject::Nonterm(non) => {
+                match lookahead.and_then(|t| self.analysis.table.cell(non, t)) {
+                    Some(rule) => TraceAction::Expand {
+                        nonterm: self
+                            .analysis
+                            .grammar
+                            .nonterminals
+                            .name(non)
+                            .clone(),
+                        rule,
+                    },
+                    None => TraceAction::Error,
+                }
+            }
+            StackObject::Term(term)
+                if term != END && Some(term) == lookahead =>
+            {
+                TraceAction::Match(
+                    self.analysis.grammar.terminals.name(term).clone(),
+                )
+            }
+            StackObject::Term(_) => TraceAction::Error,
+            StackObject::Epsilon => TraceAction::PopEpsilon,
+            StackObject::PopNode => return None,
+        };
+        Some(TraceStep {
+            stack: stack
+                .iter()
+                .rev()
+                .filter_map(|x| match *x {
+                    StackObject::Term(a) => Some(Symbol::Term(a)),
+                    StackObject::Nonterm(a) => Some(Symbol::Non(a)),
+                    StackObject::Epsilon => Some(Symbol::Epsilon),
+                    StackObject::PopNode => None,
+                })
+                .map(|x| self.analysis.grammar.name(x).into())
+                .collect(),
+            input: input_str.get(i.str_pos()..).unwrap_or("").into(),
+            action,
+        })
+    }
+
+    /// The LL(1) driver loop shared by all parsers. With `lossless` set
+    /// every nonterminal gets its own node, otherwise helpers and
+    /// `_`-prefixed ones are left out and the tree is shaped by the rule
+    /// annotations. Returns the tree and the indices of the applied
+    /// productions; every step is recorded to `trace`.
+    fn drive<'a, TokenT: TokenReq>(
+        &self,
+        start: SymbolId,
+        input_str: &'a str,
+        mut input: TokenIteratorType<'a, TokenT>,
+        lossless: bool,
+        mut trace: Option<&mut Vec<TraceStep>>,
+    ) -> Result<(AST<TokenT>, Vec<usize>), Box<dyn Error>> {
+        let mut stack = vec![StackObject::Nonterm(start)];
         let mut rules = vec![];
-        let mut input = tokenizer(input_str);
-        let mut i = input.next().ok_or("Not from language, empty")?;
+        let mut i = input.next().unwrap_or(TokenT::make_eof(0, 0, 0));
         let mut node_stack = vec![AST::Node {
             name: Rstr::from("chomchom_root"),
             children: vec![],
         }];
-        // dbg!(&stack);
-        // dbg!(&grammar.start_nonterm);
+        // The nonterminal each node on `node_stack` was made for.
+        let mut owners: Vec<Option<SymbolId>> = vec![None];
         while !stack.is_empty() {
-            // dbg!(stack.last());
-            // dbg!(&i, &input);
+            if let Some(trace) = trace.as_deref_mut() {
+                trace.extend(self.trace_step(&stack, input_str, &i));
+            }
             match stack.pop().ok_or("Not from language")? {
                 StackObject::Nonterm(non) => {
-                    // dbg!(&non);
-                    // println!(
-                    //     "Possible tokens at {:?}: {:?}",
-                    //     non,
-                    //     parse_table
-                    //         .get(&non)
-                    //         .ok_or("Invalid parsetable")?
-                    //         .keys()
-                    // );
-                    let rul = parse_table
-                    .get(&non)
-                    .ok_or(format!("Ivalid parsetable. No rules for {}", non))?
-                    .get(i.as_str().as_ref())
-                    .ok_or(
-                        format!("Unexpected token. Got `{}`. Expected one of {:?}. Line: {}, Pos: {}\n{}\n{}",
-                            i.as_str(),
-                            parse_table.get(&non).ok_or("Invalid parsetable")?.keys(), i.line(),
-                            i.column(),
-                            {
-                                let pos = i.str_pos();
-
-                                // 1. Find the start of the line (search backwards for '\n')
-                                // If no newline is found, we are at the start of the string (0)
-                                let start = input_str[..pos]
-                                    .rfind('\n')
-                                    .map(|index| index + 1) // Skip the actual '\n' char
-                                    .unwrap_or(0);
-
-                                // 2. Find the end of the line (search forwards for '\n')
-                                // If no newline is found, we go to the end of the string
-                                let end = input_str[pos..]
-                                    .find('\n')
-                                    .map(|index| pos + index) // Add offset to current pos
-                                    .unwrap_or(input_str.len());
-
-                                // println!("Line content: {}, start: {}, end: {}", &input_str[start..end], start, end);
-                                // println!("Actual string: {}", i.actual_string());
-
-                                // 3. Slice the string
-                                &input_str[start..end]
-                            },
-                            // input_str[i.str_pos()], //[(i.str_pos().saturating_sub(100)).max(0)..(i.str_pos().saturating_add(100)).min(input_str.len()-1)],
-                            print_arrow(i.column(), i.actual_string().len()),
-                        ),
-                    )?;
-                    // println!("Using Rule {}", rul);
+                    let name = self.analysis.grammar.nonterminals.name(non);
+                    if !self.analysis.table.defines(non) {
+                        return Err(format!(
+                            "Ivalid parsetable. No rules for {}",
+                            name
+                        )
+                        .into());
+                    }
+                    let rul = self
+                        .terminal(&i)
+                        .and_then(|t| self.analysis.table.cell(non, t))
+                        .ok_or(
+                            format!("Unexpected token. Got `{}`. Expected one of {:?}. Line: {}, Pos: {}\n{}\n{}",
+                                i.as_str(),
+                                self.analysis.table.lookaheads(name), i.line(),
+                                i.column(),
+                                {
+                                    let pos = i.str_pos();
+
+                                    // 1. Find the start of the line (search backwards for '\n')
+                                    // If no newline is found, we are at the start of the string (0)
+                                    let start = input_str[..pos]
+                                        .rfind('\n')
+                                        .map(|index| index + 1) // Skip the actual '\n' char
+                                        .unwrap_or(0);
+
+                                    // 2. Find the end of the line (search forwards for '\n')
+                                    // If no newline is found, we go to the end of the string
+                                    let end = input_str[pos..]
+                                        .find('\n')
+                                        .map(|index| pos + index) // Add offset to current pos
+                                        .unwrap_or(input_str.len());
+
+
+                                    // 3. Slice the string
+                                    &input_str[start..end]
+                                },
+                                print_arrow(i.column(), i.actual_string().len()),
+                            ),
+                        )?;
                     rules.push(rul);
-                    if !non.starts_with('_') {
+                    if lossless || !self.inline[non.index()] {
                         stack.push(StackObject::PopNode);
                         node_stack.push(AST::Node {
-                            name: non.clone(),
+                            name: name.clone(),
                             children: Vec::new(),
-                        })
+                        });
+                        owners.push(Some(non));
                     }
-                    // println!("Expanding nonterm: {}", non);
                     stack.extend(
-                        grammar.rules[*rul].1.iter().rev().cloned().map(|x| {
-                            // println!("{}", x);
-                            x.into()
-                        }),
+                        self.analysis.grammar.rules[rul]
+                            .1
+                            .iter()
+                            .rev()
+                            .copied()
+                            .map(StackObject::from),
                     );
-                    // println!("---");
                 }
                 StackObject::Term(term) => {
-                    // println!("Matching term: {}", term);
-                    if term.as_ref() != i.as_str().as_ref() {
+                    if i.is_eof() || self.terminal(&i) != Some(term) {
                         return Err(format!(
                             "Not from language. Term '{}' not expected. Expected {}. Rest: {}, Stack:",
                             i.as_str(),
-                            term,
-                            input_str[i.str_pos()..].to_string(),
+                            self.analysis.grammar.terminals.name(term),
+                            &input_str[i.str_pos()..],
                         )
                         .into());
                     }
@@ -514,34 +825,56 @@ pub fn get_parser<'a, TokenT: TokenReq>(
                         i.column() + i.as_str().len(),
                         i.str_pos() + i.as_str().len(),
                     );
+                    let drop = !lossless
+                        && owners
+                            .last()
+                            .copied()
+                            .flatten()
+                            .and_then(|n| self.shapes[n.index()].as_ref())
+                            .is_some_and(|s| s.drops(&i.as_str()));
                     if let AST::Node { children, .. } =
                         node_stack.last_mut().ok_or("Empty stack?")?
                     {
-                        children.push(AST::Token(i));
+                        if !drop {
+                            children.push(AST::Token(i));
+                        }
                     }
                     i = input.next().unwrap_or(eof);
                 }
-                StackObject::Epsilon => {
-                    // dbg!("Epsilon");
-                }
+                StackObject::Epsilon => {}
                 StackObject::PopNode => {
-                    // dbg!("PopNode");
-                    let n = node_stack.pop().ok_or("Empty stack?")?;
+                    let mut n = node_stack.pop().ok_or("Empty stack?")?;
+                    let owner = owners.pop().flatten();
+                    if let AST::Node { children, .. } = &mut n {
+                        if !lossless
+                            && children.len() == 1
+                            && owner
+                                .and_then(|o| self.shapes[o.index()].as_ref())
+                                .is_some_and(|s| s.collapse)
+                        {
+                            n = children.pop().expect("checked above");
+                        }
+                    }
                     if let Some(AST::Node { children, .. }) =
                         node_stack.last_mut()
                     {
                         children.push(n);
                     } else {
                         panic!("Cannot pop emptry stack");
-                        // node_stack.push(n);
                     }
                 }
             }
         }
-        if stack.is_empty() && input.next().is_some() {
+        if !i.is_eof() || input.next().is_some() {
             return Err("Not from language too long.".into());
         }
-        // println!("{:?}", rules);
-        Ok(node_stack.pop().ok_or("Invalid language.")?)
-    })
+        if let Some(trace) = trace {
+            trace.push(TraceStep {
+                stack: vec![],
+                input: "".into(),
+                action: TraceAction::Accept,
+            });
+        }
+        Ok((node_stack.pop().ok_or("Invalid language.")?, rules))
+    }
 }
@@ -0,0 +1,108 @@
+//! Export to the grammar input of
+//! [parsingtbl](https://pages.fit.cvut.cz/peckato1/parsingtbl/), the tool
+//! that builds LL(1) parsing tables. It reads a grammar as one line per
+//! nonterminal, `->` between the nonterminal and its alternatives, `|`
+//! between alternatives and `ε` for the empty one, the first line being
+//! the start rule:
+//!
+//! ```text
+//! Assign -> Number ":=" Value Assign__opt1
+//! Assign__opt1 -> " " | ε
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+
+use crate::grammar::{GrammarChomsky, Rstr, NT};
+
+/// `t` in double quotes, with `"` and `\` escaped by a backslash.
+fn quote(t: &str) -> String {
+    let mut out = String::from('"');
+    for c in t.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    out
+}
+
+impl GrammarChomsky {
+    /// The grammar in the input format of
+    /// [parsingtbl](https://pages.fit.cvut.cz/peckato1/parsingtbl/): one
+    /// line per nonterminal with its alternatives separated by `|`, the
+    /// start rule first and the rest in the order they are reached from it.
+    /// Symbols are separated by spaces, terminals are quoted with `"` and
+    /// `\` escaped, and the empty alternative is `ε`.
+    pub fn to_parsingtbl(&self) -> String {
+        let mut groups: Vec<(Rstr, Vec<&[NT]>)> = vec![];
+        let mut index: HashMap<Rstr, usize> = HashMap::new();
+        for (l, r) in self.rules.iter() {
+            let i = *index.entry(l.clone()).or_insert_with(|| {
+                groups.push((l.clone(), vec![]));
+                groups.len() - 1
+            });
+            groups[i].1.push(r);
+        }
+
+        // Breadth first from the start, then whatever is unreachable.
+        let mut order = vec![];
+        let mut seen = vec![false; groups.len()];
+        let mut queue = VecDeque::new();
+        let roots = std::iter::once(&self.start_nonterm)
+            .chain(groups.iter().map(|(l, _)| l));
+        for root in roots {
+            let Some(&i) = index.get(root) else {
+                continue;
+            };
+            if seen[i] {
+                continue;
+            }
+            seen[i] = true;
+            queue.push_back(i);
+            while let Some(i) = queue.pop_front() {
+                order.push(i);
+                for nt in groups[i].1.iter().flat_map(|r| r.iter()) {
+                    if let NT::Non(n) = nt {
+                        if let Some(&j) = index.get(n) {
+                            if !seen[j] {
+                                seen[j] = true;
+                                queue.push_back(j);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut out = String::new();
+        for i in order {
+            let (l, alternatives) = &groups[i];
+            let _ = write!(out, "{} ->", l);
+            for (a, r) in alternatives.iter().enumerate() {
+                if a > 0 {
+                    out.push_str(" |");
+                }
+                let mut empty = true;
+                for nt in r.iter() {
+                    match nt {
+                        NT::Non(n) => {
+                            let _ = write!(out, " {}", n);
+                        }
+                        NT::Term(t) if !t.is_empty() => {
+                            let _ = write!(out, " {}", quote(t));
+                        }
+                        NT::Term(_) | NT::Epsilon => continue,
+                    }
+                    empty = false;
+                }
+                if empty {
+                    out.push_str(" ε");
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::VecDeque;
+
+use crate::grammar::capitalize_first_letter;
+use crate::parser_generator::{TokenReq, AST};
+
+impl<T: TokenReq> AST<T> {
+    pub fn children(&self) -> &[AST<T>] {
+        match self {
+            AST::Node { children, .. } => children,
+            AST::Token(_) => &[],
+        }
+    }
+
+    pub fn is_token(&self) -> bool {
+        matches!(self, AST::Token(_))
+    }
+
+    /// Nodes are named after the chomsky nonterminals, which have their
+    /// first letter capitalised, so `expression` matches `Expression`.
+    pub fn has_name(&self, name: &str) -> bool {
+        match self {
+            AST::Node { name: n, .. } => {
+                n.as_ref() == name || *n == capitalize_first_letter(name)
+            }
+            AST::Token(_) => false,
+        }
+    }
+
+    pub fn preorder(&self) -> Preorder<'_, T> {
+        Preorder { stack: vec![self] }
+    }
+
+    pub fn postorder(&self) -> Postorder<'_, T> {
+        Postorder {
+            stack: vec![(self, 0)],
+        }
+    }
+
+    /// Breadth-first iteration, yielding every node with its depth.
+    pub fn by_depth(&self) -> ByDepth<'_, T> {
+        ByDepth {
+            queue: VecDeque::from([(0, self)]),
+        }
+    }
+
+    pub fn find_all(&self, name: &str) -> Vec<&AST<T>> {
+        self.preorder().filter(|x| x.has_name(name)).collect()
+    }
+
+    pub fn cursor(&self) -> Cursor<'_, T> {
+        Cursor {
+            path: vec![(self, 0)],
+        }
+    }
+
+    /// Returns the nodes matching `selector`, in preorder.
+    ///
+    /// A selector is a list of node names separated by combinators, like in
+    /// CSS: `a > b` matches `b` whose parent is `a`, `a b` matches `b` with
+    /// any ancestor `a`. `*` matches any node and `"x"` matches a token `x`.
+    pub fn select(&self, selector: &str) -> Result<Vec<&AST<T>>, String> {
+        let selector = Selector::parse(selector)?;
+        let mut found = vec![];
+        let mut ancestors = vec![];
+        self.select_into(&selector, &mut ancestors, &mut found);
+        Ok(found)
+    }
+
+    fn select_into<'a>(
+        &'a self,
+        selector: &Selector,
+        ancestors: &mut Vec<&'a AST<T>>,
+        found: &mut Vec<&'a AST<T>>,
+    ) {
+        if selector.matches(self, ancestors) {
+            found.push(self);
+        }
+        ancestors.push(self);
+        for c in self.children() {
+            c.select_into(selector, ancestors, found);
+        }
+        ancestors.pop();
+    }
+}
+
+pub struct Preorder<'a, T: TokenReq> {
+    stack: Vec<&'a AST<T>>,
+}
+
+impl<'a, T: TokenReq> Iterator for Preorder<'a, T> {
+    type Item = &'a AST<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.children().iter().rev());
+        Some(node)
+    }
+}
+
+pub struct Postorder<'a, T: TokenReq> {
+    stack: Vec<(&'a AST<T>, usize)>,
+}
+
+impl<'a, T: TokenReq> Iterator for Postorder<'a, T> {
+    type Item = &'a AST<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, i) = self.stack.last_mut()?;
+            let node = *node;
+            if let Some(child) = node.children().get(*i) {
+                *i += 1;
+                self.stack.push((child, 0));
+            } else {
+                self.stack.pop();
+                return Some(node);
+            }
+        }
+    }
+}
+
+pub struct ByDepth<'a, T: TokenReq> {
+    queue: VecDeque<(usize, &'a AST<T>)>,
+}
+
+impl<'a, T: TokenReq> Iterator for ByDepth<'a, T> {
+    type Item = (usize, &'a AST<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (depth, node) = self.queue.pop_front()?;
+        self.queue
+            .extend(node.children().iter().map(|c| (depth + 1, c)));
+        Some((depth, node))
+    }
+}
+
+/// Walks the tree with parent and sibling navigation. Every `goto_*`
+/// method returns `false` and stays in place if there is nowhere to go.
+#[derive(Clone)]
+pub struct Cursor<'a, T: TokenReq> {
+    // Every node on the way from the root, with its index in its parent.
+    path: Vec<(&'a AST<T>, usize)>,
+}
+
+impl<'a, T: TokenReq> Cursor<'a, T> {
+    pub fn node(&self) -> &'a AST<T> {
+        self.path.last().expect("cursor path is never empty").0
+    }
+
+    pub fn depth(&self) -> usize {
+        self.path.len() - 1
+    }
+
+    pub fn parent(&self) -> Option<&'a AST<T>> {
+        self.path.len().checked_sub(2).map(|i| self.path[i].0)
+    }
+
+    pub fn goto_parent(&mut self) -> bool {
+        if self.path.len() > 1 {
+            self.path.pop();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn goto_first_child(&mut self) -> bool {
+        match self.node().children().first() {
+            Some(c) => {
+                self.path.push((c, 0));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn goto_next_sibling(&mut self) -> bool {
+        self.goto_sibling(|i| i.checked_add(1))
+    }
+
+    pub fn goto_prev_sibling(&mut self) -> bool {
+        self.goto_sibling(|i| i.checked_sub(1))
+    }
+
+    fn goto_sibling(&mut self, step: impl Fn(usize) -> Option<usize>) -> bool {
+        let Some(parent) = self.parent() else {
+            return false;
+        };
+        let (_, i) = self.path[self.path.len() - 1];
+        match step(i).and_then(|i| parent.children().get(i).map(|c| (c, i))) {
+            Some(sibling) => {
+                *self.path.last_mut().expect("checked above") = sibling;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+enum Combinator {
+    Child,
+    Descendant,
+}
+
+enum Pattern {
+    Any,
+    Node(String),
+    Token(String),
+}
+
+impl Pattern {
+    fn matches<T: TokenReq>(&self, node: &AST<T>) -> bool {
+        match (self, node) {
+            (Pattern::Any, _) => true,
+            (Pattern::Node(n), node) => node.has_name(n),
+            (Pattern::Token(s), AST::Token(t)) => t.as_str().as_ref() == s,
+            (Pattern::Token(_), AST::Node { .. }) => false,
+        }
+    }
+}
+
+struct Selector {
+    patterns: Vec<Pattern>,
+    // `combinators[i]` sits between `patterns[i]` and `patterns[i + 1]`.
+    combinators: Vec<Combinator>,
+}
+
+impl Selector {
+    fn parse(s: &str) -> Result<Selector, String> {
+        let mut patterns = vec![];
+        let mut combinators = vec![];
+        let mut rest = s.trim_start();
+        while !rest.is_empty() {
+            if let Some(r) = rest.strip_prefix('>') {
+                if combinators.len() + 1 != patterns.len() {
+                    return Err(format!("Misplaced '>' in selector `{}`", s));
+                }
+                combinators.push(Combinator::Child);
+                rest = r.trim_start();
+                continue;
+            }
+            let (pattern, r) = if let Some(r) = rest.strip_prefix('"') {
+                let n = r
+                    .find('"')
+                    .ok_or(format!("Unterminated string in `{}`", s))?;
+                (Pattern::Token(r[..n].to_string()), &r[n + 1..])
+            } else {
+                let n = rest
+                    .find(|c: char| c.is_whitespace() || c == '>')
+                    .unwrap_or(rest.len());
+                match &rest[..n] {
+                    "*" => (Pattern::Any, &rest[n..]),
+                    name => (Pattern::Node(name.to_string()), &rest[n..]),
+                }
+            };
+            if combinators.len() < patterns.len() {
+                combinators.push(Combinator::Descendant);
+            }
+            patterns.push(pattern);
+            rest = r.trim_start();
+        }
+        if patterns.is_empty() || combinators.len() + 1 != patterns.len() {
+            return Err(format!("Invalid selector `{}`", s));
+        }
+        Ok(Selector {
+            patterns,
+            combinators,
+        })
+    }
+
+    fn matches<T: TokenReq>(
+        &self,
+        node: &AST<T>,
+        ancestors: &[&AST<T>],
+    ) -> bool {
+        let last = self.patterns.len() - 1;
+        self.patterns[last].matches(node) && self.matches_up(last, ancestors)
+    }
+
+    /// Checks the patterns left of `step` against `ancestors`.
+    fn matches_up<T: TokenReq>(
+        &self,
+        step: usize,
+        ancestors: &[&AST<T>],
+    ) -> bool {
+        if step == 0 {
+            return true;
+        }
+        let pattern = &self.patterns[step - 1];
+        match self.combinators[step - 1] {
+            Combinator::Child => match ancestors.split_last() {
+                Some((parent, rest)) => {
+                    pattern.matches(*parent) && self.matches_up(step - 1, rest)
+                }
+                None => false,
+            },
+            Combinator::Descendant => (0..ancestors.len()).rev().any(|i| {
+                pattern.matches(ancestors[i])
+                    && self.matches_up(step - 1, &ancestors[..i])
+            }),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::fmt::Write;
+
+use crate::parser_generator::{TokenReq, AST};
+
+pub(crate) fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Quotes `s` as a Graphviz label. Backslashes start escapes like `\n` in
+/// labels, so they are doubled, and newlines become `\n`.
+fn dot_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Escapes `s` for LaTeX text mode, e.g. inside `\texttt{}`.
+pub(crate) fn latex_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\textbackslash{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            '~' => out.push_str("\\textasciitilde{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+impl<T: TokenReq> AST<T> {
+    /// The `str_pos` range covered by the tokens under this node, if it has
+    /// any.
+    pub fn span(&self) -> Option<(usize, usize)> {
+        match self {
+            AST::Token(t) => {
+                Some((t.str_pos(), t.str_pos() + t.actual_string().len()))
+            }
+            AST::Node { children, .. } => {
+                let start = children.iter().find_map(|c| c.span())?.0;
+                let end = children.iter().rev().find_map(|c| c.span())?.1;
+                Some((start, end))
+            }
+        }
+    }
+
+    /// `{"name": .., "span": .., "children": [..]}` for nodes and
+    /// `{"token": .., "span": .., "line": .., "column": ..}` for tokens.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+
+    fn write_json(&self, out: &mut String) {
+        let span = match self.span() {
+            Some((start, end)) => {
+                format!("{{\"start\":{},\"end\":{}}}", start, end)
+            }
+            None => "null".into(),
+        };
+        match self {
+            AST::Node { name, children } => {
+                let _ = write!(
+                    out,
+                    "{{\"name\":{},\"span\":{},\"children\":[",
+                    json_escape(name),
+                    span
+                );
+                for (i, c) in children.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    c.write_json(out);
+                }
+                out.push_str("]}");
+            }
+            AST::Token(t) => {
+                let _ = write!(
+                    out,
+                    "{{\"token\":{},\"span\":{},\"line\":{},\"column\":{}}}",
+                    json_escape(&t.actual_string()),
+                    span,
+                    t.line(),
+                    t.column()
+                );
+            }
+        }
+    }
+
+    /// A compact S-expression, e.g. `(Expression (Term "1") "+" (Term "2"))`.
+    pub fn to_sexpr(&self) -> String {
+        let mut out = String::new();
+        self.write_sexpr(&mut out);
+        out
+    }
+
+    fn write_sexpr(&self, out: &mut String) {
+        match self {
+            AST::Node { name, children } => {
+                out.push('(');
+                out.push_str(name);
+                for c in children.iter() {
+                    out.push(' ');
+                    c.write_sexpr(out);
+                }
+                out.push(')');
+            }
+            AST::Token(t) => out.push_str(&json_escape(&t.actual_string())),
+        }
+    }
+
+    /// A Graphviz `digraph`; tokens are drawn as boxes.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph AST {\n");
+        let mut counter = 0;
+        self.write_dot(&mut out, &mut counter);
+        out.push_str("}\n");
+        out
+    }
+
+    fn write_dot(&self, out: &mut String, counter: &mut usize) -> usize {
+        let id = *counter;
+        *counter += 1;
+        match self {
+            AST::Node { name, children } => {
+                let _ =
+                    writeln!(out, "    n{} [label={}];", id, dot_escape(name));
+                for c in children.iter() {
+                    let child = c.write_dot(out, counter);
+                    let _ = writeln!(out, "    n{} -> n{};", id, child);
+                }
+            }
+            AST::Token(t) => {
+                let _ = writeln!(
+                    out,
+                    "    n{} [label={}, shape=box];",
+                    id,
+                    dot_escape(&t.actual_string())
+                );
+            }
+        }
+        id
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::grammar::{
+    capitalize_first_letter, GrammarChomsky, GrammarEBNF, Rstr, EBNF, NT,
+};
+
+/// A sequence as one expression, `ε` if it is empty.
+fn seq(mut items: Vec<EBNF>) -> EBNF {
+    match items.len() {
+        0 => EBNF::Epsilon,
+        1 => items.remove(0),
+        _ => EBNF::Concat(items),
+    }
+}
+
+/// The items of a sequence, the reverse of [`seq`].
+fn parts(e: &EBNF) -> Vec<EBNF> {
+    match e {
+        EBNF::Concat(items) => items.clone(),
+        EBNF::Epsilon => vec![],
+        e => vec![e.clone()],
+    }
+}
+
+/// Alternatives as one expression, an option if one of them is empty.
+fn options(alternatives: Vec<Vec<EBNF>>) -> EBNF {
+    let empty = alternatives.iter().any(|a| a.is_empty());
+    let mut rest: Vec<EBNF> = vec![];
+    for a in alternatives.into_iter().filter(|a| !a.is_empty()) {
+        let a = seq(a);
+        if !rest.contains(&a) {
+            rest.push(a);
+        }
+    }
+    let e = match rest.len() {
+        0 => return EBNF::Epsilon,
+        1 => rest.remove(0),
+        _ => EBNF::Or(rest),
+    };
+    if empty {
+        EBNF::Optional(Box::new(e))
+    } else {
+        e
+    }
+}
+
+/// The alternatives of an expression, the reverse of [`options`].
+fn alternatives(e: &EBNF) -> Vec<Vec<EBNF>> {
+    match e {
+        EBNF::Or(a) => a.iter().map(parts).collect(),
+        EBNF::Optional(a) => {
+            let mut out = vec![vec![]];
+            out.extend(alternatives(a));
+            out
+        }
+        e => vec![parts(e)],
+    }
+}
+
+/// The productions of `nonterm` as one expression.
+fn body(nonterm: &Rstr, productions: &[&Vec<NT>]) -> EBNF {
+    let productions: Vec<Vec<EBNF>> = productions
+        .iter()
+        .map(|r| {
+            r.iter()
+                .filter_map(|x| match x {
+                    NT::Non(n) => Some(EBNF::Non(n.clone())),
+                    NT::Term(t) if !t.is_empty() => Some(EBNF::Term(t.clone())),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+    repeat(nonterm, productions)
+}
+
+/// The alternatives of `nonterm` as one expression. Tail recursion
+/// `X -> α X | ε` becomes `{ α }` and lists like `X -> β γ X | β` become
+/// `{ β γ } β`, if `X` is not used anywhere else in them. Other tail
+/// recursion, like prefix operators, is clearer left as it is.
+fn repeat(nonterm: &Rstr, productions: Vec<Vec<EBNF>>) -> EBNF {
+    let this = EBNF::Non(nonterm.clone());
+    let (repeated, rest): (Vec<_>, Vec<_>) = productions
+        .iter()
+        .cloned()
+        .partition(|r| r.len() > 1 && r.last() == Some(&this));
+    let repeated: Vec<Vec<EBNF>> = repeated
+        .into_iter()
+        .map(|mut r| {
+            r.pop();
+            r
+        })
+        .collect();
+    let rest: Vec<Vec<EBNF>> =
+        rest.into_iter().filter(|r| !r.is_empty()).collect();
+    let list = match rest.as_slice() {
+        [] => true,
+        [item] => repeated.iter().all(|r| r.starts_with(item)),
+        _ => false,
+    };
+    if !list
+        || repeated.is_empty()
+        || repeated
+            .iter()
+            .chain(rest.iter())
+            .any(|r| r.contains(&this))
+    {
+        return options(productions);
+    }
+    let mut out = vec![EBNF::Iteration(Box::new(options(repeated)))];
+    out.extend(rest.into_iter().flatten());
+    seq(out)
+}
+
+/// Flattens nested sequences and alternatives, drops the ε in them and
+/// merges the options and repetitions that can be.
+fn tidy(e: EBNF) -> EBNF {
+    match e {
+        EBNF::Concat(items) => {
+            let mut out: Vec<EBNF> = vec![];
+            for item in items.into_iter().map(tidy) {
+                match item {
+                    EBNF::Concat(inner) => out.extend(inner),
+                    EBNF::Epsilon => {}
+                    EBNF::Iteration(repeated) => {
+                        // `α { α }` is `α +`.
+                        let r = parts(&repeated);
+                        if out.len() >= r.len() && out.ends_with(&r) {
+                            out.truncate(out.len() - r.len());
+                            out.push(EBNF::OneOrMore(repeated));
+                        } else {
+                            out.push(EBNF::Iteration(repeated));
+                        }
+                    }
+                    item => out.push(item),
+                }
+            }
+            // `{ α } α` is `α +` as well.
+            let mut i = 0;
+            while i < out.len() {
+                if let EBNF::Iteration(repeated) = &out[i] {
+                    let r = parts(repeated);
+                    if out[i + 1..].starts_with(&r) {
+                        let repeated = repeated.clone();
+                        out.splice(
+                            i..=i + r.len(),
+                            [EBNF::OneOrMore(repeated)],
+                        );
+                    }
+                }
+                i += 1;
+            }
+            seq(out)
+        }
+        EBNF::Or(alternatives) => {
+            let mut out: Vec<Vec<EBNF>> = vec![];
+            for a in alternatives.into_iter().map(tidy) {
+                match a {
+                    EBNF::Or(inner) => out.extend(inner.iter().map(parts)),
+                    EBNF::Optional(inner) => {
+                        out.push(vec![]);
+                        match *inner {
+                            EBNF::Or(inner) => {
+                                out.extend(inner.iter().map(parts))
+                            }
+                            inner => out.push(parts(&inner)),
+                        }
+                    }
+                    a => out.push(parts(&a)),
+                }
+            }
+            options(out)
+        }
+        EBNF::Optional(a) => match tidy(*a) {
+            EBNF::Epsilon => EBNF::Epsilon,
+            EBNF::OneOrMore(a) => EBNF::Iteration(a),
+            a @ (EBNF::Iteration(_) | EBNF::Optional(_)) => a,
+            a => EBNF::Optional(Box::new(a)),
+        },
+        EBNF::Iteration(a) => match tidy(*a) {
+            EBNF::Epsilon => EBNF::Epsilon,
+            EBNF::Iteration(a) | EBNF::OneOrMore(a) | EBNF::Optional(a) => {
+                EBNF::Iteration(a)
+            }
+            a => EBNF::Iteration(Box::new(a)),
+        },
+        EBNF::OneOrMore(a) => EBNF::OneOrMore(Box::new(tidy(*a))),
+        e => e,
+    }
+}
+
+/// Replaces the nonterminals in `inline` by their expressions and the rest
+/// by their names in `names`.
+fn expand(
+    e: &EBNF,
+    inline: &HashMap<Rstr, EBNF>,
+    names: &HashMap<Rstr, Rstr>,
+) -> EBNF {
+    let expand_all = |a: &[EBNF]| -> Vec<EBNF> {
+        a.iter().map(|x| expand(x, inline, names)).collect()
+    };
+    match e {
+        EBNF::Non(n) => match inline.get(n) {
+            Some(body) => expand(body, inline, names),
+            None => EBNF::Non(names.get(n).unwrap_or(n).clone()),
+        },
+        EBNF::Concat(a) => EBNF::Concat(expand_all(a)),
+        EBNF::Or(a) => EBNF::Or(expand_all(a)),
+        EBNF::Iteration(a) => {
+            EBNF::Iteration(Box::new(expand(a, inline, names)))
+        }
+        EBNF::OneOrMore(a) => {
+            EBNF::OneOrMore(Box::new(expand(a, inline, names)))
+        }
+        EBNF::Optional(a) => EBNF::Optional(Box::new(expand(a, inline, names))),
+        e => e.clone(),
+    }
+}
+
+fn uses(e: &EBNF, out: &mut Vec<Rstr>) {
+    match e {
+        EBNF::Non(n) => out.push(n.clone()),
+        EBNF::Concat(a) | EBNF::Or(a) => a.iter().for_each(|x| uses(x, out)),
+        EBNF::Iteration(a) | EBNF::OneOrMore(a) | EBNF::Optional(a) => {
+            uses(a, out)
+        }
+        EBNF::Epsilon | EBNF::Term(_) => {}
+    }
+}
+
+impl GrammarChomsky {
+    /// The grammar back in EBNF, the reverse of
+    /// [`GrammarEBNF::to_chomsky`]. Every nonterminal gets one rule with its
+    /// productions as alternatives, where tail recursion `X -> α X | β`
+    /// becomes `{ α } β`, an ε-alternative makes the others optional and
+    /// `α { α }` becomes `α +`. Nonterminals that are not rules of the EBNF
+    /// grammar, like helpers, are inlined where they are used if that is
+    /// only once.
+    pub fn to_ebnf_grammar(&self) -> GrammarEBNF {
+        let mut order: Vec<Rstr> = vec![];
+        let mut of: HashMap<Rstr, Vec<&Vec<NT>>> = HashMap::new();
+        for (l, r) in self.rules.iter() {
+            if !of.contains_key(l) {
+                order.push(l.clone());
+            }
+            of.entry(l.clone()).or_default().push(r);
+        }
+        let mut bodies: HashMap<Rstr, EBNF> =
+            order.iter().map(|n| (n.clone(), body(n, &of[n]))).collect();
+        let names: HashMap<Rstr, Rstr> = self
+            .origins
+            .iter()
+            .map(|o| (capitalize_first_letter(&o.rule), o.rule.clone()))
+            .collect();
+
+        // Merging `α { α }` into `α +` can leave the helpers in `α` used
+        // once, so this goes on until nothing is inlined.
+        loop {
+            let mut count: HashMap<Rstr, usize> = HashMap::new();
+            let mut user_of: HashMap<Rstr, Rstr> = HashMap::new();
+            for n in order.iter() {
+                let mut used = vec![];
+                uses(&bodies[n], &mut used);
+                for m in used {
+                    *count.entry(m.clone()).or_default() += 1;
+                    user_of.insert(m, n.clone());
+                }
+            }
+            let candidates: HashSet<&Rstr> = order
+                .iter()
+                .filter(|n| {
+                    !names.contains_key(*n)
+                        && **n != self.start_nonterm
+                        && count.get(*n) == Some(&1)
+                        && user_of.get(*n) != Some(*n)
+                })
+                .collect();
+            // Helpers that only use each other would be inlined forever.
+            let inline: HashMap<Rstr, EBNF> = candidates
+                .iter()
+                .filter(|n| {
+                    let mut at = **n;
+                    for _ in 0..=candidates.len() {
+                        at = &user_of[at];
+                        if !candidates.contains(at) {
+                            return true;
+                        }
+                    }
+                    false
+                })
+                .map(|n| ((*n).clone(), bodies[*n].clone()))
+                .collect();
+            if inline.is_empty() {
+                break;
+            }
+            order.retain(|n| !inline.contains_key(n));
+            for n in order.iter() {
+                let e = tidy(expand(&bodies[n], &inline, &HashMap::new()));
+                let e = tidy(repeat(n, alternatives(&e)));
+                bodies.insert(n.clone(), e);
+            }
+        }
+
+        let name = |n: &Rstr| names.get(n).unwrap_or(n).clone();
+        let mut rules = BTreeMap::new();
+        for n in order.iter() {
+            let e = expand(&bodies[n], &HashMap::new(), &names);
+            rules.insert(name(n), vec![e]);
+        }
+        let shapes = self
+            .shapes
+            .iter()
+            .filter(|(n, _)| order.contains(n))
+            .map(|(n, s)| (name(n), s.clone()))
+            .collect();
+        GrammarEBNF {
+            start_nonterm: name(&self.start_nonterm),
+            rules,
+            shapes,
+            spans: BTreeMap::new(),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use crate::grammar::{GrammarChomsky, Rstr, NT};
+
+/// A grammar symbol interned in a [`SymbolTable`]. Nonterminals and
+/// terminals are numbered separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SymbolId(u32);
+
+impl SymbolId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Maps names to dense [`SymbolId`]s and back.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    names: Vec<Rstr>,
+    ids: HashMap<Rstr, SymbolId>,
+}
+
+impl SymbolTable {
+    pub fn intern(&mut self, name: &Rstr) -> SymbolId {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = SymbolId(self.names.len() as u32);
+        self.names.push(name.clone());
+        self.ids.insert(name.clone(), id);
+        id
+    }
+
+    pub fn get(&self, name: &str) -> Option<SymbolId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: SymbolId) -> &Rstr {
+        &self.names[id.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (SymbolId, &Rstr)> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (SymbolId(i as u32), n))
+    }
+}
+
+/// A fixed size set of symbol ids.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new(len: usize) -> BitSet {
+        BitSet {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    /// Returns whether `id` was not in the set yet.
+    pub fn insert(&mut self, id: SymbolId) -> bool {
+        let (w, b) = (id.index() / 64, 1 << (id.index() % 64));
+        let new = self.words[w] & b == 0;
+        self.words[w] |= b;
+        new
+    }
+
+    pub fn contains(&self, id: SymbolId) -> bool {
+        self.words[id.index() / 64] & (1 << (id.index() % 64)) != 0
+    }
+
+    /// Returns whether anything was added.
+    pub fn union_with(&mut self, other: &BitSet) -> bool {
+        let mut changed = false;
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            changed |= *b & !*a != 0;
+            *a |= *b;
+        }
+        changed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = SymbolId> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, w)| {
+            (0..64)
+                .filter(move |b| w & (1 << b) != 0)
+                .map(move |b| SymbolId((i * 64 + b) as u32))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbol {
+    Non(SymbolId),
+    Term(SymbolId),
+    Epsilon,
+}
+
+/// The terminal standing for the end of input, `""`.
+pub const END: SymbolId = SymbolId(0);
+
+/// A [`GrammarChomsky`] with every symbol replaced by its id. The rules
+/// keep their indices.
+#[derive(Debug, Clone)]
+pub struct InternedGrammar {
+    pub nonterminals: SymbolTable,
+    /// Terminal [`END`] is `""`, the rest in order of first use.
+    pub terminals: SymbolTable,
+    pub rules: Vec<(SymbolId, Vec<Symbol>)>,
+}
+
+impl InternedGrammar {
+    pub fn new(grammar: &GrammarChomsky) -> InternedGrammar {
+        let mut nonterminals = SymbolTable::default();
+        let mut terminals = SymbolTable::default();
+        terminals.intern(&Rstr::from(""));
+        // Defined nonterminals come first, in rule order.
+        for (l, _) in grammar.rules.iter() {
+            nonterminals.intern(l);
+        }
+        let rules = grammar
+            .rules
+            .iter()
+            .map(|(l, r)| {
+                let r = r
+                    .iter()
+                    .map(|x| match x {
+                        NT::Non(n) => Symbol::Non(nonterminals.intern(n)),
+                        NT::Term(t) if t.is_empty() => Symbol::Epsilon,
+                        NT::Term(t) => Symbol::Term(terminals.intern(t)),
+                        NT::Epsilon => Symbol::Epsilon,
+                    })
+                    .collect();
+                (nonterminals.get(l).expect("interned above"), r)
+            })
+            .collect();
+        InternedGrammar {
+            nonterminals,
+            terminals,
+            rules,
+        }
+    }
+
+    pub fn name(&self, symbol: Symbol) -> &str {
+        match symbol {
+            Symbol::Non(n) => self.nonterminals.name(n),
+            Symbol::Term(t) => self.terminals.name(t),
+            Symbol::Epsilon => "ε",
+        }
+    }
+}
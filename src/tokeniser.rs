@@ -1,4 +1,4 @@
-type Rstr = std::rc::Rc<str>;
+type Rstr = std::sync::Arc<str>;
 
 #[derive(Debug)]
 pub enum Token {
@@ -16,68 +16,136 @@ pub enum Token {
     Semicolon,
     Asterix,
     Epsilon,
+    Annotation(Rstr),
 }
 
-pub fn tokenize(mut string: &str) -> Vec<Token> {
-    string = string.trim();
-    let mut ts = Vec::<Token>::new();
+/// Where something is in the grammar source, as byte offsets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// The span from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+        }
+    }
+
+    /// The line and column, both from 1, at which the span starts in
+    /// `source`.
+    pub fn line_column(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|c| *c != '\n').count();
+        (line, column + 1)
+    }
+}
+
+/// Tokens with their spans, and the spans of the comments.
+type Tokenized = (Vec<(Token, Span)>, Vec<Span>);
+
+/// The tokens of a grammar.
+///
+/// # Panics
+///
+/// If the grammar has something that is not a token, see
+/// [`try_tokenize`] for the error instead.
+pub fn tokenize(string: &str) -> Vec<Token> {
+    tokenize_with_spans(string)
+        .into_iter()
+        .map(|(t, _)| t)
+        .collect()
+}
+
+/// Like [`tokenize`], with the span of each token.
+pub fn tokenize_with_spans(source: &str) -> Vec<(Token, Span)> {
+    try_tokenize(source).unwrap_or_else(|e| panic!("{}", e)).0
+}
+
+/// The tokens of a grammar with their spans, and the spans of the comments
+/// `(* … *)`, which are otherwise skipped, or where the grammar has
+/// something that is not a token.
+pub fn try_tokenize(source: &str) -> Result<Tokenized, String> {
+    let offset = |s: &str| s.as_ptr() as usize - source.as_ptr() as usize;
+    let error = |message: &str, start: usize| {
+        let (line, column) = Span { start, end: start }.line_column(source);
+        format!("{} at {}:{}", message, line, column)
+    };
+    let mut string = source.trim();
+    let mut ts = Vec::<(Token, Span)>::new();
+    let mut comments = vec![];
     use Token::*;
     while !string.is_empty() {
-        if let Some(r) = string.strip_prefix(';') {
-            string = r.trim_start();
-            ts.push(Semicolon);
+        let start = offset(string);
+        if let Some(r) = string.strip_prefix("(*") {
+            let n = r
+                .find("*)")
+                .ok_or_else(|| error("Unterminated comment", start))?;
+            let rest = &r[n + 2..];
+            comments.push(Span {
+                start,
+                end: offset(rest),
+            });
+            string = rest.trim_start();
+            continue;
+        }
+        let (token, rest) = if let Some(r) = string.strip_prefix(';') {
+            (Semicolon, r)
         } else if let Some(r) = string.strip_prefix('ε') {
-            string = r.trim_start();
-            ts.push(Epsilon);
+            (Epsilon, r)
         } else if let Some(r) = string.strip_prefix('+') {
-            string = r.trim_start();
-            ts.push(Plus);
+            (Plus, r)
         } else if let Some(r) = string.strip_prefix('*') {
-            string = r.trim_start();
-            ts.push(Asterix);
+            (Asterix, r)
         } else if let Some(r) = string.strip_prefix('=') {
-            string = r.trim_start();
-            ts.push(Equal);
+            (Equal, r)
         } else if let Some(r) = string.strip_prefix('|') {
-            string = r.trim_start();
-            ts.push(Or);
+            (Or, r)
         } else if let Some(r) = string.strip_prefix('(') {
-            string = r.trim_start();
-            ts.push(LParen);
+            (LParen, r)
         } else if let Some(r) = string.strip_prefix(')') {
-            string = r.trim_start();
-            ts.push(RParen);
+            (RParen, r)
         } else if let Some(r) = string.strip_prefix('{') {
-            string = r.trim_start();
-            ts.push(LBrace);
+            (LBrace, r)
         } else if let Some(r) = string.strip_prefix('}') {
-            string = r.trim_start();
-            ts.push(RBrace);
+            (RBrace, r)
         } else if let Some(r) = string.strip_prefix('[') {
-            string = r.trim_start();
-            ts.push(LBracket);
+            (LBracket, r)
         } else if let Some(r) = string.strip_prefix(']') {
-            string = r.trim_start();
-            ts.push(RBracket);
+            (RBracket, r)
+        } else if let Some(r) = string.strip_prefix('@') {
+            let n = r
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(r.len());
+            let (t, r) = r.split_at(n);
+            (Annotation(t.into()), r)
         } else if let Some(r) = string.strip_prefix('"') {
-            let n = r.find('"').expect("Syntax error");
-            let (t, mut r) = r.split_at(n);
-            r = r.strip_prefix('"').expect("impossible");
-            string = r.trim_start();
-            ts.push(Terminal(t.into()));
+            let n = r
+                .find('"')
+                .ok_or_else(|| error("Unterminated terminal", start))?;
+            (Terminal(r[..n].into()), &r[n + 1..])
         } else {
             let n = string
                 .find(|c: char| !c.is_alphanumeric() && c != '_')
-                .expect("Syntax error");
+                .unwrap_or(string.len());
             if n == 0 {
-                ts.push(Epsilon);
-                string = string.trim_start();
-                continue;
+                return Err(error("Syntax error", start));
             }
             let (t, r) = string.split_at(n);
-            string = r.trim_start();
-            ts.push(Nonterminal(t.into()));
-        }
+            (Nonterminal(t.into()), r)
+        };
+        ts.push((
+            token,
+            Span {
+                start,
+                end: offset(rest),
+            },
+        ));
+        string = rest.trim_start();
     }
-    ts
+    Ok((ts, comments))
 }
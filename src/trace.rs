@@ -0,0 +1,155 @@
+use std::fmt::{Display, Write};
+
+use crate::analysis::GrammarAnalysis;
+use crate::grammar::Rstr;
+use crate::serialize::json_escape;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceAction {
+    /// Replace the nonterminal on top of the stack by production `rule`.
+    Expand {
+        nonterm: Rstr,
+        rule: usize,
+    },
+    /// Pop the terminal on top of the stack and consume it from the input.
+    Match(Rstr),
+    PopEpsilon,
+    Accept,
+    /// The parser could not continue from here.
+    Error,
+}
+
+/// The driver state before `action` was taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    /// The parse stack, top first.
+    pub stack: Vec<Rstr>,
+    /// The input that is left, starting with the current lookahead.
+    pub input: Rstr,
+    pub action: TraceAction,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    pub steps: Vec<TraceStep>,
+}
+
+impl TraceAction {
+    fn describe(&self, analysis: &GrammarAnalysis) -> String {
+        match self {
+            TraceAction::Expand { rule, .. } => {
+                format!("expand ({}) {}", rule, analysis.production(*rule))
+            }
+            TraceAction::Match(t) => format!("match {}", t),
+            TraceAction::PopEpsilon => "pop ε".into(),
+            TraceAction::Accept => "accept".into(),
+            TraceAction::Error => "error".into(),
+        }
+    }
+}
+
+impl Trace {
+    /// Renders the trace as a table with a stack, input and action column.
+    /// The bottom of the stack and the end of the input are shown as `$`.
+    pub fn table<'t>(
+        &'t self,
+        analysis: &'t GrammarAnalysis,
+    ) -> TraceTable<'t> {
+        TraceTable {
+            trace: self,
+            analysis,
+        }
+    }
+
+    pub fn to_json(&self, analysis: &GrammarAnalysis) -> String {
+        let mut out = String::from("[");
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let stack = step
+                .stack
+                .iter()
+                .map(|s| json_escape(s))
+                .collect::<Vec<_>>()
+                .join(",");
+            let _ = write!(
+                out,
+                "{{\"stack\":[{}],\"input\":{},",
+                stack,
+                json_escape(&step.input)
+            );
+            let _ = match &step.action {
+                TraceAction::Expand { nonterm, rule } => write!(
+                    out,
+                    "\"action\":\"expand\",\"nonterm\":{},\"rule\":{},\
+                     \"production\":{}}}",
+                    json_escape(nonterm),
+                    rule,
+                    json_escape(&analysis.production(*rule))
+                ),
+                TraceAction::Match(t) => write!(
+                    out,
+                    "\"action\":\"match\",\"terminal\":{}}}",
+                    json_escape(t)
+                ),
+                TraceAction::PopEpsilon => {
+                    write!(out, "\"action\":\"epsilon\"}}")
+                }
+                TraceAction::Accept => write!(out, "\"action\":\"accept\"}}"),
+                TraceAction::Error => write!(out, "\"action\":\"error\"}}"),
+            };
+        }
+        out.push(']');
+        out
+    }
+}
+
+pub struct TraceTable<'t> {
+    trace: &'t Trace,
+    analysis: &'t GrammarAnalysis,
+}
+
+impl Display for TraceTable<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rows: Vec<[String; 3]> = self
+            .trace
+            .steps
+            .iter()
+            .map(|step| {
+                let mut stack = step
+                    .stack
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>();
+                stack.push("$".into());
+                [
+                    stack.join(" "),
+                    format!("{}$", step.input),
+                    step.action.describe(self.analysis),
+                ]
+            })
+            .collect();
+        let header = ["Stack", "Input", "Action"];
+        let width = |c: usize| {
+            rows.iter()
+                .map(|r| r[c].chars().count())
+                .chain([header[c].len()])
+                .max()
+                .unwrap_or(0)
+        };
+        let (ws, wi) = (width(0), width(1));
+        writeln!(f, "{:<ws$} | {:>wi$} | {}", header[0], header[1], header[2])?;
+        writeln!(
+            f,
+            "{}-+-{}-+-{}",
+            "-".repeat(ws),
+            "-".repeat(wi),
+            "-".repeat(6)
+        )?;
+        for [stack, input, action] in rows.iter() {
+            writeln!(f, "{:<ws$} | {:>wi$} | {}", stack, input, action)?;
+        }
+        Ok(())
+    }
+}
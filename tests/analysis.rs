@@ -0,0 +1,268 @@
+//! Nullability, FIRST and FOLLOW sets and the LL(1) table.
+
+mod common;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chomchom::analysis::GrammarAnalysis;
+use chomchom::grammar::{GrammarChomsky, Rstr, NT};
+use common::{chomsky, example};
+
+const GRAMMAR: &str = r#"expr = sign term { "+" term } ;
+                         sign = [ "-" ] ;
+                         term = "(" expr ")" | "x" ;"#;
+
+fn analysis() -> GrammarAnalysis {
+    GrammarAnalysis::new(&chomsky(GRAMMAR)).unwrap()
+}
+
+fn set(items: &[&str]) -> BTreeSet<Rstr> {
+    items.iter().map(|&x| x.into()).collect()
+}
+
+#[test]
+fn nullable() {
+    let analysis = analysis();
+    let nullable: Vec<String> =
+        analysis.nullable().iter().map(|n| n.to_string()).collect();
+    assert_eq!(nullable, ["Expr__rep1", "Sign__opt1", "Sign"]);
+    assert!(analysis.is_nullable("sign"));
+    assert!(analysis.is_nullable("Sign"));
+    assert!(!analysis.is_nullable("expr"));
+    assert!(!analysis.is_nullable("nothing"));
+    assert!(analysis.rule_nullable(5));
+    assert!(!analysis.rule_nullable(2));
+}
+
+#[test]
+fn first() {
+    let analysis = analysis();
+    assert_eq!(analysis.first("expr"), set(&["(", "-", "x"]));
+    assert_eq!(analysis.first("Expr"), analysis.first("expr"));
+    assert_eq!(analysis.first("sign"), set(&["", "-"]));
+    assert_eq!(analysis.first("Expr__rep1"), set(&["", "+"]));
+    assert!(analysis.first("nothing").is_empty());
+    assert_eq!(analysis.production(2), "Expr -> Sign Term Expr__rep1");
+    assert_eq!(analysis.rule_first(2), set(&["(", "-", "x"]));
+    assert_eq!(analysis.rule_first(4), set(&[""]));
+}
+
+#[test]
+fn follow() {
+    let analysis = analysis();
+    assert_eq!(analysis.follow("expr"), set(&["", ")"]));
+    assert_eq!(analysis.follow("Expr"), analysis.follow("expr"));
+    assert_eq!(analysis.follow("sign"), set(&["(", "x"]));
+    assert_eq!(analysis.follow("term"), set(&["", ")", "+"]));
+    assert!(analysis.follow("nothing").is_empty());
+}
+
+#[test]
+fn table() {
+    let analysis = analysis();
+    let table = analysis.table();
+    assert!(table.conflicts().is_empty());
+    assert_eq!(table.get("sign", "-"), Some(5));
+    assert_eq!(table.get("Sign__opt1", "x"), Some(4));
+    assert_eq!(table.get("expr", "+"), None);
+    assert_eq!(
+        table.lookaheads("expr__rep1"),
+        ["", ")", "+"].map(Into::into)
+    );
+    assert!(table.contains("term"));
+    assert!(!table.contains("nothing"));
+}
+
+#[test]
+fn entries() {
+    let grammar = chomsky(GRAMMAR);
+    let analysis = GrammarAnalysis::with_entries(&grammar, &["term"]).unwrap();
+    // End of input only follows the entries.
+    assert_eq!(analysis.follow("term"), set(&["", ")", "+"]));
+    assert_eq!(analysis.follow("expr"), set(&[")"]));
+    assert!(GrammarAnalysis::with_entries(&grammar, &["nothing"]).is_err());
+}
+
+#[test]
+fn to_json() {
+    assert_eq!(
+        analysis().to_json(),
+        concat!(
+            r#"{"nullable":["Expr__rep1","Sign__opt1","Sign"],"#,
+            r#""first":{"Expr__rep1":["","+"],"Expr":["(","-","x"],"#,
+            r#""Sign__opt1":["","-"],"Sign":["","-"],"#,
+            r#""Term__alt1":["(","x"],"Term":["(","x"]},"#,
+            r#""productions":["#,
+            r#"{"rule":0,"production":"Expr__rep1 -> ε","first":[""],"#,
+            r#""nullable":true},"#,
+            r#"{"rule":1,"production":"Expr__rep1 -> + Term Expr__rep1","#,
+            r#""first":["+"],"nullable":false},"#,
+            r#"{"rule":2,"production":"Expr -> Sign Term Expr__rep1","#,
+            r#""first":["(","-","x"],"nullable":false},"#,
+            r#"{"rule":3,"production":"Sign__opt1 -> -","first":["-"],"#,
+            r#""nullable":false},"#,
+            r#"{"rule":4,"production":"Sign__opt1 -> ε","first":[""],"#,
+            r#""nullable":true},"#,
+            r#"{"rule":5,"production":"Sign -> Sign__opt1","#,
+            r#""first":["","-"],"nullable":true},"#,
+            r#"{"rule":6,"production":"Term__alt1 -> ( Expr )","#,
+            r#""first":["("],"nullable":false},"#,
+            r#"{"rule":7,"production":"Term__alt1 -> x","first":["x"],"#,
+            r#""nullable":false},"#,
+            r#"{"rule":8,"production":"Term -> Term__alt1","#,
+            r#""first":["(","x"],"nullable":false}],"#,
+            r#""follow":{"Expr__rep1":["",")"],"Expr":["",")"],"#,
+            r#""Sign__opt1":["(","x"],"Sign":["(","x"],"#,
+            r#""Term__alt1":["",")","+"],"Term":["",")","+"]},"#,
+            r#""table":{"Expr__rep1":{"":0,")":0,"+":1},"#,
+            r#""Expr":{"(":2,"-":2,"x":2},"#,
+            r#""Sign__opt1":{"(":4,"-":3,"x":4},"#,
+            r#""Sign":{"(":5,"-":5,"x":5},"#,
+            r#""Term__alt1":{"(":6,"x":7},"Term":{"(":8,"x":8}}}"#
+        )
+    );
+}
+
+/// FIRST, FOLLOW and the table computed the textbook way, by going over
+/// every rule until nothing changes.
+struct Naive {
+    first: BTreeMap<Rstr, BTreeSet<Rstr>>,
+    follow: BTreeMap<Rstr, BTreeSet<Rstr>>,
+    table: BTreeMap<(Rstr, Rstr), BTreeSet<usize>>,
+}
+
+impl Naive {
+    fn new(grammar: &GrammarChomsky) -> Naive {
+        let mut first: BTreeMap<Rstr, BTreeSet<Rstr>> = BTreeMap::new();
+        let mut follow: BTreeMap<Rstr, BTreeSet<Rstr>> = BTreeMap::new();
+        for (l, _) in grammar.rules.iter() {
+            first.insert(l.clone(), BTreeSet::new());
+            follow.insert(l.clone(), BTreeSet::new());
+        }
+        follow
+            .get_mut(&grammar.start_nonterm)
+            .unwrap()
+            .insert("".into());
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (l, r) in grammar.rules.iter() {
+                let f = Naive::first_of(&first, r);
+                let set = first.get_mut(l).unwrap();
+                let before = set.len();
+                set.extend(f);
+                changed |= set.len() != before;
+            }
+        }
+        changed = true;
+        while changed {
+            changed = false;
+            for (l, r) in grammar.rules.iter() {
+                for (i, x) in r.iter().enumerate() {
+                    let NT::Non(n) = x else { continue };
+                    let mut f = Naive::first_of(&first, &r[i + 1..]);
+                    if f.remove("") {
+                        f.extend(follow[l].iter().cloned());
+                    }
+                    let Some(set) = follow.get_mut(n) else {
+                        continue;
+                    };
+                    let before = set.len();
+                    set.extend(f);
+                    changed |= set.len() != before;
+                }
+            }
+        }
+        let mut table: BTreeMap<(Rstr, Rstr), BTreeSet<usize>> =
+            BTreeMap::new();
+        for (k, (l, r)) in grammar.rules.iter().enumerate() {
+            let mut f = Naive::first_of(&first, r);
+            if f.remove("") {
+                f.extend(follow[l].iter().cloned());
+            }
+            for t in f {
+                table.entry((l.clone(), t)).or_default().insert(k);
+            }
+        }
+        Naive {
+            first,
+            follow,
+            table,
+        }
+    }
+
+    /// FIRST of a sequence, with `""` if it derives ε.
+    fn first_of(
+        first: &BTreeMap<Rstr, BTreeSet<Rstr>>,
+        seq: &[NT],
+    ) -> BTreeSet<Rstr> {
+        let mut out = BTreeSet::new();
+        for x in seq.iter() {
+            match x {
+                NT::Epsilon => {}
+                NT::Term(t) if t.is_empty() => {}
+                NT::Term(t) => {
+                    out.insert(t.clone());
+                    return out;
+                }
+                NT::Non(n) => {
+                    let f = first.get(n).cloned().unwrap_or_default();
+                    let nullable = f.contains("");
+                    out.extend(f.into_iter().filter(|t| !t.is_empty()));
+                    if !nullable {
+                        return out;
+                    }
+                }
+            }
+        }
+        out.insert("".into());
+        out
+    }
+}
+
+fn check_naive(name: &str, grammar: &GrammarChomsky) {
+    let analysis = GrammarAnalysis::new(grammar).unwrap();
+    let naive = Naive::new(grammar);
+    for (n, first) in naive.first.iter() {
+        assert_eq!(&analysis.first(n), first, "{}: FIRST({})", name, n);
+        assert_eq!(analysis.is_nullable(n), first.contains(""));
+    }
+    for (n, follow) in naive.follow.iter() {
+        assert_eq!(&analysis.follow(n), follow, "{}: FOLLOW({})", name, n);
+    }
+    let mut table: BTreeMap<(Rstr, Rstr), BTreeSet<usize>> = BTreeMap::new();
+    for n in analysis.table().nonterminals() {
+        for t in analysis.table().lookaheads(n) {
+            let rules = analysis.table().rules(n, &t);
+            table.insert((n.clone(), t), rules.into_iter().collect());
+        }
+    }
+    assert_eq!(table, naive.table, "{}", name);
+}
+
+#[test]
+fn same_as_naive() {
+    for name in ["idk", "mila", "long", "test"] {
+        check_naive(name, &example(name));
+    }
+}
+
+/// The chains `benches/analysis.rs` times, shorter.
+#[test]
+fn chains_same_as_naive() {
+    let mut chain = String::new();
+    let mut nullable_chain = String::new();
+    for i in 0..100 {
+        chain.push_str(&format!("N{} = N{} ;\n", i, i + 1));
+        nullable_chain.push_str(&format!(
+            "N{} = [ N{} ] \"x{}\" ;\n",
+            i,
+            i + 1,
+            i
+        ));
+    }
+    chain.push_str("N100 = \"a\" ;\n");
+    nullable_chain.push_str("N100 = \"a\" ;\n");
+    check_naive("chain", &chomsky(&chain));
+    check_naive("nullable chain", &chomsky(&nullable_chain));
+}
@@ -0,0 +1,79 @@
+//! Fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use chomchom::grammar::{GrammarChomsky, GrammarEBNF};
+use chomchom::parser;
+use chomchom::parser_generator::{Token, TokenIteratorType};
+
+pub fn ebnf(source: &str) -> GrammarEBNF {
+    parser::parse_source(source).expect("Failed to parse grammar")
+}
+
+pub fn chomsky(source: &str) -> GrammarChomsky {
+    ebnf(source).to_chomsky()
+}
+
+pub fn example_source(name: &str) -> String {
+    std::fs::read_to_string(format!("examples/{}.gram", name))
+        .expect("Failed to read grammar.")
+}
+
+pub fn example(name: &str) -> GrammarChomsky {
+    chomsky(&example_source(name))
+}
+
+pub fn tokens<S: AsRef<str>>(sentence: &[S]) -> Vec<Token> {
+    sentence
+        .iter()
+        .map(|t| Token::from(t.as_ref().to_string()))
+        .collect()
+}
+
+/// Splits the input at spaces, for inputs that are sentences joined by
+/// `" "`.
+pub fn split_tokens(input: &str) -> TokenIteratorType<'_, Token> {
+    Box::new(
+        input
+            .split(' ')
+            .filter(|t| !t.is_empty())
+            .map(|t| Token::from(t.to_string())),
+    )
+}
+
+/// Every string of at most `max` terminals of the grammar, shortest first.
+pub fn sentences(grammar: &GrammarChomsky, max: usize) -> Vec<Vec<String>> {
+    let mut terminals: Vec<String> = grammar
+        .terminals
+        .iter()
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_string())
+        .collect();
+    terminals.sort();
+    let mut all = vec![vec![]];
+    let mut last: Vec<Vec<String>> = vec![vec![]];
+    for _ in 0..max {
+        let mut next = vec![];
+        for s in last.iter() {
+            for t in terminals.iter() {
+                let mut s = s.clone();
+                s.push(t.clone());
+                next.push(s);
+            }
+        }
+        all.extend(next.iter().cloned());
+        last = next;
+    }
+    all
+}
+
+/// Compares `exported` with `tests/golden/{file}`. Run with `UPDATE_GOLDEN=1`
+/// to rewrite the file after an intended change.
+pub fn check_golden(file: &str, exported: &str) {
+    let path = format!("tests/golden/{}", file);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, exported).expect("Failed to write golden");
+        return;
+    }
+    let golden = std::fs::read_to_string(&path).expect("Missing golden file");
+    assert_eq!(exported, golden, "{} differs", path);
+}
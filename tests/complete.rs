@@ -0,0 +1,135 @@
+//! Completing a prefix of a sentence.
+
+mod common;
+
+use chomchom::grammar::Rstr;
+use chomchom::parser_generator::{
+    Completion, Parser, TokenIteratorType, TokenReq, TokenTrait,
+};
+use common::chomsky;
+
+/// A word of the input, as a lexer that skips spaces would give it.
+struct Word {
+    text: Rstr,
+    str_pos: usize,
+    eof: bool,
+}
+
+impl TokenTrait for Word {
+    fn as_str(&self) -> Rstr {
+        self.text.clone()
+    }
+    fn column(&self) -> usize {
+        self.str_pos
+    }
+    fn line(&self) -> usize {
+        0
+    }
+    fn str_pos(&self) -> usize {
+        self.str_pos
+    }
+    fn make_eof(_: usize, _: usize, str_pos: usize) -> Self {
+        Word {
+            text: "".into(),
+            str_pos,
+            eof: true,
+        }
+    }
+    fn actual_string(&self) -> Rstr {
+        self.text.clone()
+    }
+    fn is_eof(&self) -> bool {
+        self.eof
+    }
+}
+
+impl TokenReq for Word {}
+
+fn words(input: &str) -> TokenIteratorType<'_, Word> {
+    let words = input.split(' ').scan(0, |pos, w| {
+        let word = Word {
+            text: w.into(),
+            str_pos: *pos,
+            eof: false,
+        };
+        *pos += w.len() + 1;
+        Some(word)
+    });
+    Box::new(words.filter(|w| !w.text.is_empty()))
+}
+
+fn parser() -> Parser {
+    Parser::new(chomsky(
+        r#"stmt = "let" name "=" value [ ";" ] ;
+           name = "a" | "b" ;
+           value = "1" | "(" value ")" ;"#,
+    ))
+    .expect("Failed to build parser")
+}
+
+fn complete(input: &str) -> Completion {
+    parser().complete_tokens(input, words(input))
+}
+
+fn expected(completion: &Completion) -> Vec<&str> {
+    completion.expected.iter().map(|t| t.as_ref()).collect()
+}
+
+#[test]
+fn empty_prefix() {
+    let completion = complete("");
+    assert!(completion.viable);
+    assert!(!completion.can_end);
+    assert_eq!(expected(&completion), ["let"]);
+    assert_eq!(completion.rest.as_ref(), "");
+}
+
+#[test]
+fn mid_rule() {
+    let completion = complete("let");
+    assert!(completion.viable);
+    assert!(!completion.can_end);
+    assert_eq!(expected(&completion), ["a", "b"]);
+    assert_eq!(expected(&complete("let a")), ["="]);
+    assert_eq!(expected(&complete("let a = (")), ["(", "1"]);
+    assert_eq!(expected(&complete("let a = ( 1")), [")"]);
+}
+
+#[test]
+fn nullable_tail() {
+    let completion = complete("let a = 1");
+    assert!(completion.viable);
+    assert!(completion.can_end);
+    assert_eq!(expected(&completion), [";"]);
+    let completion = complete("let a = ( 1 )");
+    assert!(completion.can_end);
+    assert_eq!(expected(&completion), [";"]);
+    let completion = complete("let a = 1 ;");
+    assert!(completion.viable);
+    assert!(completion.can_end);
+    assert!(completion.expected.is_empty());
+}
+
+#[test]
+fn invalid_prefix() {
+    for input in ["=", "let =", "let a = 1 ; ;", "let a = )", "let c"] {
+        let completion = complete(input);
+        assert!(!completion.viable, "{}", input);
+        assert!(!completion.can_end, "{}", input);
+        assert!(completion.expected.is_empty(), "{}", input);
+    }
+}
+
+#[test]
+fn partial_token() {
+    let parser = parser();
+    let completion = parser.complete("le");
+    assert!(completion.viable);
+    assert!(!completion.can_end);
+    assert_eq!(completion.rest.as_ref(), "le");
+    let completion = parser.complete("leta=");
+    assert!(completion.viable);
+    assert_eq!(completion.rest.as_ref(), "");
+    assert_eq!(expected(&completion), ["(", "1"]);
+    assert!(!parser.complete("lex").viable);
+}
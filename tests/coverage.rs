@@ -0,0 +1,121 @@
+//! The coverage suite uses every production, and every LL(1) table cell if
+//! asked to, with sentences the generated parser accepts.
+
+use std::collections::BTreeSet;
+
+mod common;
+
+use chomchom::analysis::GrammarAnalysis;
+use chomchom::coverage::{Coverage, Goal, Suite};
+use chomchom::grammar::Rstr;
+use common::{chomsky, example, split_tokens};
+
+/// Checks that `get_parser` accepts the suite and, if the table has no
+/// conflicts, that it covers everything.
+fn check(name: &str, cells: bool) -> Suite {
+    let file = format!("examples/{}.gram", name);
+    let grammar = example(name);
+    let analysis = GrammarAnalysis::new(&grammar).unwrap();
+    let mut coverage = Coverage::new(&grammar).with_table(analysis.table());
+    if cells {
+        coverage = coverage.with_cells();
+    }
+    let suite = coverage.suite();
+    let inputs: Vec<String> =
+        suite.cases.iter().map(|c| c.sentence.join(" ")).collect();
+    let parse = chomchom::get_parser(&file, split_tokens);
+    for input in inputs.iter() {
+        assert!(parse(input).is_ok(), "{}", input);
+    }
+    if !analysis.table().conflicts().is_empty() {
+        assert!(!suite.cases.is_empty());
+        return suite;
+    }
+    assert!(suite.missed.is_empty(), "{:?}", suite.missed);
+
+    let covered: BTreeSet<&Goal> =
+        suite.cases.iter().flat_map(|c| c.covers.iter()).collect();
+    for rule in 0..grammar.rules.len() {
+        assert!(covered.contains(&Goal::Production(rule)), "{}", rule);
+    }
+    if cells {
+        for n in analysis.table().nonterminals() {
+            for t in analysis.table().lookaheads(n) {
+                let cell = Goal::Cell(n.clone(), t);
+                assert!(covered.contains(&cell), "{}", cell);
+            }
+        }
+    }
+    suite
+}
+
+#[test]
+fn every_production() {
+    for name in ["idk", "mila", "long"] {
+        check(name, false);
+    }
+    let grammar = example("idk");
+    assert!(check("idk", false).cases.len() < grammar.rules.len());
+}
+
+#[test]
+fn every_cell() {
+    for name in ["idk", "mila", "test"] {
+        check(name, true);
+    }
+}
+
+#[test]
+fn conflicts() {
+    // The first `{ ahoj }` takes every "ahoj", so the parser never uses the
+    // second one.
+    let grammar = example("test");
+    let suite = check("test", false);
+    let missed: Vec<&Rstr> = suite
+        .missed
+        .iter()
+        .map(|g| match g {
+            Goal::Production(rule) => &grammar.rules[*rule].0,
+            Goal::Cell(n, _) => n,
+        })
+        .collect();
+    assert_eq!(missed, vec![&Rstr::from("A__rep3")]);
+}
+
+#[test]
+fn shortest_sentences() {
+    let grammar = chomsky(
+        r#"s = "(" s ")" s | list ;
+           list = item { "," item } | ε ;
+           item = "x" | "[" list "]" ;"#,
+    );
+    let analysis = GrammarAnalysis::new(&grammar).unwrap();
+    let coverage = Coverage::new(&grammar).with_table(analysis.table());
+    let sentences = |suite: Suite| -> Vec<String> {
+        assert!(suite.missed.is_empty(), "{:?}", suite.missed);
+        suite.cases.iter().map(|c| c.sentence.join(" ")).collect()
+    };
+    assert_eq!(sentences(coverage.suite()), vec!["[ ]", "x , x", "( )"]);
+    assert_eq!(
+        sentences(coverage.with_cells().suite()),
+        vec!["( x )", "[ ]", "( )", "x , x", "[ x ]"]
+    );
+}
+
+#[test]
+fn missed() {
+    let grammar = chomsky(r#"a = "x" | "y" c ; c = "z" c ; d = "w" ;"#);
+    let suite = Coverage::new(&grammar).suite();
+    let sentences: Vec<String> =
+        suite.cases.iter().map(|c| c.sentence.join(" ")).collect();
+    assert_eq!(sentences, vec!["x"]);
+    let missed: Vec<&str> = suite
+        .missed
+        .iter()
+        .map(|g| match g {
+            Goal::Production(rule) => grammar.rules[*rule].0.as_ref(),
+            Goal::Cell(..) => panic!("{}", g),
+        })
+        .collect();
+    assert_eq!(missed, vec!["A__alt1", "C", "D"]);
+}
@@ -0,0 +1,73 @@
+//! The concrete syntax tree gives back the parsed input byte for byte,
+//! trivia included.
+
+mod common;
+
+use chomchom::cst::{TriviaConfig, TriviaKind};
+use chomchom::parser_generator::{Parser, AST};
+use common::chomsky;
+
+fn parser() -> Parser {
+    Parser::new(chomsky(r#"list = { item } ; item = "a" | "b" "c" ;"#))
+        .expect("Failed to build parser")
+}
+
+fn trivia() -> TriviaConfig {
+    TriviaConfig::default()
+        .with_line_comment("//")
+        .with_block_comment("/*", "*/")
+}
+
+fn round_trip(input: &str) {
+    let cst = parser()
+        .parse_cst(input, &trivia())
+        .expect("Failed to parse");
+    assert_eq!(cst.to_source(), input);
+}
+
+#[test]
+fn leading_inner_and_trailing_trivia() {
+    round_trip("a");
+    round_trip("  \n// first\na");
+    round_trip("a /* between */ b\n\tc  a");
+    round_trip("a b c  // last\n  ");
+    round_trip("/* all */ a /**/b// of\nc /* it */");
+}
+
+#[test]
+fn empty() {
+    round_trip("");
+    round_trip("   ");
+    round_trip("// nothing\n");
+}
+
+#[test]
+fn trivia_on_tokens() {
+    let cst = parser().parse_cst(" a // x", &trivia()).unwrap();
+    let leaves: Vec<_> = cst.preorder().filter(|n| n.is_token()).collect();
+    let [AST::Token(a)] = leaves.as_slice() else {
+        panic!("{:?}", leaves);
+    };
+    assert_eq!(a.text(), "a");
+    assert_eq!(a.leading[0].kind, TriviaKind::Whitespace);
+    let trailing: Vec<&str> =
+        a.trailing.iter().map(|t| t.text.as_ref()).collect();
+    assert_eq!(trailing, [" ", "// x"]);
+}
+
+#[test]
+fn not_lossless() {
+    assert!(parser().parse_cst("a ?", &trivia()).is_err());
+    assert!(parser().parse_cst("b a", &trivia()).is_err());
+    let no_comments = TriviaConfig::default();
+    assert!(parser().parse_cst("a // x", &no_comments).is_err());
+}
+
+#[test]
+fn eof_keyword() {
+    let parser = Parser::new(chomsky(r#"s = "EOF" "x" | "y" ;"#)).unwrap();
+    assert!(parser.parse("EOFx").is_ok());
+    assert!(parser.parse("EOF").is_err());
+    let cst = parser.parse_cst("EOF x", &TriviaConfig::default()).unwrap();
+    assert_eq!(cst.to_source(), "EOF x");
+}
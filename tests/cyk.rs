@@ -0,0 +1,75 @@
+//! The CYK parser as an oracle: on LL(1) grammars it accepts exactly what
+//! the LL(1) parser accepts, for every token string up to a length.
+
+mod common;
+
+use chomchom::cyk::Cyk;
+use chomchom::parser_generator::{Parser, Token, TokenTrait};
+use common::{chomsky, example_source, sentences, tokens};
+
+fn check(source: &str, max: usize) {
+    let grammar = chomsky(source);
+    let parser = Parser::new(grammar.clone()).expect("Failed to build parser");
+    assert!(parser.table().conflicts().is_empty(), "not LL(1)");
+    let cyk = Cyk::new(grammar.to_cnf());
+    let start = grammar.start_nonterm.clone();
+    let mut accepted = 0;
+    for sentence in sentences(&grammar, max) {
+        let mut input = tokens(&sentence);
+        input.push(Token::make_eof(0, 0, 0));
+        let ll = parser
+            .parse_tokens(&start, "", Box::new(input.into_iter()))
+            .is_ok();
+        let oracle = cyk.recognize(&tokens(&sentence));
+        assert_eq!(ll, oracle, "{:?}", sentence);
+        assert_eq!(oracle, cyk.parse(tokens(&sentence)).is_ok());
+        accepted += oracle as usize;
+    }
+    assert!(accepted > 0);
+}
+
+#[test]
+fn idk_agrees_with_ll1() {
+    check(&example_source("idk"), 4);
+}
+
+#[test]
+fn lists_agree_with_ll1() {
+    check(r#"value = "n" | "[" [ value { "," value } ] "]" ;"#, 6);
+}
+
+#[test]
+fn nullable_agrees_with_ll1() {
+    check(r#"s = [ "a" ] { "b" s "c" } [ "d" { "d" } ] ;"#, 6);
+}
+
+#[test]
+fn ambiguous() {
+    let grammar = chomsky(r#"e = e "+" e | "1" | "(" e ")" ;"#);
+    let cyk = Cyk::new(grammar.to_cnf());
+    assert!(cyk.recognize(&tokens(&["1", "+", "1", "+", "(", "1", ")"])));
+    assert!(!cyk.recognize(&tokens(&["1", "+"])));
+    assert!(!cyk.recognize(&tokens::<&str>(&[])));
+    let tree = cyk.parse(tokens(&["1", "+", "1"])).unwrap();
+    assert_eq!(
+        tree.to_sexpr(),
+        r#"(chomchom_root (E (E "1") "+" (E "1")))"#
+    );
+}
+
+#[test]
+fn left_out_start() {
+    let grammar = chomsky(r#"_s = "(" _s ")" | "[" x "]" ; x = "x" ;"#);
+    let cyk = Cyk::new(grammar.to_cnf());
+    let tree = cyk.parse(tokens(&["(", "[", "x", "]", ")"])).unwrap();
+    assert_eq!(
+        tree.to_sexpr(),
+        r#"(chomchom_root "(" "[" (X "x") "]" ")")"#
+    );
+    let parser = Parser::new(grammar).expect("Failed to build parser");
+    assert_eq!(parser.parse("([x])").unwrap().to_sexpr(), tree.to_sexpr());
+
+    let cyk = Cyk::new(chomsky(r#"_s = [ "x" ] ;"#).to_cnf());
+    let tree = cyk.parse(tokens::<&str>(&[])).unwrap();
+    assert_eq!(tree.to_sexpr(), "(chomchom_root)");
+}
@@ -0,0 +1,83 @@
+//! The leftmost derivation the parser goes through, from whichever entry it
+//! starts at.
+
+mod common;
+
+use chomchom::derivation::Derivation;
+use chomchom::grammar::{GrammarChomsky, NT};
+use chomchom::parser_generator::Parser;
+use common::chomsky;
+
+const GRAMMAR: &str = r#"sum = term { "+" term } ;
+                         term = "1" | "(" sum ")" ;"#;
+
+fn show(form: &[NT]) -> String {
+    form.iter()
+        .map(|nt| match nt {
+            NT::Epsilon => "ε".to_string(),
+            NT::Non(n) => n.to_string(),
+            NT::Term(t) => format!("\"{}\"", t),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Checks that every step expands the leftmost nonterminal of the form
+/// before it, and returns the forms.
+fn checked_forms(
+    derivation: &Derivation,
+    grammar: &GrammarChomsky,
+) -> Vec<String> {
+    let forms = derivation.sentential_forms(grammar);
+    assert_eq!(forms.len(), derivation.steps.len() + 1);
+    for (form, &step) in forms.iter().zip(derivation.steps.iter()) {
+        let leftmost = form.iter().find_map(|x| match x {
+            NT::Non(n) => Some(n),
+            _ => None,
+        });
+        assert_eq!(leftmost, Some(&grammar.rules[step].0));
+    }
+    forms.iter().map(|f| show(f)).collect()
+}
+
+#[test]
+fn from_start() {
+    let parser = Parser::new(chomsky(GRAMMAR)).unwrap();
+    let (_, derivation) = parser.derive("1+1").unwrap();
+    let forms = checked_forms(&derivation, parser.grammar());
+    assert_eq!(forms.first().unwrap(), "Sum");
+    assert_eq!(forms.last().unwrap(), r#""1" "+" "1""#);
+    let origins: Vec<&str> = derivation
+        .origins(parser.grammar())
+        .iter()
+        .map(|o| o.rule.as_ref())
+        .collect();
+    assert_eq!(origins.first(), Some(&"sum"));
+    assert!(origins.iter().all(|o| ["sum", "term"].contains(o)));
+}
+
+#[test]
+fn from_other_entry() {
+    let parser = Parser::with_entries(chomsky(GRAMMAR), &["term"]).unwrap();
+    let (_, derivation) = parser.derive("(1)").unwrap();
+    let forms = checked_forms(&derivation, parser.grammar());
+    assert_eq!(forms[0], "Term");
+    assert_eq!(forms[1], "Term__alt1");
+    assert_eq!(forms[2], r#""(" Sum ")""#);
+    assert_eq!(forms.last().unwrap(), r#""(" "1" ")""#);
+    let shown = derivation.display(parser.grammar()).to_string();
+    assert_eq!(shown.lines().count(), forms.len());
+    assert!(shown.starts_with(&forms[0]), "{}", shown);
+}
+
+#[test]
+fn empty_sentence() {
+    let parser = Parser::new(chomsky(r#"list = { "x" } ;"#)).unwrap();
+    let (_, derivation) = parser.derive("").unwrap();
+    let forms = checked_forms(&derivation, parser.grammar());
+    assert_eq!(forms.first().unwrap(), "List");
+    assert_eq!(forms.last().unwrap(), "");
+    let (_, derivation) = parser.derive("xx").unwrap();
+    let forms = checked_forms(&derivation, parser.grammar());
+    assert_eq!(forms.last().unwrap(), r#""x" "x""#);
+}
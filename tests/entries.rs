@@ -0,0 +1,80 @@
+//! A parser with several entry points starts from the first one unless told
+//! otherwise, whichever way it is driven.
+
+mod common;
+
+use chomchom::cst::TriviaConfig;
+use chomchom::parser_generator::Parser;
+use common::chomsky;
+
+const GRAMMAR: &str = r#"program = "begin" expr "end" ;
+                         expr = term { "+" term } ;
+                         term = "1" | "(" expr ")" ;"#;
+
+fn parser(entries: &[&str]) -> Parser {
+    Parser::with_entries(chomsky(GRAMMAR), entries)
+        .expect("Failed to build parser")
+}
+
+#[test]
+fn first_entry() {
+    let parser = parser(&["expr"]);
+    assert_eq!(
+        parser.parse("1+1").unwrap().to_sexpr(),
+        r#"(chomchom_root (Expr (Term "1") "+" (Term "1")))"#
+    );
+    assert!(parser.parse("begin1end").is_err());
+    assert!(parser.parse_from("program", "begin1end").is_err());
+}
+
+#[test]
+fn several_entries() {
+    let parser = parser(&["program", "expr"]);
+    assert!(parser.parse("begin1+(1)end").is_ok());
+    assert!(parser.parse("1+1").is_err());
+    assert!(parser.parse_from("program", "begin1end").is_ok());
+    assert!(parser.parse_from("expr", "1+(1)").is_ok());
+    assert!(parser.parse_from("Expr", "1").is_ok());
+    let err = parser.parse_from("term", "1").unwrap_err();
+    assert_eq!(err.to_string(), "Term is not an entry point");
+}
+
+#[test]
+fn same_input_from_two_entries() {
+    let parser = parser(&["expr", "term"]);
+    assert_eq!(
+        parser.parse_from("expr", "(1)").unwrap().to_sexpr(),
+        r#"(chomchom_root (Expr (Term "(" (Expr (Term "1")) ")")))"#
+    );
+    assert_eq!(
+        parser.parse_from("term", "(1)").unwrap().to_sexpr(),
+        r#"(chomchom_root (Term "(" (Expr (Term "1")) ")"))"#
+    );
+}
+
+#[test]
+fn every_driver() {
+    let parser = parser(&["expr"]);
+    let (ast, derivation) = parser.derive("1+1").unwrap();
+    assert_eq!(ast.to_sexpr(), parser.parse("1+1").unwrap().to_sexpr());
+    assert!(!derivation.steps.is_empty());
+    let (traced, _) = parser.trace("(1)");
+    assert!(traced.is_ok());
+    let cst = parser.parse_cst("1 + 1", &TriviaConfig::default()).unwrap();
+    assert_eq!(cst.to_source(), "1 + 1");
+
+    let completion = parser.complete("1+");
+    assert!(completion.viable);
+    assert!(!completion.can_end);
+    let expected: Vec<&str> =
+        completion.expected.iter().map(|t| t.as_ref()).collect();
+    assert_eq!(expected, ["(", "1"]);
+    assert!(parser.complete("1").can_end);
+    assert!(!parser.complete("begin").viable);
+}
+
+#[test]
+fn invalid_entries() {
+    assert!(Parser::with_entries(chomsky(GRAMMAR), &[]).is_err());
+    assert!(Parser::with_entries(chomsky(GRAMMAR), &["nope"]).is_err());
+}
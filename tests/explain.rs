@@ -0,0 +1,140 @@
+//! The iterations explaining FIRST and FOLLOW end with the sets the
+//! analysis computes.
+
+mod common;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chomchom::analysis::GrammarAnalysis;
+use chomchom::explain::{Addition, Reason, SetKind};
+use chomchom::grammar::Rstr;
+use common::{chomsky, example};
+
+/// The sets after all `iterations`.
+fn sets(iterations: &[Vec<Addition>]) -> BTreeMap<Rstr, BTreeSet<Rstr>> {
+    let mut sets: BTreeMap<Rstr, BTreeSet<Rstr>> = BTreeMap::new();
+    for a in iterations.iter().flatten() {
+        let new = sets
+            .entry(a.nonterm.clone())
+            .or_default()
+            .insert(a.element.clone());
+        assert!(new, "{:?} added twice", a);
+    }
+    sets
+}
+
+fn check(name: &str, analysis: &GrammarAnalysis) {
+    let explanation = analysis.explain();
+    assert!(explanation.first.last().unwrap().is_empty());
+    assert!(explanation.follow.last().unwrap().is_empty());
+    assert!(explanation
+        .first
+        .iter()
+        .flatten()
+        .all(|a| a.set == SetKind::First));
+    assert!(explanation
+        .follow
+        .iter()
+        .flatten()
+        .all(|a| a.set == SetKind::Follow));
+    let first = sets(&explanation.first);
+    let follow = sets(&explanation.follow);
+    for n in analysis.nonterminals() {
+        let explained = first.get(n).cloned().unwrap_or_default();
+        assert_eq!(explained, analysis.first(n), "{}: FIRST({})", name, n);
+        let explained = follow.get(n).cloned().unwrap_or_default();
+        assert_eq!(explained, analysis.follow(n), "{}: FOLLOW({})", name, n);
+    }
+}
+
+#[test]
+fn same_as_analysis() {
+    for name in ["idk", "mila", "long", "test"] {
+        check(name, &GrammarAnalysis::new(&example(name)).unwrap());
+    }
+    let grammar = example("mila");
+    let entries = ["program", "expression", "block"];
+    let analysis = GrammarAnalysis::with_entries(&grammar, &entries).unwrap();
+    check("mila", &analysis);
+}
+
+#[test]
+fn reasons() {
+    let grammar = chomsky(r#"s = a "x" ; a = [ "y" ] ;"#);
+    let explanation = GrammarAnalysis::new(&grammar).unwrap().explain();
+    let show = |iterations: &[Vec<Addition>]| -> Vec<Vec<String>> {
+        iterations
+            .iter()
+            .map(|i| {
+                i.iter()
+                    .map(|a| format!("{} {:?}", a.nonterm, a.element))
+                    .collect()
+            })
+            .collect()
+    };
+    assert_eq!(
+        show(&explanation.first),
+        [
+            vec![
+                r#"A__opt1 "y""#,
+                r#"A__opt1 """#,
+                r#"A "y""#,
+                r#"A """#,
+                r#"S "y""#,
+                r#"S "x""#
+            ],
+            vec![]
+        ]
+    );
+    let reasons: Vec<Reason> =
+        explanation.first[0].iter().map(|a| a.reason).collect();
+    assert_eq!(
+        reasons,
+        [
+            Reason::Terminal {
+                rule: 0,
+                position: 0
+            },
+            Reason::Nullable { rule: 1 },
+            Reason::First {
+                rule: 2,
+                position: 0
+            },
+            Reason::Nullable { rule: 2 },
+            Reason::First {
+                rule: 3,
+                position: 0
+            },
+            Reason::Terminal {
+                rule: 3,
+                position: 1
+            },
+        ]
+    );
+    // FOLLOW of `A__opt1` comes from FOLLOW of `A`, which only has `"x"`
+    // after the first iteration.
+    assert_eq!(
+        show(&explanation.follow),
+        [vec![r#"S """#, r#"A "x""#], vec![r#"A__opt1 "x""#], vec![]]
+    );
+    let reasons: Vec<Reason> = explanation
+        .follow
+        .iter()
+        .flatten()
+        .map(|a| a.reason)
+        .collect();
+    assert_eq!(
+        reasons,
+        [
+            Reason::Entry,
+            Reason::FollowFirst {
+                rule: 3,
+                position: 0
+            },
+            Reason::FollowFollow {
+                rule: 2,
+                position: 0
+            }
+        ]
+    );
+}
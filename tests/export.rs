@@ -0,0 +1,119 @@
+//! Exporting the LL(1) table escapes what each format needs escaped and
+//! marks conflicting cells.
+
+mod common;
+
+use chomchom::analysis::GrammarAnalysis;
+use chomchom::grammar::{GrammarChomsky, NT};
+use common::chomsky;
+
+/// `S` has a conflict on `"` between `S -> " ,` and `S -> " |`; there is
+/// no way to write a `"` terminal in a grammar, so it is swapped in.
+fn grammar() -> GrammarChomsky {
+    let mut grammar = chomsky(r#"s = "q" "," | "q" "|" | "&" "<" ;"#);
+    for (_, r) in grammar.rules.iter_mut() {
+        for x in r.iter_mut() {
+            if *x == NT::Term("q".into()) {
+                *x = NT::Term("\"".into());
+            }
+        }
+    }
+    grammar.terminals.remove("q");
+    grammar.terminals.insert("\"".into());
+    grammar
+}
+
+fn analysis() -> GrammarAnalysis {
+    GrammarAnalysis::new(&grammar()).unwrap()
+}
+
+#[test]
+fn csv() {
+    let analysis = analysis();
+    assert_eq!(
+        analysis.table().to_csv(&analysis),
+        r#","""",&,",",<,|,$
+S__alt1,"CONFLICT: S__alt1 -> "" , / S__alt1 -> "" |",S__alt1 -> & <,,,,
+S,S -> S__alt1,S -> S__alt1,,,,
+"#
+    );
+}
+
+#[test]
+fn markdown() {
+    let analysis = analysis();
+    let markdown = analysis.table().to_markdown(&analysis);
+    let lines: Vec<&str> = markdown.lines().collect();
+    assert_eq!(lines[0], r#"| | " | & | , | \< | \| | $ |"#);
+    assert_eq!(lines[1], "|---|---|---|---|---|---|---|");
+    assert_eq!(
+        lines[2],
+        concat!(
+            r#"| S\_\_alt1 | **⚠ S\_\_alt1 -\> " ,<br>S\_\_alt1 -\> " \|** "#,
+            r#"| S\_\_alt1 -\> & \< |  |  |  |  |"#
+        )
+    );
+    assert_eq!(
+        lines[3],
+        r"| S | S -\> S\_\_alt1 | S -\> S\_\_alt1 |  |  |  |  |"
+    );
+}
+
+#[test]
+fn html() {
+    let analysis = analysis();
+    let html = analysis.table().to_html(&analysis);
+    assert!(html.starts_with("<!DOCTYPE html>\n"));
+    assert!(html.ends_with("</table>\n</body>\n</html>\n"));
+    assert!(html.contains(concat!(
+        "<tr><th></th><th>&quot;</th><th>&amp;</th><th>,</th>",
+        "<th>&lt;</th><th>|</th><th>$</th></tr>\n"
+    )));
+    assert!(html.contains(concat!(
+        "<tr><th>S__alt1</th><td class=\"conflict\">",
+        "S__alt1 -&gt; &quot; ,<br>S__alt1 -&gt; &quot; |</td>",
+        "<td>S__alt1 -&gt; &amp; &lt;</td>",
+        "<td></td><td></td><td></td><td></td></tr>\n"
+    )));
+}
+
+#[test]
+fn latex() {
+    let analysis = analysis();
+    assert_eq!(
+        analysis.table().to_latex(&analysis),
+        concat!(
+            "\\begin{tabular}{l|llllll}\n",
+            r#" & \texttt{"} & \texttt{\&} & \texttt{,} & \texttt{<} "#,
+            "& \\texttt{|} & \\texttt{\\$} \\\\\n",
+            "\\hline\n",
+            r#"S\_\_alt1 & \textcolor{red}{\texttt{S\_\_alt1 -> " ,} / "#,
+            r#"\texttt{S\_\_alt1 -> " |}} & \texttt{S\_\_alt1 -> \& <} "#,
+            "&  &  &  &  \\\\\n",
+            r"S & \texttt{S -> S\_\_alt1} & \texttt{S -> S\_\_alt1} ",
+            "&  &  &  &  \\\\\n",
+            "\\end{tabular}\n"
+        )
+    );
+}
+
+#[test]
+fn epsilon() {
+    let grammar = chomsky(r#"s = [ "x" ] ;"#);
+    let analysis = GrammarAnalysis::new(&grammar).unwrap();
+    assert!(analysis.table().to_csv(&analysis).contains("S__opt1 -> ε"));
+    assert!(analysis
+        .table()
+        .to_latex(&analysis)
+        .contains(r"\texttt{S\_\_opt1 -> $\varepsilon$}"));
+}
+
+/// Only nonterminals with rules get a row, even if others are used.
+#[test]
+fn undefined_nonterminal() {
+    let mut grammar = chomsky(r#"s = "x" t ; t = "y" ;"#);
+    grammar.rules.retain(|(l, _)| l.as_ref() != "T");
+    let analysis = GrammarAnalysis::new(&grammar).unwrap();
+    assert!(analysis.nonterminals().any(|n| n.as_ref() == "T"));
+    assert_eq!(analysis.table().to_csv(&analysis), ",x,$\nS,S -> x T,\n");
+}
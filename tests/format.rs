@@ -0,0 +1,117 @@
+//! The grammar formatter keeps the meaning and the comments of a grammar
+//! and formatting twice changes nothing.
+
+mod common;
+
+use chomchom::format::format_grammar;
+use chomchom::{parser, tokeniser};
+use common::example_source;
+
+fn check(source: &str, width: usize) -> String {
+    let formatted = format_grammar(source, width).expect("Failed to format");
+    let before = parser::parse_source(source).unwrap();
+    let after = parser::parse_source(&formatted).unwrap();
+    assert_eq!(after.rules, before.rules);
+    assert_eq!(after.shapes, before.shapes);
+    assert_eq!(format_grammar(&formatted, width).unwrap(), formatted);
+    formatted
+}
+
+#[test]
+fn examples() {
+    for name in ["idk", "mila", "test", "long"] {
+        check(&example_source(name), 80);
+    }
+}
+
+#[test]
+fn parentheses() {
+    let source = r#"x = ( ( "a" | "b" ) ) + | ( "c" + ) + | ( "d" "e" ) "f" | [ ( "g" ) ] ;"#;
+    assert_eq!(
+        check(source, 80),
+        "x = ( \"a\" | \"b\" ) + | ( \"c\" + ) + | \"d\" \"e\" \"f\" | [ \"g\" ] ;\n"
+    );
+}
+
+#[test]
+fn aligned_and_wrapped() {
+    let source = r#"letter = "A" | "B" | "C" | "D" | "E" | "F" | "G" | "H" | "I" | "J" | "K" | "L" | "M" | "N" ;
+term = "(" S rhs S ")" | "[" S rhs S "]" | "{" S rhs S "}" | terminal | identifier ;
+rhs = alternation ;"#;
+    let expected = r#"letter = "A" | "B" | "C" | "D" | "E" | "F" | "G"
+       | "H" | "I" | "J" | "K" | "L" | "M" | "N" ;
+term   = "(" S rhs S ")"
+       | "[" S rhs S "]"
+       | "{" S rhs S "}"
+       | terminal
+       | identifier ;
+rhs    = alternation ;
+"#;
+    assert_eq!(check(source, 50), expected);
+}
+
+#[test]
+fn comments() {
+    let source = r#"(* A list grammar. *)
+list   =   item   { "," item } ; (* trailing *)
+(* items *)
+
+
+item = "a" (* first *) | "b"
+  | ( "c" "d" ) ;
+
+x = ε ;
+(* last *)
+"#;
+    let expected = r#"(* A list grammar. *)
+list = item { "," item } ; (* trailing *)
+(* items *)
+
+item = "a" (* first *)
+     | "b"
+     | "c" "d" ;
+
+x = ε ;
+(* last *)
+"#;
+    assert_eq!(check(source, 80), expected);
+}
+
+#[test]
+fn width() {
+    for width in [50, 60, 80] {
+        let formatted = check(&example_source("mila"), width);
+        for line in formatted.lines() {
+            assert!(line.chars().count() <= width, "{}", line);
+        }
+    }
+}
+
+#[test]
+fn nested() {
+    let source = r#"call = name "(" [ argument { "," argument } ] ")" [ "as" type ] ";" ;
+letter = { "a" | "b" | "c" | "d" | "e" | "f" | "g" | "h" } ;
+group = ( "x" "y" | "z" { "," "w" } | "v" "u" "t" "s" ) "end" ;"#;
+    let expected = r#"call   = name "(" [ argument { "," argument } ]
+         ")" [ "as" type ] ";" ;
+letter = { "a" | "b" | "c" | "d" | "e" | "f" | "g"
+         | "h" } ;
+group  = ( "x" "y"
+         | "z" { "," "w" }
+         | "v" "u" "t" "s" ) "end" ;
+"#;
+    assert_eq!(check(source, 50), expected);
+}
+
+#[test]
+fn errors() {
+    for (source, error) in [
+        ("a = \"b\" ; (* open", "Unterminated comment at 1:11"),
+        ("a = \"b ;", "Unterminated terminal at 1:5"),
+        ("a = \"b\" ;\nc = # ;", "Syntax error at 2:5"),
+    ] {
+        assert_eq!(format_grammar(source, 80).unwrap_err(), error);
+        assert_eq!(parser::parse_source(source).unwrap_err(), error);
+        assert_eq!(tokeniser::try_tokenize(source).unwrap_err(), error);
+    }
+}
@@ -0,0 +1,77 @@
+//! Random sentences are sentences of the grammar, reproducible from the
+//! seed and bounded by the depth.
+
+mod common;
+
+use chomchom::cyk::Cyk;
+use chomchom::generate::Generator;
+use chomchom::grammar::Rstr;
+use chomchom::parser_generator::{Parser, Token, TokenTrait};
+use common::{chomsky, example, tokens};
+
+#[test]
+fn accepted_by_the_parser() {
+    let grammar = example("idk");
+    let parser = Parser::new(grammar.clone()).expect("Failed to build parser");
+    let mut generator = Generator::new(&grammar).with_seed(7).with_max_depth(8);
+    for _ in 0..50 {
+        let sentence = generator.sentence().unwrap();
+        let mut input = tokens(&sentence);
+        input.push(Token::make_eof(0, 0, 0));
+        let parsed = parser.parse_tokens(
+            &grammar.start_nonterm,
+            "",
+            Box::new(input.into_iter()),
+        );
+        assert!(parsed.is_ok(), "{:?}", sentence);
+    }
+}
+
+#[test]
+fn reproducible() {
+    let grammar = example("mila");
+    let sentences = |seed| {
+        let mut generator = Generator::new(&grammar).with_seed(seed);
+        (0..10)
+            .map(|_| generator.sentence().unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(sentences(1), sentences(1));
+    assert_ne!(sentences(1), sentences(2));
+}
+
+#[test]
+fn depth_and_weights() {
+    let grammar = chomsky(r#"e = e "+" e | "1" | "(" e ")" ;"#);
+    let cyk = Cyk::new(grammar.to_cnf());
+    for max_depth in 0..6 {
+        let mut generator = Generator::new(&grammar)
+            .with_seed(3)
+            .with_max_depth(max_depth);
+        for _ in 0..20 {
+            let sentence = generator.sentence().unwrap();
+            // Below `max_depth` only `E -> E__alt1` and `E__alt1 -> 1` are
+            // left.
+            assert!(sentence.len() < 1 << (max_depth + 2), "{:?}", sentence);
+            assert!(cyk.recognize(&tokens(&sentence)), "{:?}", sentence);
+        }
+    }
+    let alternatives: Vec<usize> = (0..grammar.rules.len())
+        .filter(|i| grammar.rules[*i].0.as_ref() == "E__alt1")
+        .collect();
+    let mut only_one = Generator::new(&grammar)
+        .with_weight(alternatives[0], 0.0)
+        .with_weight(alternatives[2], 0.0);
+    for _ in 0..10 {
+        assert_eq!(only_one.sentence().unwrap(), vec![Rstr::from("1")]);
+    }
+}
+
+#[test]
+fn unproductive() {
+    let grammar = chomsky(r#"a = "x" a | b ; b = "y" ;"#);
+    let mut generator = Generator::new(&grammar);
+    assert!(generator.sentence_from("a").is_some());
+    let grammar = chomsky(r#"a = "x" a ;"#);
+    assert!(Generator::new(&grammar).sentence().is_none());
+}
@@ -0,0 +1,165 @@
+//! The Greibach normal form of a grammar derives the same strings as the
+//! grammar, checked for every string up to a length. The converted grammars
+//! are compared with `tests/golden/gnf_*.parsingtbl`.
+
+use std::collections::{BTreeSet, HashMap};
+
+mod common;
+
+use chomchom::grammar::{GrammarChomsky, NT};
+use common::{check_golden, chomsky, example_source};
+
+/// The strings of at most `max` terminals derived by each nonterminal of
+/// a grammar, found by adding the strings every rule derives from the
+/// ones found so far until nothing changes.
+fn language(grammar: &GrammarChomsky, max: usize) -> BTreeSet<Vec<String>> {
+    let mut of: HashMap<&str, BTreeSet<Vec<String>>> = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (l, r) in grammar.rules.iter() {
+            let mut strings: BTreeSet<Vec<String>> = BTreeSet::from([vec![]]);
+            for x in r.iter() {
+                strings = match x {
+                    NT::Epsilon => strings,
+                    NT::Term(t) if t.is_empty() => strings,
+                    NT::Term(t) => strings
+                        .into_iter()
+                        .filter(|s| s.len() < max)
+                        .map(|mut s| {
+                            s.push(t.to_string());
+                            s
+                        })
+                        .collect(),
+                    NT::Non(n) => {
+                        // The strings `n` derives by length, so that only
+                        // pairs short enough are joined.
+                        let mut by_len = vec![vec![]; max + 1];
+                        for d in of.get(n.as_ref()).into_iter().flatten() {
+                            by_len[d.len()].push(d);
+                        }
+                        strings
+                            .iter()
+                            .flat_map(|s| {
+                                by_len[..=max - s.len()].iter().flatten().map(
+                                    |d| {
+                                        let mut s = s.clone();
+                                        s.extend(d.iter().cloned());
+                                        s
+                                    },
+                                )
+                            })
+                            .collect()
+                    }
+                };
+            }
+            let known = of.entry(l.as_ref()).or_default();
+            for s in strings {
+                changed |= known.insert(s);
+            }
+        }
+    }
+    of.remove(grammar.start_nonterm.as_ref())
+        .unwrap_or_default()
+}
+
+/// The strings of at most `max` terminals derived by a grammar in GNF. Each
+/// step of a leftmost derivation adds a terminal and every nonterminal
+/// still to expand adds at least one more, which bounds the search.
+fn gnf_language(gnf: &GrammarChomsky, max: usize) -> BTreeSet<Vec<String>> {
+    let mut language = BTreeSet::new();
+    let mut todo = vec![(vec![], vec![NT::Non(gnf.start_nonterm.clone())])];
+    while let Some((done, mut stack)) = todo.pop() {
+        let Some(NT::Non(n)) = stack.pop() else {
+            language.insert(done);
+            continue;
+        };
+        for (_, r) in gnf.rules.iter().filter(|(l, _)| *l == n) {
+            let mut done: Vec<String> = done.clone();
+            let mut stack = stack.clone();
+            match r.as_slice() {
+                [NT::Epsilon] => {}
+                [NT::Term(a), rest @ ..] => {
+                    done.push(a.to_string());
+                    stack.extend(rest.iter().rev().cloned());
+                }
+                _ => panic!("{} -> {:?} is not in GNF", n, r),
+            }
+            if done.len() + stack.len() <= max {
+                todo.push((done, stack));
+            }
+        }
+    }
+    language
+}
+
+fn check(name: &str, source: &str, max: usize) {
+    let grammar = chomsky(source);
+    let (gnf, steps) = grammar.to_gnf();
+    assert!(!steps.is_empty());
+    for (l, r) in gnf.rules.iter() {
+        let gnf_rule = match r.as_slice() {
+            [NT::Epsilon] => *l == gnf.start_nonterm,
+            [NT::Term(_), rest @ ..] => {
+                rest.iter().all(|x| matches!(x, NT::Non(_)))
+            }
+            _ => false,
+        };
+        assert!(gnf_rule, "{} -> {:?} is not in GNF", l, r);
+    }
+    let start = NT::Non(gnf.start_nonterm.clone());
+    if gnf
+        .rules
+        .iter()
+        .any(|(l, r)| *l == gnf.start_nonterm && r[0] == NT::Epsilon)
+    {
+        for (l, r) in gnf.rules.iter() {
+            assert!(!r.contains(&start), "{} -> {:?} uses the start", l, r);
+        }
+    }
+    check_golden(&format!("gnf_{}.parsingtbl", name), &gnf.to_parsingtbl());
+
+    let expected = language(&grammar, max);
+    assert!(!expected.is_empty());
+    assert_eq!(gnf_language(&gnf, max), expected);
+}
+
+#[test]
+fn ambiguous_left_recursion() {
+    check(
+        "ambiguous",
+        r#"e = e "+" e | e "*" e | "1" | "(" e ")" ;"#,
+        7,
+    );
+}
+
+#[test]
+fn indirect_left_recursion() {
+    check(
+        "indirect",
+        r#"a = b "x" | "y" ;
+           b = a "z" | "w" | [ "v" ] ;"#,
+        7,
+    );
+}
+
+#[test]
+fn nullable() {
+    check(
+        "nullable",
+        r#"s = [ "a" ] { "b" s "c" } [ "d" { "d" } ] ;"#,
+        6,
+    );
+}
+
+/// The start derives ε and is used in its own rules, so it gets a new
+/// start.
+#[test]
+fn nullable_start_used() {
+    check("nullable_start", r#"s = [ "a" "b" s ] ;"#, 8);
+}
+
+#[test]
+fn idk() {
+    check("idk", &example_source("idk"), 4);
+}
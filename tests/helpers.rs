@@ -0,0 +1,100 @@
+//! Helper nonterminals are named after their rule and construct, never
+//! collide with the grammar's own names and point back at their source.
+
+mod common;
+
+use chomchom::grammar::{GrammarChomsky, HelperKind};
+use common::chomsky;
+
+fn text<'a>(
+    grammar: &GrammarChomsky,
+    source: &'a str,
+    helper: &str,
+) -> &'a str {
+    let span = grammar.helpers[helper].span;
+    &source[span.start..span.end]
+}
+
+#[test]
+fn named_after_rule_and_construct() {
+    let source = r#"block = "{" { statement } "}" ;
+statement = "x" [ ";" ] | ( "y" | "z" ) + ;"#;
+    let grammar = chomsky(source);
+    let names: Vec<&str> = grammar.helpers.keys().map(|h| h.as_ref()).collect();
+    assert_eq!(
+        names,
+        [
+            "Block__rep1",
+            "Statement__alt1",
+            "Statement__alt2",
+            "Statement__opt1",
+            "Statement__plus1",
+            "Statement__rep1",
+        ]
+    );
+    let opt = &grammar.helpers["Statement__opt1"];
+    assert_eq!(opt.rule.as_ref(), "statement");
+    assert_eq!(opt.kind, HelperKind::Opt);
+    assert_eq!(text(&grammar, source, "Block__rep1"), "{ statement }");
+    assert_eq!(text(&grammar, source, "Statement__opt1"), r#"[ ";" ]"#);
+    assert_eq!(
+        text(&grammar, source, "Statement__alt1"),
+        r#""x" [ ";" ] | ( "y" | "z" ) +"#
+    );
+    assert_eq!(
+        text(&grammar, source, "Statement__plus1"),
+        r#"( "y" | "z" ) +"#
+    );
+    assert_eq!(
+        text(&grammar, source, "Statement__alt2"),
+        r#"( "y" | "z" )"#
+    );
+}
+
+#[test]
+fn avoid_user_names() {
+    let source = r#"a = [ "x" ] a__opt1 A__opt2 ;
+a__opt1 = "y" ;"#;
+    let grammar = chomsky(source);
+    assert!(!grammar.helpers.contains_key("A__opt1"));
+    assert!(!grammar.helpers.contains_key("A__opt2"));
+    assert_eq!(text(&grammar, source, "A__opt3"), r#"[ "x" ]"#);
+    let rules = grammar
+        .rules
+        .iter()
+        .filter(|(l, _)| l.as_ref() == "A__opt1");
+    assert_eq!(rules.count(), 1);
+}
+
+#[test]
+fn normal_forms() {
+    let grammar = chomsky(r#"s = "a" s "b" "c" | "d" ; t = t "+" s | s ;"#);
+    let cnf = grammar.to_cnf();
+    let kinds: Vec<(&str, HelperKind, &str)> = cnf
+        .helpers
+        .iter()
+        .map(|(h, x)| (h.as_ref(), x.kind, x.source.as_ref()))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ("S__chain1", HelperKind::Chain, r#"S "b" "c""#),
+            ("S__chain2", HelperKind::Chain, r#""b" "c""#),
+            ("S__term1", HelperKind::Term, r#""a""#),
+            ("S__term2", HelperKind::Term, r#""b""#),
+            ("S__term3", HelperKind::Term, r#""c""#),
+        ]
+    );
+
+    let grammar = chomsky(r#"t = t "+" "1" | "1" ;"#);
+    let (gnf, _) = grammar.to_gnf();
+    let tail = &gnf.helpers["T__tail1"];
+    assert_eq!(tail.kind, HelperKind::Tail);
+    assert_eq!(tail.rule.as_ref(), "t");
+    assert!(gnf.rules.iter().any(|(l, _)| l.as_ref() == "T__tail1"));
+
+    let grammar = chomsky(r#"s = [ "a" "b" s ] ;"#);
+    let (gnf, _) = grammar.to_gnf();
+    assert_eq!(gnf.start_nonterm.as_ref(), "S__start1");
+    assert_eq!(gnf.helpers["S__start1"].kind, HelperKind::Start);
+}
@@ -0,0 +1,177 @@
+//! Left factoring removes FIRST/FIRST conflicts and keeps the language,
+//! checked with the CYK parser of the original grammar.
+
+mod common;
+
+use chomchom::cyk::Cyk;
+use chomchom::grammar::{GrammarChomsky, HelperKind, NT};
+use chomchom::left_factoring::Factoring;
+use chomchom::parser_generator::Parser;
+use common::{chomsky, sentences, tokens};
+
+fn conflicts(grammar: &GrammarChomsky) -> usize {
+    let parser = Parser::new(grammar.clone()).expect("Failed to build parser");
+    parser.table().conflicts().len()
+}
+
+/// The factored grammar and what was factored, after checking that the
+/// conflicts are gone and the grammar accepts the same strings up to `max`
+/// terminals as `source`.
+fn check(source: &str, max: usize) -> (GrammarChomsky, Vec<Factoring>) {
+    let grammar = chomsky(source);
+    let (factored, report) = grammar.left_factor();
+    assert!(conflicts(&grammar) > 0);
+    assert_eq!(conflicts(&factored), 0, "{}", factored);
+    let cyk = Cyk::new(grammar.to_cnf());
+    let factored_cyk = Cyk::new(factored.to_cnf());
+    let mut accepted = 0;
+    for sentence in sentences(&grammar, max) {
+        let oracle = cyk.recognize(&tokens(&sentence));
+        let again = factored_cyk.recognize(&tokens(&sentence));
+        assert_eq!(again, oracle, "{:?}", sentence);
+        accepted += oracle as usize;
+    }
+    assert!(accepted > 0);
+    for factoring in report.iter() {
+        let helper = &factored.helpers[&factoring.helper];
+        assert_eq!(helper.kind, HelperKind::Fact);
+        assert_eq!(helper.rule, factoring.rule);
+    }
+    (factored, report)
+}
+
+fn rules(grammar: &GrammarChomsky) -> Vec<String> {
+    grammar
+        .rules
+        .iter()
+        .map(|(l, r)| {
+            let r: Vec<String> = r
+                .iter()
+                .map(|x| match x {
+                    NT::Term(t) => format!("\"{}\"", t),
+                    x => x.name().to_string(),
+                })
+                .collect();
+            format!("{} -> {}", l, r.join(" "))
+        })
+        .collect()
+}
+
+fn shown(report: &[Factoring]) -> Vec<String> {
+    report.iter().map(|f| f.to_string()).collect()
+}
+
+#[test]
+fn if_then_else() {
+    let source = r#"if = "if" expression "then" expression
+                       | "if" expression "then" expression "else" expression ;
+                    expression = "x" | if ;"#;
+    let grammar = chomsky(source);
+    let (factored, report) = grammar.left_factor();
+    assert_eq!(
+        shown(&report),
+        ["if: factored `if Expression then Expression` out of 2 alternatives \
+          into If__fact1"]
+    );
+    assert_eq!(
+        factored.helpers["If__fact1"].source.as_ref(),
+        r#"ε | "else" Expression"#
+    );
+    assert_eq!(
+        rules(&factored)[3..6],
+        [
+            r#"If__alt1 -> "if" Expression "then" Expression If__fact1"#,
+            "If__fact1 -> ε",
+            r#"If__fact1 -> "else" Expression"#,
+        ]
+    );
+    // Only the dangling else is left.
+    assert_eq!(conflicts(&factored), 1);
+
+    let (_, report) = check(
+        r#"if = "if" "x" "then" "x" | "if" "x" "then" "x" "else" "x" ;"#,
+        6,
+    );
+    assert_eq!(report[0].prefix.len(), 4);
+    let parser =
+        Parser::new(grammar.left_factor().0).expect("Failed to build parser");
+    assert_eq!(
+        parser.parse("ifxthenxelsex").unwrap().to_sexpr(),
+        concat!(
+            r#"(chomchom_root (If "if" (Expression "x") "then" "#,
+            r#"(Expression "x") "else" (Expression "x")))"#
+        )
+    );
+}
+
+#[test]
+fn two_prefixes() {
+    let (factored, report) =
+        check(r#"s = "a" "b" | "d" "e" | "a" "c" | "d" "f" ;"#, 3);
+    assert_eq!(
+        shown(&report),
+        [
+            "s: factored `a` out of 2 alternatives into S__fact1",
+            "s: factored `d` out of 2 alternatives into S__fact2",
+        ]
+    );
+    assert_eq!(
+        rules(&factored),
+        [
+            r#"S__alt1 -> "a" S__fact1"#,
+            r#"S__alt1 -> "d" S__fact2"#,
+            r#"S__fact1 -> "b""#,
+            r#"S__fact1 -> "c""#,
+            r#"S__fact2 -> "e""#,
+            r#"S__fact2 -> "f""#,
+            "S -> S__alt1",
+        ]
+    );
+}
+
+#[test]
+fn nested_prefixes() {
+    let (factored, report) =
+        check(r#"s = "a" "b" "c" | "a" "b" "d" | "a" "e" ;"#, 4);
+    assert_eq!(
+        shown(&report),
+        [
+            "s: factored `a` out of 3 alternatives into S__fact1",
+            "s: factored `b` out of 2 alternatives into S__fact2",
+        ]
+    );
+    assert_eq!(
+        rules(&factored),
+        [
+            r#"S__alt1 -> "a" S__fact1"#,
+            r#"S__fact1 -> "b" S__fact2"#,
+            r#"S__fact1 -> "e""#,
+            r#"S__fact2 -> "c""#,
+            r#"S__fact2 -> "d""#,
+            "S -> S__alt1",
+        ]
+    );
+}
+
+#[test]
+fn whole_rule() {
+    let (factored, report) = check(r#"s = "a" | "a" "b" ;"#, 3);
+    assert_eq!(report[0].prefix, [NT::Term("a".into())]);
+    assert_eq!(
+        rules(&factored),
+        [
+            r#"S__alt1 -> "a" S__fact1"#,
+            "S__fact1 -> ε",
+            r#"S__fact1 -> "b""#,
+            "S -> S__alt1",
+        ]
+    );
+}
+
+#[test]
+fn nothing_shared() {
+    let grammar = chomsky(r#"s = "a" "b" | "b" "a" ;"#);
+    let (factored, report) = grammar.left_factor();
+    assert!(report.is_empty());
+    assert_eq!(rules(&factored), rules(&grammar));
+}
@@ -0,0 +1,126 @@
+//! Removing left recursion keeps the language, checked with the CYK parser
+//! of the original grammar, and the trees restored from the new grammar are
+//! those of the original one.
+
+mod common;
+
+use chomchom::cyk::Cyk;
+use chomchom::grammar::{GrammarChomsky, HelperKind};
+use chomchom::parser_generator::{Parser, Token, TokenTrait};
+use common::{chomsky, sentences, tokens};
+
+/// The grammar without left recursion, after checking that it accepts the
+/// same strings up to `max` terminals as `source`.
+fn check(source: &str, max: usize) -> GrammarChomsky {
+    let grammar = chomsky(source);
+    assert!(!grammar.left_recursion().is_empty());
+    let removed = grammar.remove_left_recursion();
+    assert!(removed.left_recursion().is_empty(), "{}", removed);
+    let cyk = Cyk::new(grammar.to_cnf());
+    let removed_cyk = Cyk::new(removed.to_cnf());
+    let mut accepted = 0;
+    for sentence in sentences(&grammar, max) {
+        let oracle = cyk.recognize(&tokens(&sentence));
+        let again = removed_cyk.recognize(&tokens(&sentence));
+        assert_eq!(again, oracle, "{:?}", sentence);
+        accepted += oracle as usize;
+    }
+    assert!(accepted > 0);
+    removed
+}
+
+/// An LL(1) parser for `source` without its left recursion.
+fn ll1_parser(source: &str, max: usize) -> Parser {
+    let parser =
+        Parser::new(check(source, max)).expect("Failed to build parser");
+    assert!(parser.table().conflicts().is_empty(), "not LL(1)");
+    parser
+}
+
+/// The restored tree of `sentence`, if it parses.
+fn parse(parser: &Parser, sentence: &[impl AsRef<str>]) -> Option<String> {
+    let grammar = parser.grammar();
+    let mut input = tokens(sentence);
+    input.push(Token::make_eof(0, 0, 0));
+    let ast = parser
+        .parse_tokens(&grammar.start_nonterm, "", Box::new(input.into_iter()))
+        .ok()?;
+    Some(ast.restore_left_recursion(grammar).to_sexpr())
+}
+
+/// The restored tree of `sentence` is the CYK tree of the original grammar,
+/// which has no unit rules for the CYK parser to skip.
+fn check_tree(source: &str, parser: &Parser, sentence: &[&str]) {
+    let cyk = Cyk::new(chomsky(source).to_cnf());
+    let expected = cyk.parse(tokens(sentence)).unwrap().to_sexpr();
+    assert_eq!(parse(parser, sentence).unwrap(), expected);
+}
+
+#[test]
+fn direct() {
+    let source = r#"e = e "+" t | e "-" t | t ; t = "1" | "(" e ")" ;"#;
+    let parser = ll1_parser(source, 6);
+    let removed = parser.grammar();
+    let tails: Vec<(&str, &str)> = removed
+        .tails
+        .iter()
+        .map(|(t, h)| (t.as_ref(), h.as_ref()))
+        .collect();
+    assert_eq!(tails, [("E__tail1", "E")]);
+    let helper = &removed.helpers["E__tail1"];
+    assert_eq!(helper.kind, HelperKind::Tail);
+    assert_eq!(helper.rule.as_ref(), "e");
+    assert_eq!(helper.source.as_ref(), r#""+" T | "-" T"#);
+    assert_eq!(
+        parse(&parser, &["1", "-", "1", "+", "1"]).unwrap(),
+        r#"(chomchom_root (E (E (E (T "1")) "-" (T "1")) "+" (T "1")))"#
+    );
+    assert_eq!(
+        parse(&parser, &["(", "1", ")"]).unwrap(),
+        r#"(chomchom_root (E (T "(" (E (T "1")) ")")))"#
+    );
+}
+
+#[test]
+fn indirect() {
+    let removed = check(
+        r#"a = b "x" | "y" ;
+           b = a "z" | "w" ;"#,
+        7,
+    );
+    let names: Vec<&str> = removed.tails.keys().map(|t| t.as_ref()).collect();
+    assert_eq!(names, ["B__tail1"]);
+    assert_eq!(removed.helpers["B__tail1"].kind, HelperKind::Tail);
+    assert_eq!(removed.helpers["B__tail1"].rule.as_ref(), "b");
+}
+
+#[test]
+fn round_trip() {
+    let source = r#"e = e "+" "1" | e "-" "1" | "1" | "(" e ")" ;"#;
+    let parser = ll1_parser(source, 6);
+    check_tree(source, &parser, &["1"]);
+    check_tree(source, &parser, &["1", "+", "1", "-", "1"]);
+    check_tree(source, &parser, &["(", "1", "-", "1", ")", "+", "1"]);
+}
+
+#[test]
+fn inlined_head() {
+    let source = r#"s = "[" _e "]" ; _e = _e "+" "1" | "1" ;"#;
+    let parser = ll1_parser(source, 6);
+    let tail = &parser.grammar().helpers["_e__tail1"];
+    assert_eq!(tail.rule.as_ref(), "_e");
+    check_tree(source, &parser, &["[", "1", "]"]);
+    check_tree(source, &parser, &["[", "1", "+", "1", "+", "1", "]"]);
+    assert_eq!(
+        parse(&parser, &["[", "1", "+", "1", "]"]).unwrap(),
+        r#"(chomchom_root (S "[" "1" "+" "1" "]"))"#
+    );
+
+    let source = r#"_e = _e "+" "1" | "1" ;"#;
+    let parser = ll1_parser(source, 5);
+    check_tree(source, &parser, &["1", "+", "1", "+", "1"]);
+    assert_eq!(
+        parse(&parser, &["1", "+", "1"]).unwrap(),
+        r#"(chomchom_root "1" "+" "1")"#
+    );
+}
@@ -0,0 +1,147 @@
+//! One grammar per lint, and the levels a [`LintConfig`] gives them.
+
+mod common;
+
+use chomchom::lint::{Diagnostic, LintCode, LintConfig, Severity};
+use chomchom::parser_generator::Parser;
+use common::chomsky;
+
+fn lint(source: &str) -> Vec<Diagnostic> {
+    chomsky(source).lint(&LintConfig::default())
+}
+
+fn show(diagnostics: &[Diagnostic]) -> Vec<String> {
+    diagnostics.iter().map(|d| d.to_string()).collect()
+}
+
+#[test]
+fn clean() {
+    assert!(lint(r#"s = "a" [ s ] ;"#).is_empty());
+}
+
+#[test]
+fn undefined_nonterminal() {
+    assert_eq!(
+        show(&lint(r#"s = "a" t ;"#)),
+        [
+            "error[G001]: `T` has no rules, used in `s = ( \"a\" t )`",
+            "warning[G003]: `S` derives no string of terminals"
+        ]
+    );
+}
+
+#[test]
+fn unreachable_rule() {
+    assert_eq!(
+        show(&lint(r#"s = "a" ; t = "a" ;"#)),
+        ["warning[G002]: `T` can not be reached from `S`"]
+    );
+}
+
+#[test]
+fn unproductive_nonterminal() {
+    assert_eq!(
+        show(&lint(r#"s = "a" | "b" t ; t = "c" t ;"#)),
+        ["warning[G003]: `T` derives no string of terminals"]
+    );
+}
+
+#[test]
+fn duplicate_alternative() {
+    assert_eq!(
+        show(&lint(r#"s = "a" | "b" | "a" ;"#)),
+        ["warning[G004]: the alternative `a` is repeated in `( \"a\" | \"b\" | \"a\" )` in `s`"]
+    );
+}
+
+#[test]
+fn unused_terminal() {
+    assert_eq!(
+        show(&lint(r#"s = "a" ; t = "b" ;"#)),
+        [
+            "warning[G002]: `T` can not be reached from `S`",
+            "warning[G005]: \"b\" is only used in unreachable rules, in `t = \"b\"`"
+        ]
+    );
+}
+
+#[test]
+fn left_recursion() {
+    assert_eq!(
+        show(&lint(r#"s = s "a" | "b" ;"#)),
+        ["warning[G006]: `S` is left-recursive"]
+    );
+    assert_eq!(
+        show(&lint(r#"s = t "a" | "b" ; t = s "c" ;"#)),
+        ["warning[G006]: `S`, `T` are left-recursive"]
+    );
+}
+
+#[test]
+fn codes_and_names() {
+    for code in LintCode::ALL {
+        assert_eq!(code.code().parse(), Ok(code));
+        assert_eq!(code.code().to_lowercase().parse(), Ok(code));
+        assert_eq!(code.name().parse(), Ok(code));
+    }
+    assert!("G007".parse::<LintCode>().is_err());
+}
+
+#[test]
+fn levels() {
+    let grammar = chomsky(r#"s = "a" t | s "b" | "d" ; u = "c" ;"#);
+    let default = LintConfig::default();
+    assert_eq!(
+        default.severity(LintCode::UndefinedNonterminal),
+        Severity::Error
+    );
+    assert_eq!(default.severity(LintCode::LeftRecursion), Severity::Warn);
+    let severities = |config: &LintConfig| -> Vec<(LintCode, Severity)> {
+        grammar
+            .lint(config)
+            .iter()
+            .map(|d| (d.code, d.severity))
+            .collect()
+    };
+    assert_eq!(
+        severities(&default),
+        [
+            (LintCode::UndefinedNonterminal, Severity::Error),
+            (LintCode::UnreachableRule, Severity::Warn),
+            (LintCode::UnusedTerminal, Severity::Warn),
+            (LintCode::LeftRecursion, Severity::Warn),
+        ]
+    );
+    let config = LintConfig::default()
+        .warn(LintCode::UndefinedNonterminal)
+        .deny(LintCode::UnreachableRule);
+    assert_eq!(
+        severities(&config),
+        [
+            (LintCode::UndefinedNonterminal, Severity::Warn),
+            (LintCode::UnreachableRule, Severity::Error),
+            (LintCode::UnusedTerminal, Severity::Warn),
+            (LintCode::LeftRecursion, Severity::Warn),
+        ]
+    );
+}
+
+#[test]
+fn parser_errors() {
+    let source = r#"s = "a" [ s ] ; u = "c" ;"#;
+    assert!(Parser::new(chomsky(source)).is_ok());
+    let deny = LintConfig::default().deny(LintCode::UnreachableRule);
+    let err = Parser::with_lints(chomsky(source), &["s"], &deny).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "error[G002]: `U` can not be reached from `S`"
+    );
+    let source = r#"s = "a" t ; t = "b" ;"#;
+    assert!(Parser::with_lints(chomsky(source), &["s"], &deny).is_ok());
+    let err = Parser::new(chomsky(r#"s = "a" t ;"#)).unwrap_err();
+    assert!(err.to_string().starts_with("error[G001]"), "{}", err);
+    let allow = LintConfig::default().warn(LintCode::UndefinedNonterminal);
+    assert!(
+        Parser::with_lints(chomsky(r#"s = "a" | t ;"#), &["s"], &allow).is_ok()
+    );
+}
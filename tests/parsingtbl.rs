@@ -0,0 +1,59 @@
+//! Golden tests for the parsingtbl export of the example grammars. Run
+//! with `UPDATE_GOLDEN=1` to rewrite the files in `tests/golden` after an
+//! intended change.
+
+mod common;
+
+use common::{check_golden, chomsky, example};
+
+fn check(name: &str) {
+    check_exported(name, &example(name).to_parsingtbl());
+}
+
+fn check_exported(name: &str, exported: &str) {
+    check_golden(&format!("{}.parsingtbl", name), exported);
+}
+
+#[test]
+fn idk() {
+    check("idk");
+}
+
+#[test]
+fn mila() {
+    check("mila");
+}
+
+#[test]
+fn test() {
+    check("test");
+}
+
+#[test]
+fn long() {
+    check("long");
+}
+
+/// Terminals that look like nonterminals, contain spaces or the `|` and
+/// `->` of the format, or need escaping.
+#[test]
+fn quoted() {
+    let grammar = chomsky(
+        r#"assign = Number ":=" value [ " " ] ;
+           Number = "Number" | "\" ;
+           value = "|" | "->" | "ε" | ε ;"#,
+    );
+    check_exported("quoted", &grammar.to_parsingtbl());
+}
+
+#[test]
+fn no_colours() {
+    for name in ["idk", "mila", "test", "long", "quoted"] {
+        let golden = std::fs::read_to_string(format!(
+            "tests/golden/{}.parsingtbl",
+            name
+        ))
+        .expect("Missing golden file");
+        assert!(!golden.contains('\x1b'), "{} has escape codes", name);
+    }
+}
@@ -0,0 +1,138 @@
+//! Walking and querying parsed trees.
+
+mod common;
+
+use chomchom::parser_generator::{Parser, Token, AST};
+use common::chomsky;
+
+/// `(chomchom_root (List "[" (Item "x") (Item (List "[" (Item "x") "]"))
+/// "]"))`
+fn tree() -> AST<Token> {
+    let grammar = chomsky(r#"list = "[" { item } "]" ; item = "x" | list ;"#);
+    let parser = Parser::new(grammar).expect("Failed to build parser");
+    parser.parse("[x[x]]").expect("Failed to parse")
+}
+
+fn names<'a>(nodes: impl IntoIterator<Item = &'a AST<Token>>) -> Vec<String> {
+    nodes.into_iter().map(|n| n.as_str().to_string()).collect()
+}
+
+fn select(tree: &AST<Token>, selector: &str) -> Vec<String> {
+    names(tree.select(selector).expect("Invalid selector"))
+}
+
+#[test]
+fn orders() {
+    let tree = tree();
+    assert_eq!(
+        names(tree.preorder()),
+        [
+            "chomchom_root",
+            "List",
+            "[",
+            "Item",
+            "x",
+            "Item",
+            "List",
+            "[",
+            "Item",
+            "x",
+            "]",
+            "]"
+        ]
+    );
+    assert_eq!(
+        names(tree.postorder()),
+        [
+            "[",
+            "x",
+            "Item",
+            "[",
+            "x",
+            "Item",
+            "]",
+            "List",
+            "Item",
+            "]",
+            "List",
+            "chomchom_root"
+        ]
+    );
+    let by_depth: Vec<(usize, String)> = tree
+        .by_depth()
+        .map(|(d, n)| (d, n.as_str().to_string()))
+        .collect();
+    let depths: Vec<usize> = by_depth.iter().map(|(d, _)| *d).collect();
+    assert_eq!(depths, [0, 1, 2, 2, 2, 2, 3, 3, 4, 4, 4, 5]);
+    assert_eq!(by_depth[7].1, "List");
+    assert_eq!(by_depth[11].1, "x");
+}
+
+#[test]
+fn names_and_find_all() {
+    let tree = tree();
+    assert_eq!(tree.find_all("item").len(), 3);
+    assert_eq!(tree.find_all("Item").len(), 3);
+    assert!(tree.find_all("x").is_empty());
+    let token = tree.preorder().find(|n| n.is_token()).unwrap();
+    assert!(!token.has_name("["));
+    assert!(token.children().is_empty());
+}
+
+#[test]
+fn selectors() {
+    let tree = tree();
+    assert_eq!(select(&tree, "list"), ["List", "List"]);
+    assert_eq!(select(&tree, "list > item"), ["Item", "Item", "Item"]);
+    assert_eq!(select(&tree, "item list"), ["List"]);
+    assert_eq!(select(&tree, "list list item").len(), 1);
+    assert_eq!(select(&tree, r#"item > "x""#), ["x", "x"]);
+    assert!(select(&tree, r#"list > "x""#).is_empty());
+    assert_eq!(select(&tree, r#"list > * > "x""#), ["x", "x"]);
+    assert_eq!(select(&tree, r#"item>list>"[""#), ["["]);
+    assert_eq!(select(&tree, r#""]""#), ["]", "]"]);
+    assert_eq!(select(&tree, "*").len(), 12);
+    assert!(select(&tree, "nothing").is_empty());
+}
+
+#[test]
+fn malformed_selectors() {
+    let tree = tree();
+    for selector in
+        ["", "   ", ">", "> item", "list >", "list > > item", r#""x"#]
+    {
+        assert!(tree.select(selector).is_err(), "{:?}", selector);
+    }
+}
+
+#[test]
+fn cursor() {
+    let tree = tree();
+    let mut cursor = tree.cursor();
+    assert_eq!(cursor.depth(), 0);
+    assert!(cursor.parent().is_none());
+    assert!(!cursor.goto_parent());
+    assert!(!cursor.goto_next_sibling());
+    assert!(cursor.goto_first_child());
+    assert!(cursor.goto_first_child());
+    assert_eq!(cursor.node().as_str().as_ref(), "[");
+    assert!(!cursor.goto_first_child());
+    assert!(!cursor.goto_prev_sibling());
+    let mut siblings = vec![];
+    while cursor.goto_next_sibling() {
+        siblings.push(cursor.node().as_str().to_string());
+    }
+    assert_eq!(siblings, ["Item", "Item", "]"]);
+    assert_eq!(cursor.node().as_str().as_ref(), "]");
+    assert_eq!(cursor.depth(), 2);
+    assert!(cursor.goto_prev_sibling());
+    assert!(cursor.goto_first_child());
+    assert_eq!(cursor.node().as_str().as_ref(), "List");
+    assert_eq!(cursor.parent().unwrap().as_str().as_ref(), "Item");
+    assert!(cursor.goto_parent());
+    assert!(cursor.goto_parent());
+    assert_eq!(cursor.node().as_str().as_ref(), "List");
+    assert!(cursor.goto_parent());
+    assert!(!cursor.goto_parent());
+    assert_eq!(cursor.depth(), 0);
+}
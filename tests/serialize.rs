@@ -0,0 +1,75 @@
+//! Printing parsed trees as JSON, S-expressions and Graphviz, with tokens
+//! that need escaping.
+
+mod common;
+
+use chomchom::parser_generator::{Parser, Token, AST};
+use common::chomsky;
+
+fn token(s: &str) -> AST<Token> {
+    AST::Token(Token::from(s.to_string()))
+}
+
+/// A node over the tokens `a"b`, `c\d` and a newline.
+fn tree() -> AST<Token> {
+    AST::Node {
+        name: "Text".into(),
+        children: vec![token("a\"b"), token("c\\d"), token("\n")],
+    }
+}
+
+#[test]
+fn sexpr() {
+    assert_eq!(tree().to_sexpr(), r#"(Text "a\"b" "c\\d" "\n")"#);
+    let parser = Parser::new(chomsky(r#"s = "(" [ s ] ")" ;"#)).unwrap();
+    let parsed = parser.parse("(())").unwrap();
+    assert_eq!(
+        parsed.to_sexpr(),
+        r#"(chomchom_root (S "(" (S "(" ")") ")"))"#
+    );
+}
+
+#[test]
+fn json() {
+    // Hand made tokens all start at 0.
+    assert_eq!(
+        tree().to_json(),
+        concat!(
+            r#"{"name":"Text","span":{"start":0,"end":1},"children":["#,
+            r#"{"token":"a\"b","span":{"start":0,"end":3},"line":0,"column":0},"#,
+            r#"{"token":"c\\d","span":{"start":0,"end":3},"line":0,"column":0},"#,
+            r#"{"token":"\n","span":{"start":0,"end":1},"line":0,"column":0}]}"#
+        )
+    );
+    let empty: AST<Token> = AST::Node {
+        name: "E".into(),
+        children: vec![],
+    };
+    assert_eq!(empty.to_json(), r#"{"name":"E","span":null,"children":[]}"#);
+}
+
+#[test]
+fn spans() {
+    let parser = Parser::new(chomsky(r#"s = "(" [ s ] ")" ;"#)).unwrap();
+    let parsed = parser.parse("(())").unwrap();
+    assert_eq!(parsed.span(), Some((0, 4)));
+    let inner = &parsed.find_all("s")[1];
+    assert_eq!(inner.span(), Some((1, 3)));
+}
+
+#[test]
+fn dot() {
+    assert_eq!(
+        tree().to_dot(),
+        r#"digraph AST {
+    n0 [label="Text"];
+    n1 [label="a\"b", shape=box];
+    n0 -> n1;
+    n2 [label="c\\d", shape=box];
+    n0 -> n2;
+    n3 [label="\n", shape=box];
+    n0 -> n3;
+}
+"#
+    );
+}
@@ -0,0 +1,113 @@
+//! Rule annotations shape the parsed tree and survive printing the grammar
+//! back.
+
+mod common;
+
+use chomchom::parser;
+use chomchom::parser_generator::Parser;
+use common::{chomsky, ebnf};
+
+fn sexpr(grammar: &str, input: &str) -> String {
+    let parser = Parser::new(chomsky(grammar)).expect("Failed to build parser");
+    parser.parse(input).expect("Failed to parse").to_sexpr()
+}
+
+#[test]
+fn plain() {
+    assert_eq!(
+        sexpr(
+            r#"list = "[" items "]" ; items = "x" { "," "x" } ;"#,
+            "[x,x]"
+        ),
+        r#"(chomchom_root (List "[" (Items "x" "," "x") "]"))"#
+    );
+}
+
+#[test]
+fn inline() {
+    assert_eq!(
+        sexpr(
+            r#"list = "[" items "]" ; @inline items = "x" { "," "x" } ;"#,
+            "[x,x]"
+        ),
+        r#"(chomchom_root (List "[" "x" "," "x" "]"))"#
+    );
+    // Leading underscores inline too.
+    assert_eq!(
+        sexpr(r#"list = "[" _items "]" ; _items = "x" ;"#, "[x]"),
+        r#"(chomchom_root (List "[" "x" "]"))"#
+    );
+}
+
+#[test]
+fn collapse() {
+    let grammar = r#"@collapse sum = term { "+" term } ; term = "1" ;"#;
+    assert_eq!(sexpr(grammar, "1"), r#"(chomchom_root (Term "1"))"#);
+    assert_eq!(
+        sexpr(grammar, "1+1"),
+        r#"(chomchom_root (Sum (Term "1") "+" (Term "1")))"#
+    );
+}
+
+#[test]
+fn drop() {
+    assert_eq!(
+        sexpr(r#"@drop "(" ")" group = "(" "x" ")" ;"#, "(x)"),
+        r#"(chomchom_root (Group "x"))"#
+    );
+    assert_eq!(
+        sexpr(r#"@drop group = "(" "x" ")" ;"#, "(x)"),
+        r#"(chomchom_root (Group))"#
+    );
+    // Only the tokens of the annotated rule itself are dropped.
+    assert_eq!(
+        sexpr(r#"@drop group = "(" x ")" ; x = "(" ")" ;"#, "(())"),
+        r#"(chomchom_root (Group (X "(" ")")))"#
+    );
+}
+
+#[test]
+fn keep() {
+    assert_eq!(
+        sexpr(r#"@drop @keep "x" group = "(" "x" ")" ;"#, "(x)"),
+        r#"(chomchom_root (Group "x"))"#
+    );
+    assert_eq!(
+        sexpr(r#"@drop "x" @keep "x" group = "(" "x" ")" ;"#, "(x)"),
+        r#"(chomchom_root (Group "(" "x" ")"))"#
+    );
+}
+
+#[test]
+fn invalid() {
+    for source in [
+        r#"@inline "x" a = "b" ;"#,
+        r#"@collapse "x" a = "b" ;"#,
+        r#"@unknown a = "b" ;"#,
+    ] {
+        assert!(parser::parse_source(source).is_err(), "{}", source);
+    }
+}
+
+#[test]
+fn to_ebnf_round_trip() {
+    let sources = [
+        std::fs::read_to_string("tests/mila_annotated.gram")
+            .expect("Failed to read grammar."),
+        r#"b = a ;
+           @inline @collapse @drop "(" @keep "," "x"
+           a = "(" "x" { "," "x" } ")" ;
+           @drop
+           c = "y" ;"#
+            .to_string(),
+    ];
+    for source in sources.iter() {
+        let original = ebnf(source);
+        let printed = original.to_ebnf();
+        let again = ebnf(&printed);
+        assert_eq!(again.start_nonterm, original.start_nonterm, "{}", printed);
+        assert_eq!(again.shapes, original.shapes, "{}", printed);
+        assert_eq!(again.rules, original.rules, "{}", printed);
+        assert_eq!(again.to_ebnf(), printed);
+    }
+}
@@ -0,0 +1,58 @@
+//! Converting a grammar to BNF and simplifying it back gives the EBNF
+//! grammar it came from.
+
+mod common;
+
+use chomchom::grammar::GrammarEBNF;
+use common::{ebnf, example_source};
+
+fn example(name: &str) -> GrammarEBNF {
+    ebnf(&example_source(name))
+}
+
+#[test]
+fn idk_round_trip() {
+    let original = example("idk");
+    let simplified = original.to_chomsky().to_ebnf_grammar();
+    assert_eq!(simplified.start_nonterm, original.start_nonterm);
+    assert_eq!(simplified.rules, original.rules);
+}
+
+#[test]
+fn mila_round_trip() {
+    let original = example("mila");
+    let simplified = original.to_chomsky().to_ebnf_grammar();
+    assert_eq!(simplified.shapes, original.shapes);
+    for (name, rule) in original.rules.iter() {
+        if name.as_ref() != "expression" {
+            assert_eq!(&simplified.rules[name], rule, "{}", name);
+        }
+    }
+    // `a | ε` comes back as `[ a ]`.
+    let expected =
+        ebnf(r#"expression = [ block | if | while | for | operationL7 ] ;"#);
+    assert_eq!(simplified.rules["expression"], expected.rules["expression"]);
+    let again = simplified.to_chomsky().to_ebnf_grammar();
+    assert_eq!(again.rules, simplified.rules);
+}
+
+#[test]
+fn tail_recursion() {
+    let grammar = ebnf(
+        r#"list = item "," list | item ;
+           items = item items | item ;
+           tail = "x" tail | ε ;
+           not = "!" not | item ;
+           item = "i" opt ;
+           opt = "o" | ε ;"#,
+    );
+    let expected = ebnf(
+        r#"list = { item "," } item ;
+           items = item + ;
+           tail = { "x" } ;
+           not = "!" not | item ;
+           item = "i" opt ;
+           opt = [ "o" ] ;"#,
+    );
+    assert_eq!(grammar.to_chomsky().to_ebnf_grammar().rules, expected.rules);
+}
@@ -0,0 +1,54 @@
+//! One parser shared between threads parses like it does on one thread.
+
+mod common;
+
+use std::sync::Arc;
+use std::thread;
+
+use chomchom::parser_generator::{ParseResult, Parser, Token};
+use common::chomsky;
+
+const GRAMMAR: &str = r#"program = "begin" expr "end" ;
+                         expr = term { "+" term } ;
+                         term = "1" | "(" expr ")" ;"#;
+
+const INPUTS: [&str; 5] = ["begin1end", "begin(1+1)+1end", "1+1", "(1", ""];
+
+fn show(result: ParseResult<Token>) -> String {
+    match result {
+        Ok(ast) => ast.to_sexpr(),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// The tree or error of every input, from the first entry and from `expr`.
+fn results(parser: &Parser) -> Vec<String> {
+    INPUTS
+        .iter()
+        .flat_map(|input| {
+            [
+                show(parser.parse(input)),
+                show(parser.parse_from("expr", input)),
+            ]
+        })
+        .collect()
+}
+
+#[test]
+fn shared_parser() {
+    let parser = Parser::with_entries(chomsky(GRAMMAR), &["program", "expr"])
+        .expect("Failed to build parser");
+    let expected = results(&parser);
+    let parser = Arc::new(parser);
+    thread::scope(|scope| {
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let parser = Arc::clone(&parser);
+                scope.spawn(move || results(&parser))
+            })
+            .collect();
+        for t in threads {
+            assert_eq!(t.join().unwrap(), expected);
+        }
+    });
+}
@@ -0,0 +1,81 @@
+//! Tracing the LL(1) driver step by step.
+
+mod common;
+
+use chomchom::parser_generator::Parser;
+use chomchom::trace::TraceAction;
+use common::chomsky;
+
+fn parser() -> Parser {
+    Parser::new(chomsky(r#"s = "(" [ s ] ")" ;"#)).unwrap()
+}
+
+#[test]
+fn steps() {
+    let parser = parser();
+    let (parsed, trace) = parser.trace("()");
+    assert!(parsed.is_ok());
+    let actions: Vec<&TraceAction> =
+        trace.steps.iter().map(|s| &s.action).collect();
+    assert_eq!(actions.len(), 6);
+    assert!(matches!(actions[0], TraceAction::Expand { nonterm, .. }
+        if nonterm.as_ref() == "S"));
+    assert_eq!(actions[1], &TraceAction::Match("(".into()));
+    assert!(matches!(actions[2], TraceAction::Expand { nonterm, .. }
+        if nonterm.as_ref() == "S__opt1"));
+    assert_eq!(actions[3], &TraceAction::PopEpsilon);
+    assert_eq!(actions[4], &TraceAction::Match(")".into()));
+    assert_eq!(actions[5], &TraceAction::Accept);
+    assert_eq!(trace.steps[0].stack, ["S".into()]);
+    assert_eq!(trace.steps[0].input.as_ref(), "()");
+    assert_eq!(trace.steps[4].input.as_ref(), ")");
+}
+
+#[test]
+fn failing() {
+    let parser = parser();
+    let (parsed, trace) = parser.trace("(()");
+    assert!(parsed.is_err());
+    assert_eq!(trace.steps.last().unwrap().action, TraceAction::Error);
+    let (parsed, trace) = parser.trace(")");
+    assert!(parsed.is_err());
+    assert_eq!(trace.steps.len(), 1);
+    assert_eq!(trace.steps[0].action, TraceAction::Error);
+}
+
+#[test]
+fn table() {
+    let parser = parser();
+    let (_, trace) = parser.trace("()");
+    assert_eq!(
+        trace.table(parser.analysis()).to_string(),
+        "Stack         | Input | Action
+--------------+-------+-------
+S $           |   ()$ | expand (2) S -> ( S__opt1 )
+( S__opt1 ) $ |   ()$ | match (
+S__opt1 ) $   |    )$ | expand (1) S__opt1 -> ε
+ε ) $         |    )$ | pop ε
+) $           |    )$ | match )
+$             |     $ | accept
+"
+    );
+}
+
+#[test]
+fn json() {
+    let parser = parser();
+    let (_, trace) = parser.trace("()");
+    let json = trace.to_json(parser.analysis());
+    assert!(json.starts_with(concat!(
+        r#"[{"stack":["S"],"input":"()","action":"expand","#,
+        r#""nonterm":"S","rule":2,"production":"S -> ( S__opt1 )"},"#,
+        r#"{"stack":["(","S__opt1",")"],"input":"()","#,
+        r#""action":"match","terminal":"("},"#
+    )));
+    assert!(json.ends_with(r#"{"stack":[],"input":"","action":"accept"}]"#));
+    let (_, trace) = parser.trace("\"");
+    assert_eq!(
+        trace.to_json(parser.analysis()),
+        r#"[{"stack":["S"],"input":"\"","action":"error"}]"#
+    );
+}